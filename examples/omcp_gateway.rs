@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use log::LevelFilter;
+use omcp::{
+    client::{builder::OMcpClientBuilder, types::OMcpServerType},
+    config::{McpConfig, McpServerConfig, StdioServerConfig},
+    error::{Error, Result},
+    gateway::Gateway,
+    server::http::HttpServerOptions,
+};
+use rstaples::logging::StaplesLogger;
+
+#[derive(Parser)]
+#[command(version, about = "Re-exports an MCP server over another transport", long_about = None)]
+struct UserArgs {
+    /// mcpServers JSON or TOML file
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Server to re-export from the config file, required when it has more than one
+    #[arg(short, long)]
+    server: Option<String>,
+
+    /// Upstream SSE url
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// Serve Streamable HTTP on this address instead of stdio
+    #[arg(long)]
+    listen: Option<String>,
+
+    /// Serve HTTP+SSE on this address instead of stdio
+    #[arg(long)]
+    sse_listen: Option<String>,
+
+    /// Debug
+    #[arg(short, long)]
+    debug: bool,
+
+    /// Upstream command and its arguments
+    #[arg(last = true)]
+    command: Vec<String>,
+}
+
+async fn connect(args: &UserArgs) -> Result<Gateway> {
+    if let Some(url) = &args.url {
+        let builder = OMcpClientBuilder::new(OMcpServerType::Sse).with_sse_url(url);
+        return Gateway::connect_sse(builder).await;
+    }
+
+    if let Some((command, command_args)) = args.command.split_first() {
+        let config = StdioServerConfig {
+            command: command.clone(),
+            args: command_args.to_vec(),
+            ..Default::default()
+        };
+        return Gateway::spawn(&config).await;
+    }
+
+    let config = match &args.config {
+        Some(v) => McpConfig::load(v)?,
+        None => return Err(Error::ParameterNotFound),
+    };
+
+    //
+    // picking one of several at random would be a surprise, --server says
+    // which
+    //
+    let server: &McpServerConfig = match &args.server {
+        Some(name) => config.servers.get(name).ok_or(Error::NotFound)?,
+        None if config.servers.len() > 1 => {
+            let names: Vec<&str> = config.servers.keys().map(|v| v.as_str()).collect();
+            eprintln!("--server is required, the config has {}", names.join(", "));
+            return Err(Error::ParameterNotFound);
+        }
+        None => config.servers.values().next().ok_or(Error::Empty)?,
+    };
+
+    Gateway::connect(server).await
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = UserArgs::parse();
+
+    let level = match args.debug {
+        true => LevelFilter::Debug,
+        false => LevelFilter::Info,
+    };
+
+    //
+    // stdout belongs to the stdio transport
+    //
+    StaplesLogger::new().with_log_level(level).with_stderr().start()?;
+
+    let gateway = connect(&args).await?;
+    let mut server = gateway.server();
+
+    match (&args.listen, &args.sse_listen) {
+        (Some(addr), _) => server.http_loop(addr, HttpServerOptions::new()).await,
+        (None, Some(addr)) => server.sse_loop(addr).await,
+        (None, None) => server.io_loop().await,
+    }
+}
@@ -1,18 +1,19 @@
 use async_trait::async_trait;
 use omcp::{
     error::{Error, Result},
-    server::matrix::OmcpServer,
+    server::{context::ToolContext, matrix::OmcpServer},
     types::{BakedMcpToolTrait, McpParams},
 };
 use rstaples::logging::StaplesLogger;
 
 struct UnameTool {}
 
-#[async_trait(?Send)]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl BakedMcpToolTrait for UnameTool {
     type Error = Error;
 
-    async fn call(&mut self, _params: &McpParams) -> Result<String> {
+    async fn call(&self, _ctx: &ToolContext, _params: &McpParams) -> Result<String> {
         unimplemented!()
     }
 }
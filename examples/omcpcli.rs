@@ -6,9 +6,9 @@ use log::{
     LevelFilter, {error, info},
 };
 use omcp::{
-    client::{baked::BakedClient, builder::OMcpClientBuilder, types::OMcpServerType},
+    client::{baked::BakedClient, builder::OMcpClientBuilder, io::OMcpClientTrait, types::OMcpServerType},
     error::{Error, Result},
-    server::{stdio::StdioServer, types::OMcpServerTrait},
+    server::context::ToolContext,
     types::{BakedMcpToolTrait, McpParams},
 };
 
@@ -107,11 +107,12 @@ impl BakedUname {
     }
 }
 
-#[async_trait(?Send)]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl BakedMcpToolTrait for BakedUname {
     type Error = Error;
 
-    async fn call(&mut self, _params: &McpParams) -> Result<String> {
+    async fn call(&self, _ctx: &ToolContext, _params: &McpParams) -> Result<String> {
         self.to_json()
     }
 }
@@ -159,7 +160,7 @@ async fn main_list_tool(args: UserArgsDump) -> Result<()> {
         None => builder,
     };
 
-    let mut client = builder.build();
+    let mut client = builder.build()?;
 
     client.connect().await?;
 
@@ -214,7 +215,7 @@ async fn main_call(args: UserArgsCall) -> Result<()> {
         None => builder,
     };
 
-    let mut client = builder.build();
+    let mut client = builder.build()?;
 
     client.connect().await?;
 
@@ -258,13 +259,15 @@ async fn main_baked_uname() -> Result<()> {
 }
 
 async fn main_list_tools_std(args: UserArgsDumpStd) -> Result<()> {
-    let mut s = StdioServer::new(args.program)?;
+    let mut client = OMcpClientBuilder::new(OMcpServerType::Stdio)
+        .with_stdio_command(args.program)
+        .build()?;
 
-    s.listen().await?;
-
-    let tools = s.list_tools().await?;
+    client.connect().await?;
 
-    info!("{tools}");
+    for tool in client.list_tools().await? {
+        info!("{}: {}", tool.name, tool.description);
+    }
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 use crate::{
     client::io::OMcpClientTrait,
     error::{Error, Result},
+    server::context::ToolContext,
     types::{BakedMcpToolTrait, McpParams, McpTool},
 };
 use async_trait::async_trait;
@@ -10,19 +11,18 @@ pub struct BakedClient<E> {
 }
 
 impl<E: std::fmt::Display + 'static> BakedClient<E> {
-    pub fn new<H>(handler: H) -> Box<dyn OMcpClientTrait>
+    pub fn new<H>(handler: H) -> Self
     where
         H: BakedMcpToolTrait<Error = E> + 'static,
     {
-        let b = Self {
+        Self {
             handler: Box::new(handler),
-        };
-
-        Box::new(b)
+        }
     }
 }
 
-#[async_trait(?Send)]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl<E: std::fmt::Display> OMcpClientTrait for BakedClient<E> {
     async fn connect(&mut self) -> Result<()> {
         Ok(())
@@ -34,7 +34,9 @@ impl<E: std::fmt::Display> OMcpClientTrait for BakedClient<E> {
         Err(Error::NotImplemented)
     }
     async fn call(&mut self, mcp_params: &McpParams) -> Result<String> {
-        match self.handler.call(mcp_params).await {
+        let ctx = ToolContext::detached();
+
+        match self.handler.call(&ctx, mcp_params).await {
             Ok(v) => Ok(v),
             Err(e) => {
                 let err_msg = format!("{e}");
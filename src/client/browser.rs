@@ -0,0 +1,447 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use js_sys::Promise;
+use log::{debug, error, warn};
+use reqwest::header::{HeaderMap, WWW_AUTHENTICATE};
+use serde_json::{Map, Value, json};
+use tokio::sync::mpsc;
+use wasm_bindgen::{JsCast, JsValue, closure::Closure, prelude::wasm_bindgen};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Event, EventSource, Headers, MessageEvent, Request, RequestInit, Response};
+
+use crate::{
+    client::{
+        builder::OMcpClientBuilder,
+        credentials::CredentialProvider,
+        io::OMcpClientTrait,
+        sse::{sse_next_block, sse_parse_wire},
+        types::{OMcpServerType, SseEvent, SseWireEvent},
+    },
+    error::{Error, Result},
+    json_rpc::{JSON_RPC_METHOD_NOT_FOUND, JsonRPCInitParams, JsonRPCMessage, JsonRPCMessageBuilder},
+    types::{McpParams, McpTool},
+};
+
+const MCP_SESSION_ID: &str = "mcp-session-id";
+const ACCEPT_ANY: &str = "application/json, text/event-stream";
+
+#[wasm_bindgen]
+extern "C" {
+    //
+    // the global one, there's no window in workers or node
+    //
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &Request) -> Promise;
+}
+
+//
+// GET side of the legacy SSE transport. EventSource can't send headers so
+// the builder's headers and credentials only go on the POSTs, the stream
+// itself gets whatever cookies the browser has for the server
+//
+struct EventStream {
+    source: EventSource,
+    events: mpsc::UnboundedReceiver<Result<SseEvent>>,
+    _listeners: Vec<Closure<dyn FnMut(MessageEvent)>>,
+    _on_error: Closure<dyn FnMut(Event)>,
+}
+
+//
+// OMcpServerType::Sse and OMcpServerType::Http on wasm32 with the browser
+// feature. Everything goes through fetch() and EventSource, no tokio runtime
+// needed. tests/browser.rs runs in node with
+// `wasm-pack test --node --features browser -- --test browser`.
+// Cross origin servers have to expose the Mcp-Session-Id header through CORS
+//
+pub struct BrowserClient {
+    server_type: OMcpServerType,
+    url: String,
+    headers: HeaderMap,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+    next_id: u64,
+    session_id: Option<String>,
+    endpoint: Option<String>,
+    stream: Option<EventStream>,
+    connected: bool,
+    tools_changed: bool,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+
+//
+// a single message or a batch
+//
+fn parse_body(body: &str) -> Result<Vec<JsonRPCMessage>> {
+    let messages = match serde_json::from_str(body)? {
+        Value::Array(v) => v.into_iter().map(serde_json::from_value).collect::<serde_json::Result<_>>()?,
+        v => vec![serde_json::from_value(v)?],
+    };
+
+    Ok(messages)
+}
+
+//
+// the whole text/event-stream body of a POST, the server ends it once the
+// response is out
+//
+fn parse_event_stream(server: &str, body: &str) -> Vec<JsonRPCMessage> {
+    let mut pending = body.replace("\r\n", "\n").into_bytes();
+    pending.extend_from_slice(b"\n\n");
+
+    let mut messages = Vec::new();
+
+    while let Some(block) = sse_next_block(&mut pending) {
+        match sse_parse_wire(server, &block) {
+            Ok(SseEvent::JsonRpcMessage(msg)) => messages.push(*msg),
+            Ok(SseEvent::Endpoint(_)) | Err(Error::NotFound) => {}
+            Err(e) => warn!("{e}"),
+        }
+    }
+
+    messages
+}
+
+fn into_result(msg: JsonRPCMessage) -> Result<Map<String, Value>> {
+    match (msg.result, msg.error) {
+        (_, Some(e)) => Err(Error::JsonRpcError {
+            code: e.code,
+            message: e.message,
+        }),
+        (Some(result), None) => Ok(result.into_iter().collect()),
+        (None, None) => Err(Error::Empty),
+    }
+}
+
+async fn text(response: &Response) -> Result<String> {
+    let text = JsFuture::from(response.text()?).await?;
+    Ok(text.as_string().unwrap_or_default())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl EventStream {
+    fn open(url: &str) -> Result<Self> {
+        let source = EventSource::new(url)?;
+        let (tx, events) = mpsc::unbounded_channel();
+
+        let mut listeners = Vec::new();
+
+        for name in ["endpoint", "message"] {
+            let tx = tx.clone();
+            let server = url.to_string();
+
+            let listener = Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+                let data = e.data().as_string().unwrap_or_default();
+
+                let wire = SseWireEvent {
+                    server: &server,
+                    event: name,
+                    data: &data,
+                };
+
+                let _ = tx.send(SseEvent::try_from(wire));
+            });
+
+            source.add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())?;
+            listeners.push(listener);
+        }
+
+        //
+        // EventSource reconnects on its own unless it gave up
+        //
+        let closed = source.clone();
+
+        let on_error = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            if closed.ready_state() == EventSource::CLOSED {
+                let _ = tx.send(Err(Error::NotConnected));
+            } else {
+                debug!("event source reconnecting");
+            }
+        });
+
+        source.add_event_listener_with_callback("error", on_error.as_ref().unchecked_ref())?;
+
+        Ok(Self {
+            source,
+            events,
+            _listeners: listeners,
+            _on_error: on_error,
+        })
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+impl BrowserClient {
+    //
+    // url, headers and credentials come from the builder, the TLS and proxy
+    // settings are the browser's business
+    //
+    pub fn from_builder(mut builder: OMcpClientBuilder) -> Result<Self> {
+        let client = builder.http_client()?;
+        let credentials = builder.credential_provider(&client);
+
+        Ok(Self {
+            server_type: builder.server_type,
+            url: builder.url,
+            headers: builder.headers,
+            credentials,
+            next_id: 1,
+            session_id: None,
+            endpoint: None,
+            stream: None,
+            connected: false,
+            tools_changed: false,
+        })
+    }
+
+    async fn request_headers(&self) -> Result<Headers> {
+        let mut map = self.headers.clone();
+
+        if let Some(credentials) = &self.credentials {
+            map.extend(credentials.headers().await?);
+        }
+
+        let headers = Headers::new()?;
+
+        for (k, v) in map.iter() {
+            match v.to_str() {
+                Ok(v) => headers.set(k.as_str(), v)?,
+                Err(e) => warn!("{k}: {e}"),
+            }
+        }
+
+        if let Some(id) = &self.session_id {
+            headers.set(MCP_SESSION_ID, id)?;
+        }
+
+        Ok(headers)
+    }
+
+    async fn fetch(&self, method: &str, url: &str, body: Option<&str>) -> Result<Response> {
+        let headers = self.request_headers().await?;
+
+        let init = RequestInit::new();
+        init.set_method(method);
+
+        if let Some(body) = body {
+            headers.set("content-type", "application/json")?;
+            headers.set("accept", ACCEPT_ANY)?;
+            init.set_body(&JsValue::from_str(body));
+        }
+
+        init.set_headers(&headers);
+
+        let request = Request::new_with_str_and_init(url, &init)?;
+        let response = JsFuture::from(fetch_with_request(&request)).await?;
+
+        Ok(response.dyn_into()?)
+    }
+
+    //
+    // same deal as SseClient, one retry after refreshing the credentials
+    //
+    async fn send(&self, method: &str, url: &str, body: Option<&str>) -> Result<Response> {
+        let response = self.fetch(method, url, body).await?;
+
+        let credentials = match (&self.credentials, response.status()) {
+            (Some(c), 401) => c,
+            _ => return Ok(response),
+        };
+
+        let challenge = response.headers().get(WWW_AUTHENTICATE.as_str())?;
+
+        if !credentials.refresh(challenge.as_deref()).await? {
+            return Ok(response);
+        }
+
+        self.fetch(method, url, body).await
+    }
+
+    //
+    // Streamable HTTP answers on the POST itself, SSE on the event stream
+    //
+    async fn post(&mut self, msg: &JsonRPCMessage) -> Result<Vec<JsonRPCMessage>> {
+        let url = match (&self.server_type, &self.endpoint) {
+            (OMcpServerType::Sse, Some(endpoint)) => endpoint.clone(),
+            (OMcpServerType::Sse, None) => return Err(Error::EndpointMissing),
+            _ => self.url.clone(),
+        };
+
+        let body = serde_json::to_string(msg)?;
+
+        debug!("sending: {body}");
+
+        let response = self.send("POST", &url, Some(&body)).await?;
+
+        if !response.ok() {
+            error!("{url} returned {}", response.status());
+            return Err(Error::ConnectionFailure);
+        }
+
+        if self.session_id.is_none()
+            && let Some(id) = response.headers().get(MCP_SESSION_ID)?
+        {
+            self.session_id = Some(id);
+        }
+
+        let content_type = response.headers().get("content-type")?.unwrap_or_default();
+
+        if content_type.starts_with("text/event-stream") {
+            Ok(parse_event_stream(&url, &text(&response).await?))
+        } else if content_type.starts_with("application/json") {
+            parse_body(&text(&response).await?)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    async fn recv_event(&mut self) -> Result<SseEvent> {
+        let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
+        stream.events.recv().await.ok_or(Error::NotConnected)?
+    }
+
+    //
+    // whatever isn't the response we're waiting for. Nothing is advertised in
+    // initialize so requests from the server are turned down
+    //
+    async fn dispatch(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        match (msg.id, msg.method.as_deref()) {
+            (None, Some("notifications/tools/list_changed")) => self.tools_changed = true,
+            (None, Some(method)) => debug!("notification: {method}"),
+            (Some(id), Some(method)) => {
+                let res = JsonRPCMessageBuilder::new()
+                    .with_id(id)
+                    .with_error(JSON_RPC_METHOD_NOT_FOUND, format!("{method} not supported"))
+                    .build();
+
+                self.post(&res).await?;
+            }
+            (Some(id), None) => warn!("unexpected response {id}"),
+            (None, None) => warn!("ignoring message without a method"),
+        }
+
+        Ok(())
+    }
+
+    async fn request<S>(&mut self, method: S, params: Value) -> Result<Map<String, Value>>
+    where
+        S: AsRef<str>,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let params: HashMap<String, Value> = serde_json::from_value(params)?;
+
+        let msg = JsonRPCMessageBuilder::new()
+            .with_id(id)
+            .with_method(method)
+            .with_parameter(params)
+            .build();
+
+        let mut answers = self.post(&msg).await?.into_iter();
+
+        loop {
+            let msg = match answers.next() {
+                Some(v) => v,
+                None if self.stream.is_none() => return Err(Error::Empty),
+                None => match self.recv_event().await? {
+                    SseEvent::JsonRpcMessage(msg) => *msg,
+                    SseEvent::Endpoint(e) => {
+                        self.endpoint = Some(e.url);
+                        continue;
+                    }
+                },
+            };
+
+            if msg.id == Some(id) && msg.method.is_none() {
+                return into_result(msg);
+            }
+
+            self.dispatch(msg).await?;
+        }
+    }
+
+    async fn notify<S>(&mut self, method: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let msg = JsonRPCMessageBuilder::new().with_method(method).build();
+
+        for msg in self.post(&msg).await? {
+            self.dispatch(msg).await?;
+        }
+
+        Ok(())
+    }
+
+    fn check_connected(&self) -> Result<()> {
+        match self.connected {
+            true => Ok(()),
+            false => Err(Error::NotConnected),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl OMcpClientTrait for BrowserClient {
+    async fn connect(&mut self) -> Result<()> {
+        self.disconnect().await?;
+
+        if let OMcpServerType::Sse = self.server_type {
+            self.stream = Some(EventStream::open(&self.url)?);
+
+            while self.endpoint.is_none() {
+                match self.recv_event().await? {
+                    SseEvent::Endpoint(e) => self.endpoint = Some(e.url),
+                    SseEvent::JsonRpcMessage(msg) => debug!("ignoring {:?} before the endpoint", msg.method),
+                }
+            }
+        }
+
+        self.request("initialize", json!(JsonRPCInitParams::new())).await?;
+        self.notify("notifications/initialized").await?;
+
+        self.connected = true;
+        Ok(())
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        self.connected = false;
+        self.stream = None;
+        self.endpoint = None;
+
+        if self.session_id.is_some() {
+            if let Err(e) = self.send("DELETE", &self.url, None).await {
+                debug!("{e}");
+            }
+
+            self.session_id = None;
+        }
+
+        Ok(())
+    }
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        self.check_connected()?;
+
+        let mut res = self.request("tools/list", json!({})).await?;
+        let tools = res.remove("tools").ok_or(Error::NotFound)?;
+        Ok(serde_json::from_value(tools)?)
+    }
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        self.check_connected()?;
+
+        let res = self.request("tools/call", json!(params)).await?;
+        Ok(serde_json::to_string_pretty(&res)?)
+    }
+    fn take_tools_changed(&mut self) -> bool {
+        std::mem::take(&mut self.tools_changed)
+    }
+}
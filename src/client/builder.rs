@@ -1,16 +1,371 @@
-use std::str::FromStr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(not(target_arch = "wasm32"))]
+use log::warn;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::{Certificate, ClientBuilder, Identity, NoProxy, Proxy, Url};
+use reqwest::{
+    Client,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
 
+#[cfg(all(target_arch = "wasm32", feature = "browser"))]
+use crate::client::browser::BrowserClient;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::sse::SseClient;
+#[cfg(unix)]
+use crate::client::unix::UnixClient;
 use crate::{
-    client::{io::OMcpClientTrait, sse::SseClient, types::OMcpServerType},
-    error::Result,
+    client::{
+        cache::CachedClient,
+        credentials::CredentialProvider,
+        io::OMcpClientTrait,
+        oauth::{OAuthClient, OAuthConfig},
+        shared::SharedClient,
+        types::OMcpServerType,
+    },
+    error::{Error, Result},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    client::{
+        http::HttpClient,
+        sandbox::Sandbox,
+        stdio::{RestartPolicy, StdioServer},
+        ws::WsClient,
+    },
+    json_rpc::{CLIENT_NAME, CLIENT_VERSION},
+    transport::ws::DEFAULT_PING_INTERVAL,
 };
 
 pub struct OMcpClientBuilder {
     pub url: String,
     pub server_type: OMcpServerType,
     pub headers: HeaderMap,
+    pub tools_cache: bool,
+    pub tools_cache_ttl: Option<Duration>,
+    pub oauth: Option<OAuthConfig>,
+    pub credentials: Option<Arc<dyn CredentialProvider>>,
+    pub http_client: Option<Client>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tls: TlsOptions,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub http: HttpOptions,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub stdio: StdioOptions,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub ws: WsOptions,
+    #[cfg(unix)]
+    pub unix_path: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default, Clone)]
+pub struct TlsOptions {
+    pub root_certificates: Vec<Certificate>,
+    pub identity: Option<Identity>,
+    pub insecure: bool,
+    //
+    // the same certificates for the WebSocket handshake, it doesn't go
+    // through reqwest
+    //
+    pub(crate) native_roots: Vec<native_tls::Certificate>,
+    pub(crate) native_identity: Option<native_tls::Identity>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct HttpOptions {
+    pub proxy: Option<Proxy>,
+    pub no_proxy: Option<String>,
+    pub system_proxy: bool,
+    pub user_agent: String,
+    pub connect_timeout: Option<Duration>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub http2_prior_knowledge: bool,
+    //
+    // the url given to with_proxy, reqwest doesn't hand it back and the
+    // WebSocket handshake opens its own tunnel
+    //
+    pub(crate) proxy_url: Option<Url>,
+}
+
+//
+// the child process behind OMcpServerType::Stdio
+//
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default, Clone)]
+pub struct StdioOptions {
+    pub command: PathBuf,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub clear_env: bool,
+    pub cwd: Option<PathBuf>,
+    pub restart: Option<RestartPolicy>,
+    pub sandbox: Sandbox,
+}
+
+//
+// OMcpServerType::WebSocket, the url and headers are shared with SSE
+//
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct WsOptions {
+    pub ping_interval: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for WsOptions {
+    fn default() -> Self {
+        Self {
+            ping_interval: DEFAULT_PING_INTERVAL,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: None,
+            system_proxy: true,
+            user_agent: format!("{CLIENT_NAME}/{CLIENT_VERSION}"),
+            connect_timeout: None,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            http2_prior_knowledge: false,
+            proxy_url: None,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpOptions {
+    fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        builder = builder.user_agent(&self.user_agent);
+
+        if !self.system_proxy {
+            builder = builder.no_proxy();
+        }
+
+        if let Some(proxy) = &self.proxy {
+            let no_proxy = self.no_proxy.as_deref().and_then(NoProxy::from_string);
+            builder = builder.proxy(proxy.clone().no_proxy(no_proxy));
+        }
+
+        //
+        // reqwest only looks at NO_PROXY for the proxies it finds in the
+        // environment, ours has to be added to them by hand
+        //
+        if let (None, true, Some(no_proxy)) = (&self.proxy, self.system_proxy, &self.no_proxy) {
+            for proxy in Self::env_proxies(no_proxy, |name| std::env::var(name).ok()) {
+                builder = builder.proxy(proxy);
+            }
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        builder
+    }
+
+    //
+    // HTTP(S)_PROXY and ALL_PROXY, upper or lower case, bypassed for the
+    // hosts in no_proxy and NO_PROXY
+    //
+    fn env_proxies<F>(no_proxy: &str, var: F) -> Vec<Proxy>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let lookup = |name: &str| var(name).or_else(|| var(&name.to_lowercase())).filter(|v| !v.is_empty());
+
+        let no_proxy = match lookup("NO_PROXY") {
+            Some(env) => format!("{no_proxy},{env}"),
+            None => no_proxy.to_string(),
+        };
+
+        ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+            .into_iter()
+            .filter_map(|name| {
+                let url = lookup(name)?;
+
+                let proxy = match name {
+                    "HTTP_PROXY" => Proxy::http(&url),
+                    "HTTPS_PROXY" => Proxy::https(&url),
+                    _ => Proxy::all(&url),
+                };
+
+                match proxy {
+                    Ok(v) => Some(v.no_proxy(NoProxy::from_string(&no_proxy))),
+                    Err(e) => {
+                        warn!("ignoring {name}: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    //
+    // the proxy a WebSocket handshake to url goes through, the same choice
+    // reqwest makes for the other transports. wss:// looks at HTTPS_PROXY,
+    // ws:// at HTTP_PROXY and both fall back to ALL_PROXY
+    //
+    pub(crate) fn ws_proxy<F>(&self, url: &Url, var: F) -> Option<Url>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let host = url.host_str()?;
+        let no_proxy = self.no_proxy.as_deref().unwrap_or_default();
+
+        if let Some(proxy) = &self.proxy_url {
+            return (!Self::bypassed(no_proxy, host)).then(|| proxy.clone());
+        }
+
+        if self.proxy.is_some() || !self.system_proxy {
+            return None;
+        }
+
+        let lookup = |name: &str| var(name).or_else(|| var(&name.to_lowercase())).filter(|v| !v.is_empty());
+
+        let name = match url.scheme() {
+            "wss" | "https" => "HTTPS_PROXY",
+            _ => "HTTP_PROXY",
+        };
+
+        let (name, proxy) = match lookup(name) {
+            Some(v) => (name, v),
+            None => ("ALL_PROXY", lookup("ALL_PROXY")?),
+        };
+
+        if Self::bypassed(no_proxy, host) || Self::bypassed(&lookup("NO_PROXY").unwrap_or_default(), host) {
+            return None;
+        }
+
+        let res = Self::parse_proxy(&proxy);
+
+        if res.is_none() {
+            warn!("ignoring {name}: {proxy}");
+        }
+
+        res
+    }
+
+    //
+    // reqwest accepts a bare host:port and assumes http
+    //
+    fn parse_proxy(proxy: &str) -> Option<Url> {
+        match proxy.contains("://") {
+            true => Url::parse(proxy).ok(),
+            false => Url::parse(&format!("http://{proxy}")).ok(),
+        }
+    }
+
+    //
+    // NO_PROXY syntax: * for everything, hosts that also match their
+    // subdomains, ip addresses and CIDRs
+    //
+    fn bypassed(no_proxy: &str, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let ip = host.parse::<IpAddr>().ok();
+
+        no_proxy.split(',').map(str::trim).filter(|v| !v.is_empty()).any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+
+            if let Some(ip) = ip {
+                let entry = entry.trim_start_matches('[').trim_end_matches(']');
+
+                return match entry.split_once('/') {
+                    Some((net, bits)) => match (net.parse::<IpAddr>(), bits.parse::<u32>()) {
+                        (Ok(net), Ok(bits)) => Self::in_network(ip, net, bits),
+                        _ => false,
+                    },
+                    None => entry.parse::<IpAddr>() == Ok(ip),
+                };
+            }
+
+            let domain = entry.trim_start_matches('.');
+
+            host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+        })
+    }
+
+    fn in_network(ip: IpAddr, net: IpAddr, bits: u32) -> bool {
+        match (ip, net) {
+            (IpAddr::V4(ip), IpAddr::V4(net)) if bits <= 32 => {
+                let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+                u32::from(ip) & mask == u32::from(net) & mask
+            }
+            (IpAddr::V6(ip), IpAddr::V6(net)) if bits <= 128 => {
+                let mask = u128::MAX.checked_shl(128 - bits).unwrap_or(0);
+                u128::from(ip) & mask == u128::from(net) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TlsOptions {
+    fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        for cert in self.root_certificates.iter() {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true).danger_accept_invalid_hostnames(true);
+        }
+
+        builder
+    }
+
+    pub(crate) fn connector(&self) -> Result<native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+
+        for cert in self.native_roots.iter() {
+            builder.add_root_certificate(cert.clone());
+        }
+
+        if let Some(identity) = &self.native_identity {
+            builder.identity(identity.clone());
+        }
+
+        if self.insecure {
+            builder.danger_accept_invalid_certs(true).danger_accept_invalid_hostnames(true);
+        }
+
+        Ok(builder.build()?)
+    }
 }
 
 impl OMcpClientBuilder {
@@ -19,6 +374,21 @@ impl OMcpClientBuilder {
             url: "".into(),
             server_type,
             headers: HeaderMap::new(),
+            tools_cache: false,
+            tools_cache_ttl: None,
+            oauth: None,
+            credentials: None,
+            http_client: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: TlsOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            http: HttpOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            stdio: StdioOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            ws: WsOptions::default(),
+            #[cfg(unix)]
+            unix_path: PathBuf::new(),
         }
     }
 
@@ -30,6 +400,117 @@ impl OMcpClientBuilder {
         self
     }
 
+    //
+    // the Streamable HTTP endpoint, usually ending in /mcp
+    //
+    #[cfg(any(not(target_arch = "wasm32"), feature = "browser"))]
+    pub fn with_http_url<S>(self, url: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.with_sse_url(url)
+    }
+
+    //
+    // ws:// or wss://
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_ws_url<S>(self, url: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.with_sse_url(url)
+    }
+
+    //
+    // zero disables the pings, the connection is dropped when the server
+    // doesn't answer one before the next is due
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_ws_ping_interval(mut self, interval: Duration) -> Self {
+        self.ws.ping_interval = interval;
+        self
+    }
+
+    //
+    // the socket OmcpServer::unix_loop() listens on
+    //
+    #[cfg(unix)]
+    pub fn with_unix_path<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.unix_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_command<P>(mut self, command: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.stdio.command = command.as_ref().to_path_buf();
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_args(mut self, args: &[String]) -> Self {
+        self.stdio.args = args.to_vec();
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_arg<S>(mut self, arg: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.stdio.args.push(arg.as_ref().to_string());
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_env<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.stdio.env.insert(key.as_ref().to_string(), value.as_ref().to_string());
+        self
+    }
+
+    //
+    // see StdioServer::with_clear_env()
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_clear_env(mut self) -> Self {
+        self.stdio.clear_env = true;
+        self
+    }
+
+    //
+    // the current directory when not set
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_cwd<P>(mut self, cwd: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.stdio.cwd = Some(cwd.as_ref().to_path_buf());
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_restart(mut self, policy: RestartPolicy) -> Self {
+        self.stdio.restart = Some(policy);
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stdio_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.stdio.sandbox = sandbox;
+        self
+    }
+
     pub fn with_sse_bearer<S>(self, bearer: S) -> Result<Self>
     where
         S: AsRef<str>,
@@ -49,15 +530,432 @@ impl OMcpClientBuilder {
         Ok(self)
     }
 
-    pub fn build(self) -> Box<dyn OMcpClientTrait> {
-        match self.server_type {
+    //
+    // asked for headers before every request, takes over from with_oauth()
+    //
+    pub fn with_credentials(mut self, provider: Arc<dyn CredentialProvider>) -> Self {
+        self.credentials = Some(provider);
+        self
+    }
+
+    //
+    // runs the authorization flow when the server answers with a 401
+    //
+    pub fn with_oauth(mut self, config: OAuthConfig) -> Self {
+        self.oauth = Some(config);
+        self
+    }
+
+    //
+    // used as is, none of the TLS options below are applied to it
+    //
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    //
+    // PEM file with one or more CA certificates trusted on top of the system
+    // ones
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_root_certificate<P>(mut self, path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let pem = std::fs::read(path)?;
+        self.tls.root_certificates.extend(Certificate::from_pem_bundle(&pem)?);
+        self.tls.native_roots.extend(native_tls::Certificate::stack_from_pem(&pem)?);
+        Ok(self)
+    }
+
+    //
+    // mTLS, PEM certificate chain and PKCS#8 PEM private key
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_client_identity<C, K>(mut self, cert: C, key: K) -> Result<Self>
+    where
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        let cert = std::fs::read(cert)?;
+        let key = std::fs::read(key)?;
+        self.tls.identity = Some(Identity::from_pkcs8_pem(&cert, &key)?);
+        self.tls.native_identity = Some(native_tls::Identity::from_pkcs8(&cert, &key)?);
+        Ok(self)
+    }
+
+    //
+    // accepts any certificate, local development only
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_insecure_tls(mut self) -> Self {
+        self.tls.insecure = true;
+        self
+    }
+
+    //
+    // every request goes through it, http://, https:// or socks5:// urls.
+    // Replaces the HTTP_PROXY / HTTPS_PROXY environment variables
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_proxy<S>(mut self, url: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        self.http.proxy = Some(Proxy::all(url.as_ref())?);
+        self.http.proxy_url = HttpOptions::parse_proxy(url.as_ref());
+        self.http.system_proxy = false;
+        Ok(self)
+    }
+
+    //
+    // comma separated hosts, domains and CIDRs that bypass the proxy
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_no_proxy<S>(mut self, hosts: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.http.no_proxy = Some(hosts.as_ref().into());
+        self
+    }
+
+    //
+    // ignore the proxy environment variables
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn without_proxy(mut self) -> Self {
+        self.http.proxy = None;
+        self.http.proxy_url = None;
+        self.http.system_proxy = false;
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_user_agent<S>(mut self, user_agent: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.http.user_agent = user_agent.as_ref().into();
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http.connect_timeout = Some(timeout);
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.http.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.http.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    //
+    // HTTP/2 from the first byte without negotiating it, the server has to
+    // speak it. Plain http:// urls only in practice, there's no ALPN
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_http2_prior_knowledge(mut self) -> Self {
+        self.http.http2_prior_knowledge = true;
+        self
+    }
+
+    //
+    // fails rather than falling back to a default client, the TLS and proxy
+    // settings would be lost
+    //
+    pub(crate) fn http_client(&self) -> Result<Client> {
+        if let Some(client) = &self.http_client {
+            return Ok(client.clone());
+        }
+
+        let builder = Client::builder();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = self.http.apply(self.tls.apply(builder));
+
+        Ok(builder.build()?)
+    }
+
+    //
+    // with_credentials() wins over with_oauth(), the OAuth flow goes through
+    // the same http client
+    //
+    pub(crate) fn credential_provider(&mut self, client: &Client) -> Option<Arc<dyn CredentialProvider>> {
+        match (self.credentials.take(), self.oauth.take()) {
+            (Some(c), _) => Some(c),
+            (None, Some(config)) => {
+                let oauth = OAuthClient::new(&self.url, config).with_http_client(client.clone());
+                Some(Arc::new(oauth))
+            }
+            (None, None) => None,
+        }
+    }
+
+    pub fn with_tools_cache(mut self) -> Self {
+        self.tools_cache = true;
+        self
+    }
+
+    pub fn with_tools_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.tools_cache = true;
+        self.tools_cache_ttl = Some(ttl);
+        self
+    }
+
+    pub fn build(self) -> Result<Box<dyn OMcpClientTrait>> {
+        let tools_cache = self.tools_cache;
+        let tools_cache_ttl = self.tools_cache_ttl;
+
+        let client: Box<dyn OMcpClientTrait> = match self.server_type {
+            #[cfg(not(target_arch = "wasm32"))]
             OMcpServerType::Sse => {
-                let sse = SseClient::from_builder(self);
+                let sse = SseClient::from_builder(self)?;
                 Box::new(sse)
             }
-            OMcpServerType::Baked => {
-                todo!()
+            #[cfg(all(target_arch = "wasm32", not(feature = "browser")))]
+            OMcpServerType::Sse => return Err(Error::UnsupportedTransport { name: "sse".into() }),
+            #[cfg(all(target_arch = "wasm32", feature = "browser"))]
+            OMcpServerType::Sse | OMcpServerType::Http => {
+                let browser = BrowserClient::from_builder(self)?;
+                Box::new(browser)
             }
-        }
+            #[cfg(not(target_arch = "wasm32"))]
+            OMcpServerType::Http => {
+                let http = HttpClient::from_builder(self)?;
+                Box::new(http)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OMcpServerType::Stdio => {
+                let stdio = StdioServer::from_builder(self);
+                Box::new(stdio)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            OMcpServerType::WebSocket => {
+                let ws = WsClient::from_builder(self)?;
+                Box::new(ws)
+            }
+            #[cfg(unix)]
+            OMcpServerType::Unix => {
+                let unix = UnixClient::from_builder(self);
+                Box::new(unix)
+            }
+            //
+            // baked tools live in this process, BakedClient::new() takes them
+            //
+            OMcpServerType::Baked => return Err(Error::UnsupportedTransport { name: "baked".into() }),
+        };
+
+        let client: Box<dyn OMcpClientTrait> = match (tools_cache, tools_cache_ttl) {
+            (false, _) => client,
+            (true, None) => Box::new(CachedClient::new(client)),
+            (true, Some(ttl)) => Box::new(CachedClient::new(client).with_ttl(ttl)),
+        };
+
+        Ok(client)
+    }
+    pub fn build_shared(self) -> Result<SharedClient> {
+        Ok(SharedClient::new(self.build()?))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use reqwest::Url;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{
+        client::{
+            builder::{HttpOptions, OMcpClientBuilder},
+            types::OMcpServerType,
+        },
+        error::Error,
+        server::matrix::OmcpServer,
+        test::utils::tls::{CA_PATH, tls_listener},
+    };
+
+    #[test]
+    fn baked_is_not_built() {
+        let res = OMcpClientBuilder::new(OMcpServerType::Baked).build();
+        assert!(matches!(res, Err(Error::UnsupportedTransport { .. })));
+    }
+
+    #[test]
+    fn tls_options() {
+        let res = OMcpClientBuilder::new(OMcpServerType::Sse).with_root_certificate("/does/not/exist.pem");
+        assert!(matches!(res, Err(Error::Io(_))));
+
+        let dir = std::env::temp_dir().join(format!("omcp-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+        std::fs::write(
+            &cert,
+            "-----BEGIN CERTIFICATE-----\nbm9wZQ==\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        std::fs::write(&key, "not a key").unwrap();
+
+        let res = OMcpClientBuilder::new(OMcpServerType::Sse).with_client_identity(&cert, &key);
+        assert!(matches!(res, Err(Error::Reqwest(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::Sse).with_insecure_tls();
+        assert!(builder.tls.insecure);
+        builder.http_client().unwrap();
+    }
+
+    #[tokio::test]
+    async fn tls_custom_root() {
+        let (listener, acceptor) = tls_listener().await;
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+
+                //
+                // the handshake fails for a client that doesn't trust our CA
+                //
+                let mut stream = match acceptor.accept(stream).await {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+
+                let mut request = Vec::new();
+
+                while !request.ends_with(b"\r\n\r\n") {
+                    request.push(stream.read_u8().await.unwrap());
+                }
+
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let url = format!("https://{addr}/");
+
+        let client = OMcpClientBuilder::new(OMcpServerType::Sse).http_client().unwrap();
+        assert!(client.get(&url).send().await.is_err());
+
+        let client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_root_certificate(CA_PATH)
+            .unwrap()
+            .http_client()
+            .unwrap();
+
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), 204);
+    }
+
+    #[tokio::test]
+    async fn through_proxy() {
+        //
+        // the server doubles as the proxy, requests come in absolute form
+        //
+        let server = OmcpServer::<Error>::new();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url("http://mcp.invalid/sse")
+            .with_proxy(format!("http://{addr}"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+        assert!(client.list_tools().await.unwrap().is_empty());
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url("http://mcp.invalid/sse")
+            .with_proxy(format!("http://{addr}"))
+            .unwrap()
+            .with_no_proxy("mcp.invalid")
+            .build()
+            .unwrap();
+
+        assert!(client.connect().await.is_err());
+
+        //
+        // same again with the proxy coming from the environment
+        //
+        let proxy = format!("http://{addr}");
+        let var = |name: &str| (name == "http_proxy").then(|| proxy.clone());
+
+        let client = |no_proxy| {
+            let mut builder = reqwest::Client::builder().no_proxy();
+
+            for proxy in HttpOptions::env_proxies(no_proxy, var) {
+                builder = builder.proxy(proxy);
+            }
+
+            builder.build().unwrap()
+        };
+
+        let url = "http://mcp.invalid/messages?sessionId=none";
+
+        let res = client("other.invalid").post(url).send().await.unwrap();
+        assert_eq!(res.status(), 404);
+
+        assert!(client("mcp.invalid").post(url).send().await.is_err());
+    }
+
+    #[test]
+    fn ws_proxy() {
+        let url = |v: &str| Url::parse(v).unwrap();
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        };
+
+        let http = HttpOptions::default();
+        let vars = env(&[("https_proxy", "proxy:3128"), ("ALL_PROXY", "http://all:8080")]);
+
+        let proxy = http.ws_proxy(&url("wss://mcp.example.com/ws"), vars).unwrap();
+        assert_eq!(proxy.as_str(), "http://proxy:3128/");
+
+        let proxy = http.ws_proxy(&url("ws://mcp.example.com/ws"), vars).unwrap();
+        assert_eq!(proxy.as_str(), "http://all:8080/");
+
+        let vars = env(&[("HTTP_PROXY", "http://proxy:3128"), ("NO_PROXY", ".example.com, 10.0.0.0/8")]);
+        assert!(http.ws_proxy(&url("ws://mcp.example.com/ws"), vars).is_none());
+        assert!(http.ws_proxy(&url("ws://10.1.2.3/ws"), vars).is_none());
+        assert!(http.ws_proxy(&url("ws://11.1.2.3/ws"), vars).is_some());
+        assert!(http.ws_proxy(&url("ws://notexample.com/ws"), vars).is_some());
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket)
+            .with_proxy("http://explicit:3128")
+            .unwrap()
+            .with_no_proxy("[::1], localhost");
+
+        let proxy = builder.http.ws_proxy(&url("ws://mcp.example.com/ws"), vars).unwrap();
+        assert_eq!(proxy.as_str(), "http://explicit:3128/");
+        assert!(builder.http.ws_proxy(&url("ws://localhost/ws"), vars).is_none());
+        assert!(builder.http.ws_proxy(&url("ws://[::1]:80/ws"), vars).is_none());
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket).without_proxy();
+        assert!(builder.http.ws_proxy(&url("ws://mcp.example.com/ws"), vars).is_none());
     }
 }
@@ -0,0 +1,232 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use log::debug;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::io::OMcpCallTrait;
+use crate::{
+    client::io::OMcpClientTrait,
+    error::Result,
+    types::{McpParams, McpTool},
+};
+
+struct CachedTools {
+    tools: Vec<McpTool>,
+    fetched: Instant,
+}
+
+pub struct CachedClient {
+    inner: Box<dyn OMcpClientTrait>,
+    ttl: Option<Duration>,
+    cache: Option<CachedTools>,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// IMPL
+///////////////////////////////////////////////////////////////////////////////
+
+impl CachedClient {
+    pub fn new(inner: Box<dyn OMcpClientTrait>) -> Self {
+        Self {
+            inner,
+            ttl: None,
+            cache: None,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn invalidate(&mut self) {
+        self.cache = None;
+    }
+
+    fn cached_tools(&self) -> Option<&Vec<McpTool>> {
+        let cache = self.cache.as_ref()?;
+
+        match self.ttl {
+            Some(ttl) if cache.fetched.elapsed() >= ttl => None,
+            _ => Some(&cache.tools),
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl OMcpClientTrait for CachedClient {
+    async fn connect(&mut self) -> Result<()> {
+        self.invalidate();
+        self.inner.connect().await
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        self.invalidate();
+        self.inner.disconnect().await
+    }
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        if self.inner.take_tools_changed() {
+            debug!("tool list changed, dropping cache");
+            self.invalidate();
+        }
+
+        if let Some(tools) = self.cached_tools() {
+            return Ok(tools.clone());
+        }
+
+        let tools = self.inner.list_tools().await?;
+
+        self.cache = Some(CachedTools {
+            tools: tools.clone(),
+            fetched: Instant::now(),
+        });
+
+        Ok(tools)
+    }
+    async fn call(&mut self, mcp_params: &McpParams) -> Result<String> {
+        self.inner.call(mcp_params).await
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        self.inner.caller()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TEST
+///////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, cache::CachedClient, io::OMcpClientTrait, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams, McpTool},
+    };
+
+    struct CountingClient {
+        lists: Arc<AtomicU32>,
+        changed: Arc<AtomicBool>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl OMcpClientTrait for CountingClient {
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+            self.lists.fetch_add(1, Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+        async fn call(&mut self, _mcp_params: &McpParams) -> Result<String> {
+            Ok("".into())
+        }
+        fn take_tools_changed(&mut self) -> bool {
+            self.changed.swap(false, Ordering::SeqCst)
+        }
+    }
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(params.tool_name.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn list_changed_invalidates() {
+        let lists = Arc::new(AtomicU32::new(0));
+        let changed = Arc::new(AtomicBool::new(false));
+
+        let inner = CountingClient {
+            lists: lists.clone(),
+            changed: changed.clone(),
+        };
+
+        let mut client = CachedClient::new(Box::new(inner));
+
+        client.list_tools().await.unwrap();
+        client.list_tools().await.unwrap();
+        assert_eq!(lists.load(Ordering::SeqCst), 1);
+
+        changed.store(true, Ordering::SeqCst);
+        client.list_tools().await.unwrap();
+        assert_eq!(lists.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn ttl_expires() {
+        let lists = Arc::new(AtomicU32::new(0));
+
+        let inner = CountingClient {
+            lists: lists.clone(),
+            changed: Arc::new(AtomicBool::new(false)),
+        };
+
+        let mut client = CachedClient::new(Box::new(inner)).with_ttl(Duration::ZERO);
+
+        client.list_tools().await.unwrap();
+        client.list_tools().await.unwrap();
+        assert_eq!(lists.load(Ordering::SeqCst), 2);
+    }
+
+    //
+    // the notification shows up while nobody is calling the client
+    //
+    #[tokio::test]
+    async fn list_changed_while_idle() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+        let handle = server.handle();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("http://{addr}/sse"))
+            .with_tools_cache()
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+        assert_eq!(client.list_tools().await.unwrap().len(), 1);
+
+        handle.add_tool("echo2", EchoTool {});
+
+        let mut tools = 1;
+
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            tools = client.list_tools().await.unwrap().len();
+
+            if tools == 2 {
+                break;
+            }
+        }
+
+        assert_eq!(tools, 2);
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+
+use crate::{error::Result, types::MaybeSendSync};
+
+//
+// Consulted before every HTTP request. After a 401 refresh() gets a chance to
+// fetch new credentials and the request is sent once more if it returns true
+//
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait CredentialProvider: MaybeSendSync {
+    async fn headers(&self) -> Result<HeaderMap>;
+
+    //
+    // challenge is the WWW-Authenticate header of the 401
+    //
+    async fn refresh(&self, challenge: Option<&str>) -> Result<bool>;
+}
+
+//
+// Bearer token that can be swapped while the client is connected, e.g. with
+// rotating long-lived tokens
+//
+#[derive(Default)]
+pub struct BearerCredentials {
+    token: Mutex<String>,
+    sent: Mutex<Option<String>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn bearer_headers<S>(token: S) -> Result<HeaderMap>
+where
+    S: AsRef<str>,
+{
+    let mut headers = HeaderMap::new();
+    let value = HeaderValue::from_str(&format!("Bearer {}", token.as_ref()))?;
+    headers.insert(AUTHORIZATION, value);
+    Ok(headers)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl BearerCredentials {
+    pub fn new<S>(token: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            token: Mutex::new(token.as_ref().to_string()),
+            sent: Mutex::new(None),
+        }
+    }
+
+    pub fn set_token<S>(&self, token: S)
+    where
+        S: AsRef<str>,
+    {
+        *lock(&self.token) = token.as_ref().to_string();
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl CredentialProvider for BearerCredentials {
+    async fn headers(&self) -> Result<HeaderMap> {
+        let token = lock(&self.token).clone();
+        *lock(&self.sent) = Some(token.clone());
+        bearer_headers(token)
+    }
+
+    //
+    // only worth another try if the token changed since it was sent
+    //
+    async fn refresh(&self, _challenge: Option<&str>) -> Result<bool> {
+        let token = lock(&self.token).clone();
+        Ok(lock(&self.sent).as_ref() != Some(&token))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use async_trait::async_trait;
+    use reqwest::header::{AUTHORIZATION, HeaderMap};
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::{
+            builder::OMcpClientBuilder,
+            credentials::{CredentialProvider, bearer_headers},
+            types::OMcpServerType,
+        },
+        error::{Error, Result},
+        server::{
+            auth::{Authenticator, Principal},
+            matrix::OmcpServer,
+        },
+    };
+
+    //
+    // the token the server currently accepts
+    //
+    struct VaultAuthenticator {
+        vault: Arc<Mutex<String>>,
+    }
+
+    #[async_trait]
+    impl Authenticator for VaultAuthenticator {
+        async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal> {
+            let expected = format!("Bearer {}", self.vault.lock().unwrap());
+
+            match headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+                Some(v) if v == expected => Ok(Principal::new("ha")),
+                _ => Err(Error::Unauthorized),
+            }
+        }
+    }
+
+    struct VaultCredentials {
+        current: Mutex<String>,
+        vault: Arc<Mutex<String>>,
+        refreshes: AtomicU32,
+    }
+
+    #[async_trait]
+    impl CredentialProvider for VaultCredentials {
+        async fn headers(&self) -> Result<HeaderMap> {
+            bearer_headers(self.current.lock().unwrap().as_str())
+        }
+
+        async fn refresh(&self, _challenge: Option<&str>) -> Result<bool> {
+            self.refreshes.fetch_add(1, Ordering::SeqCst);
+            *self.current.lock().unwrap() = self.vault.lock().unwrap().clone();
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_after_401() {
+        let vault = Arc::new(Mutex::new("t1".to_string()));
+
+        let auth = VaultAuthenticator { vault: vault.clone() };
+        let server = OmcpServer::<Error>::new().with_authenticator(auth);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let credentials = Arc::new(VaultCredentials {
+            current: Mutex::new("stale".into()),
+            vault: vault.clone(),
+            refreshes: AtomicU32::new(0),
+        });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("http://{addr}/sse"))
+            .with_credentials(credentials.clone())
+            .build()
+            .unwrap();
+
+        //
+        // the GET stream
+        //
+        client.connect().await.unwrap();
+        assert_eq!(credentials.refreshes.load(Ordering::SeqCst), 1);
+
+        //
+        // and the POST endpoint once the token rotated
+        //
+        *vault.lock().unwrap() = "t2".into();
+
+        client.list_tools().await.unwrap();
+        assert_eq!(credentials.refreshes.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use log::{error, warn};
+
+use crate::{
+    client::io::OMcpClientTrait,
+    error::{Error, Result},
+    types::{McpParams, McpTool},
+};
+
+//
+// <server>__<tool>, keeps two servers exposing the same tool name apart
+//
+pub const TOOL_SEPARATOR: &str = "__";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerHealth {
+    Disconnected,
+    Connected,
+    Failed { error: String },
+}
+
+struct GroupMember {
+    name: String,
+    client: Box<dyn OMcpClientTrait>,
+    health: ServerHealth,
+}
+
+//
+// Many servers behind one client. Tools are listed as server__tool and calls
+// go to whichever server owns the tool. A server that fails is reported in
+// health() and skipped, the rest keep working
+//
+#[derive(Default)]
+pub struct ClientGroup {
+    members: Vec<GroupMember>,
+    routes: HashMap<String, (usize, String)>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn prefixed(server: &str, tool: &str) -> String {
+    format!("{server}{TOOL_SEPARATOR}{tool}")
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl GroupMember {
+    fn fail(&mut self, e: &Error) {
+        error!("{}: {e}", self.name);
+
+        self.health = ServerHealth::Failed { error: e.to_string() };
+    }
+
+    //
+    // a failed server gets another chance every time it's needed, one the
+    // caller disconnected stays that way
+    //
+    async fn reconnect(&mut self) -> bool {
+        if let ServerHealth::Failed { .. } = self.health {
+            match self.client.connect().await {
+                Ok(()) => self.health = ServerHealth::Connected,
+                Err(e) => self.fail(&e),
+            }
+        }
+
+        self.health == ServerHealth::Connected
+    }
+}
+
+impl ClientGroup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //
+    // names are unique and can't contain the separator, the tool names
+    // couldn't be told apart otherwise
+    //
+    pub fn with_client<S>(mut self, name: S, client: Box<dyn OMcpClientTrait>) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let name = name.as_ref();
+
+        if name.is_empty() || name.contains(TOOL_SEPARATOR) || self.members.iter().any(|m| m.name == name) {
+            return Err(Error::InvalidServerName { name: name.into() });
+        }
+
+        self.members.push(GroupMember {
+            name: name.to_string(),
+            client,
+            health: ServerHealth::Disconnected,
+        });
+        Ok(self)
+    }
+
+    pub fn from_clients<I, S>(clients: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (S, Box<dyn OMcpClientTrait>)>,
+        S: AsRef<str>,
+    {
+        clients
+            .into_iter()
+            .try_fold(Self::new(), |group, (name, client)| group.with_client(name, client))
+    }
+
+    pub fn health(&self) -> Vec<(String, ServerHealth)> {
+        self.members.iter().map(|m| (m.name.clone(), m.health.clone())).collect()
+    }
+
+    //
+    // the route from the last list_tools, the prefix otherwise
+    //
+    fn route(&self, tool_name: &str) -> Option<(usize, String)> {
+        if let Some(route) = self.routes.get(tool_name) {
+            return Some(route.clone());
+        }
+
+        let (server, tool) = tool_name.split_once(TOOL_SEPARATOR)?;
+        let index = self.members.iter().position(|m| m.name == server)?;
+
+        Some((index, tool.to_string()))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl OMcpClientTrait for ClientGroup {
+    //
+    // all at once, only an error when none of them made it
+    //
+    async fn connect(&mut self) -> Result<()> {
+        let results = join_all(self.members.iter_mut().map(|m| m.client.connect())).await;
+
+        for (member, res) in self.members.iter_mut().zip(results) {
+            match res {
+                Ok(_) => member.health = ServerHealth::Connected,
+                Err(e) => member.fail(&e),
+            }
+        }
+
+        let connected = self.members.iter().any(|m| m.health == ServerHealth::Connected);
+
+        match connected || self.members.is_empty() {
+            true => Ok(()),
+            false => Err(Error::ConnectionFailure),
+        }
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        let results = join_all(self.members.iter_mut().map(|m| m.client.disconnect())).await;
+
+        for (member, res) in self.members.iter_mut().zip(results) {
+            if let Err(e) = res {
+                warn!("{}: {e}", member.name);
+            }
+
+            member.health = ServerHealth::Disconnected;
+        }
+
+        self.routes.clear();
+        Ok(())
+    }
+
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        join_all(self.members.iter_mut().map(|m| m.reconnect())).await;
+
+        let connected = self
+            .members
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, m)| m.health == ServerHealth::Connected);
+
+        let (indexes, lists): (Vec<usize>, Vec<_>) = connected.map(|(i, m)| (i, m.client.list_tools())).unzip();
+        let results = join_all(lists).await;
+
+        let mut tools = Vec::new();
+        self.routes.clear();
+
+        for (i, res) in indexes.into_iter().zip(results) {
+            let member = &mut self.members[i];
+
+            let list = match res {
+                Ok(v) => v,
+                Err(e) => {
+                    member.fail(&e);
+                    continue;
+                }
+            };
+
+            for mut tool in list {
+                let name = prefixed(&member.name, &tool.name);
+                self.routes.insert(name.clone(), (i, tool.name));
+                tool.name = name;
+                tools.push(tool);
+            }
+        }
+
+        Ok(tools)
+    }
+
+    async fn call(&mut self, mcp_params: &McpParams) -> Result<String> {
+        let (index, tool_name) = self.route(&mcp_params.tool_name).ok_or(Error::NotFound)?;
+
+        let params = McpParams {
+            tool_name,
+            arguments: mcp_params.arguments.clone(),
+        };
+
+        let member = &mut self.members[index];
+
+        if !member.reconnect().await {
+            return Err(Error::NotConnected);
+        }
+
+        match member.client.call(&params).await {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                //
+                // a failed tool is the tool's business, a dead connection is ours
+                //
+                if matches!(e, Error::NotConnected | Error::ConnectionFailure | Error::Reqwest(_)) {
+                    member.fail(&e);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn take_tools_changed(&mut self) -> bool {
+        //
+        // every member has to be asked or its flag would fire again later
+        //
+        let mut changed = false;
+
+        for member in self.members.iter_mut() {
+            changed |= member.client.take_tools_changed();
+        }
+
+        changed
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    use async_trait::async_trait;
+
+    use crate::{
+        client::{
+            group::{ClientGroup, ServerHealth},
+            io::OMcpClientTrait,
+        },
+        error::{Error, Result},
+        types::{McpParams, McpTool},
+    };
+
+    struct EchoClient {
+        name: &'static str,
+        up: Arc<AtomicBool>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl OMcpClientTrait for EchoClient {
+        async fn connect(&mut self) -> Result<()> {
+            match self.up.load(Ordering::SeqCst) {
+                true => Ok(()),
+                false => Err(Error::ConnectionFailure),
+            }
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+            let tool = McpTool {
+                name: "echo".into(),
+                description: self.name.into(),
+                input_schema: None,
+            };
+            Ok(vec![tool])
+        }
+        async fn call(&mut self, mcp_params: &McpParams) -> Result<String> {
+            Ok(format!("{}:{}", self.name, mcp_params.tool_name))
+        }
+    }
+
+    fn echo(name: &'static str, up: bool) -> Box<dyn OMcpClientTrait> {
+        let up = Arc::new(AtomicBool::new(up));
+        Box::new(EchoClient { name, up })
+    }
+
+    #[tokio::test]
+    async fn prefixes_and_routing() {
+        let mut group =
+            ClientGroup::from_clients([("a", echo("a", true)), ("b", echo("b", true)), ("c", echo("c", false))])
+                .unwrap();
+
+        group.connect().await.unwrap();
+
+        let tools = group.list_tools().await.unwrap();
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["a__echo", "b__echo"]);
+
+        let params = McpParams {
+            tool_name: "b__echo".into(),
+            arguments: Default::default(),
+        };
+        assert_eq!(group.call(&params).await.unwrap(), "b:echo");
+
+        let params = McpParams {
+            tool_name: "echo".into(),
+            arguments: Default::default(),
+        };
+        assert!(matches!(group.call(&params).await, Err(Error::NotFound)));
+
+        //
+        // only the server that is exactly the prefix
+        //
+        let params = McpParams {
+            tool_name: "a_b__echo".into(),
+            arguments: Default::default(),
+        };
+        assert!(matches!(group.call(&params).await, Err(Error::NotFound)));
+
+        let health = group.health();
+        assert_eq!(health[0].1, ServerHealth::Connected);
+        assert_eq!(
+            health[2].1,
+            ServerHealth::Failed {
+                error: Error::ConnectionFailure.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn names() {
+        let group = ClientGroup::new().with_client("a", echo("a", true)).unwrap();
+        assert!(matches!(
+            group.with_client("a", echo("a", true)),
+            Err(Error::InvalidServerName { .. })
+        ));
+
+        let res = ClientGroup::new().with_client("a__b", echo("a", true));
+        assert!(matches!(res, Err(Error::InvalidServerName { .. })));
+    }
+
+    #[tokio::test]
+    async fn all_down() {
+        let mut group = ClientGroup::new().with_client("a", echo("a", false)).unwrap();
+        assert!(matches!(group.connect().await, Err(Error::ConnectionFailure)));
+    }
+
+    #[tokio::test]
+    async fn reconnects() {
+        let up = Arc::new(AtomicBool::new(false));
+        let client = Box::new(EchoClient {
+            name: "a",
+            up: up.clone(),
+        });
+
+        let mut group = ClientGroup::new()
+            .with_client("a", client)
+            .unwrap()
+            .with_client("b", echo("b", true))
+            .unwrap();
+
+        group.connect().await.unwrap();
+        assert_eq!(group.list_tools().await.unwrap().len(), 1);
+
+        up.store(true, Ordering::SeqCst);
+
+        let params = McpParams {
+            tool_name: "a__echo".into(),
+            arguments: Default::default(),
+        };
+        assert_eq!(group.call(&params).await.unwrap(), "a:echo");
+        assert_eq!(group.list_tools().await.unwrap().len(), 2);
+        assert_eq!(group.health()[0].1, ServerHealth::Connected);
+    }
+}
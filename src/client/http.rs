@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    client::{
+        builder::OMcpClientBuilder,
+        io::{OMcpCallTrait, OMcpClientTrait},
+    },
+    error::{Error, Result},
+    transport::{http::HttpConnector, session::McpSession},
+    types::{McpParams, McpTool},
+};
+
+//
+// OMcpServerType::Http, Streamable HTTP. connect() starts a new session every
+// time, disconnect() ends it on the server with a DELETE
+//
+pub struct HttpClient {
+    connector: HttpConnector,
+    session: Option<Arc<McpSession>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl HttpClient {
+    pub fn from_builder(builder: OMcpClientBuilder) -> Result<Self> {
+        Ok(Self {
+            connector: HttpConnector::from_builder(builder)?,
+            session: None,
+        })
+    }
+
+    fn session(&self) -> Result<&McpSession> {
+        self.session.as_deref().ok_or(Error::NotConnected)
+    }
+}
+
+#[async_trait]
+impl OMcpClientTrait for HttpClient {
+    async fn connect(&mut self) -> Result<()> {
+        self.disconnect().await?;
+
+        let session = McpSession::new(self.connector.transport());
+        session.initialize().await?;
+
+        self.session = Some(Arc::new(session));
+        Ok(())
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+
+        Ok(())
+    }
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        self.session()?.list_tools().await
+    }
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        self.session()?.call_tool(params).await
+    }
+    fn take_tools_changed(&mut self) -> bool {
+        self.session.as_ref().is_some_and(|s| s.take_tools_changed())
+    }
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        Some(self.session.clone()?)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, http::HttpServerOptions, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("echo {}", params.tool_name))
+        }
+    }
+
+    #[tokio::test]
+    async fn builder_http() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.http_serve(listener, HttpServerOptions::new()).await });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Http)
+            .with_http_url(format!("http://{addr}/mcp"))
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+        assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+
+        let res = client.call(&McpParams::new("echo")).await.unwrap();
+        assert!(res.contains("echo echo"));
+
+        //
+        // a new session every time
+        //
+        client.connect().await.unwrap();
+        assert_eq!(client.list_tools().await.unwrap().len(), 1);
+
+        client.disconnect().await.unwrap();
+        assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+    }
+}
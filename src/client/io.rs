@@ -1,14 +1,56 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
 use async_trait::async_trait;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::transport::session::McpSession;
 use crate::{
     error::Result,
-    types::{McpParams, McpTool},
+    types::{MaybeSendSync, McpParams, McpTool},
 };
 
-#[async_trait(?Send)]
-pub trait OMcpClientTrait {
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait OMcpClientTrait: MaybeSendSync {
     async fn connect(&mut self) -> Result<()>;
     async fn disconnect(&mut self) -> Result<()>;
     async fn list_tools(&mut self) -> Result<Vec<McpTool>>;
     async fn call(&mut self, mcp_params: &McpParams) -> Result<String>;
+
+    //
+    // true once after the server sent notifications/tools/list_changed
+    //
+    fn take_tools_changed(&mut self) -> bool {
+        false
+    }
+
+    //
+    // Some when calls can run side by side over the current connection,
+    // SharedClient calls through it without holding the client
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        None
+    }
+}
+
+//
+// The &self half of a client that multiplexes requests over one connection
+//
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+pub trait OMcpCallTrait: Send + Sync {
+    async fn call(&self, mcp_params: &McpParams) -> Result<String>;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl OMcpCallTrait for McpSession {
+    async fn call(&self, mcp_params: &McpParams) -> Result<String> {
+        self.call_tool(mcp_params).await
+    }
 }
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::debug;
+use tokio::task::JoinHandle;
+
+use crate::{
+    client::io::{OMcpCallTrait, OMcpClientTrait},
+    error::{Error, Result},
+    server::matrix::OmcpServer,
+    transport::{memory::MemoryTransport, session::McpSession},
+    types::{McpParams, McpTool},
+};
+
+type SessionSpawner = Arc<dyn Fn(MemoryTransport) -> JoinHandle<()> + Send + Sync>;
+
+//
+// Talks JSON-RPC to an OmcpServer living in the same process, over a
+// MemoryTransport pair instead of stdio or HTTP. Every connect() gets a
+// session of its own, tools are shared with the server it was created from
+//
+pub struct MemoryClient {
+    spawn_session: SessionSpawner,
+    session: Option<Arc<McpSession>>,
+    server_task: Option<JoinHandle<()>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl MemoryClient {
+    pub fn new<E>(server: &OmcpServer<E>) -> Self
+    where
+        E: std::fmt::Display + Send + 'static,
+    {
+        let new_session = server.session_factory();
+
+        let spawn_session: SessionSpawner = Arc::new(move |transport| {
+            let mut session = new_session();
+
+            tokio::spawn(async move {
+                if let Err(e) = session.serve_transport(transport).await {
+                    debug!("{e}");
+                }
+            })
+        });
+
+        Self {
+            spawn_session,
+            session: None,
+            server_task: None,
+        }
+    }
+
+    fn session(&self) -> Result<&McpSession> {
+        self.session.as_deref().ok_or(Error::NotConnected)
+    }
+}
+
+impl Drop for MemoryClient {
+    fn drop(&mut self) {
+        if let Some(task) = &self.server_task {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl OMcpClientTrait for MemoryClient {
+    async fn connect(&mut self) -> Result<()> {
+        self.disconnect().await?;
+
+        let (local, remote) = MemoryTransport::pair();
+        self.server_task = Some((self.spawn_session)(remote));
+
+        let session = McpSession::new(local);
+        session.initialize().await?;
+
+        self.session = Some(Arc::new(session));
+        Ok(())
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+
+        //
+        // the session sees Eof on its own, abort covers tool calls still
+        // running
+        //
+        if let Some(task) = self.server_task.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        self.session()?.list_tools().await
+    }
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        self.session()?.call_tool(params).await
+    }
+    fn take_tools_changed(&mut self) -> bool {
+        self.session.as_ref().is_some_and(|s| s.take_tools_changed())
+    }
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        Some(self.session.clone()?)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use crate::{
+        client::{io::OMcpClientTrait, memory::MemoryClient},
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("echo {}", params.tool_name))
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+
+        let mut client = MemoryClient::new(&server);
+        assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+
+        client.connect().await.unwrap();
+
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "echo");
+
+        let res = client.call(&McpParams::new("echo")).await.unwrap();
+        assert!(res.contains("echo echo"));
+
+        //
+        // tools added through the server show up on the live session
+        //
+        server.add_tool("other", EchoTool {});
+
+        let mut changed = false;
+
+        for _ in 0..100 {
+            changed = client.take_tools_changed();
+
+            if changed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(changed);
+
+        assert_eq!(client.list_tools().await.unwrap().len(), 2);
+
+        let res = client.call(&McpParams::new("nope")).await;
+        assert!(matches!(res, Err(Error::JsonRpcError { .. })));
+
+        client.disconnect().await.unwrap();
+        assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+
+        client.connect().await.unwrap();
+        assert_eq!(client.list_tools().await.unwrap().len(), 2);
+    }
+}
@@ -1,5 +1,24 @@
 pub mod baked;
+#[cfg(all(target_arch = "wasm32", feature = "browser"))]
+pub mod browser;
 pub mod builder;
+pub mod cache;
+pub mod credentials;
+pub mod group;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod http;
 pub mod io;
-mod sse;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod memory;
+pub mod oauth;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sandbox;
+pub mod shared;
+pub(crate) mod sse;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod stdio;
 pub mod types;
+#[cfg(unix)]
+pub mod unix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ws;
@@ -0,0 +1,1001 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use log::{debug, info, warn};
+use reqwest::{Client, Url, header::HeaderMap};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::codec::read_head;
+use crate::{
+    client::credentials::{CredentialProvider, bearer_headers},
+    error::{Error, Result},
+    json_rpc::CLIENT_NAME,
+    types::MaybeSendSync,
+};
+
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+#[cfg(not(target_arch = "wasm32"))]
+const REDIRECT_TIMEOUT: Duration = Duration::from_secs(300);
+#[cfg(not(target_arch = "wasm32"))]
+const REDIRECT_READ_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_REDIRECT_REQUEST: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    #[serde(default)]
+    pub token_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    //
+    // seconds since the epoch
+    //
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+//
+// Gets the user through the authorization page. authorize() gets the
+// authorization url and returns the url the server redirected to, code and
+// state included
+//
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait AuthorizationHandler: MaybeSendSync {
+    fn redirect_uri(&self) -> String;
+
+    async fn authorize(&self, url: &str) -> Result<String>;
+}
+
+//
+// tokens by MCP server url
+//
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait TokenStore: MaybeSendSync {
+    async fn load(&self, server: &str) -> Result<Option<OAuthTokens>>;
+
+    async fn save(&self, server: &str, tokens: &OAuthTokens) -> Result<()>;
+}
+
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<HashMap<String, OAuthTokens>>,
+}
+
+pub struct OAuthConfig {
+    handler: Arc<dyn AuthorizationHandler>,
+    store: Arc<dyn TokenStore>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    client_name: String,
+    scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ResourceMetadata {
+    #[serde(default)]
+    authorization_servers: Vec<String>,
+    #[serde(default)]
+    scopes_supported: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AuthServerMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    #[serde(default)]
+    registration_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistrationResponse {
+    client_id: String,
+    #[serde(default)]
+    client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+#[derive(Default)]
+struct OAuthState {
+    metadata: Option<AuthServerMetadata>,
+    scopes: Vec<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    tokens: Option<OAuthTokens>,
+    loaded: bool,
+}
+
+//
+// OAuth 2.1 authorization code + PKCE flow for one MCP server
+//
+pub struct OAuthClient {
+    http: Client,
+    server: String,
+    config: OAuthConfig,
+    state: tokio::sync::Mutex<OAuthState>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn random_string(len: usize) -> Result<String> {
+    let mut buf = vec![0u8; len];
+
+    getrandom::getrandom(&mut buf).map_err(|e| Error::AuthorizationFailure { error: e.to_string() })?;
+
+    Ok(URL_SAFE_NO_PAD.encode(buf))
+}
+
+fn pkce_challenge<S>(verifier: S) -> String
+where
+    S: AsRef<str>,
+{
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_ref().as_bytes()))
+}
+
+fn auth_failure<S>(error: S) -> Error
+where
+    S: AsRef<str>,
+{
+    Error::AuthorizationFailure {
+        error: error.as_ref().to_string(),
+    }
+}
+
+//
+// resource_metadata="https://..." from a WWW-Authenticate header
+//
+fn challenge_param<'a>(challenge: &'a str, name: &str) -> Option<&'a str> {
+    let start = challenge.find(&format!("{name}=\""))? + name.len() + 2;
+    let end = challenge[start..].find('"')? + start;
+    Some(&challenge[start..end])
+}
+
+//
+// RFC 8414 / 9728 well-known urls, the path of the original url goes last
+//
+fn well_known(url: &Url, suffix: &str) -> Vec<String> {
+    let origin = url.origin().ascii_serialization();
+    let path = url.path().trim_end_matches('/');
+
+    let mut urls = Vec::new();
+
+    if !path.is_empty() {
+        urls.push(format!("{origin}/.well-known/{suffix}{path}"));
+    }
+
+    urls.push(format!("{origin}/.well-known/{suffix}"));
+    urls
+}
+
+fn to_tokens(res: TokenResponse, previous_refresh: Option<String>) -> OAuthTokens {
+    OAuthTokens {
+        access_token: res.access_token,
+        token_type: res.token_type,
+        //
+        // servers don't always rotate refresh tokens
+        //
+        refresh_token: res.refresh_token.or(previous_refresh),
+        expires_at: res.expires_in.map(|s| now() + s),
+        scope: res.scope,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl OAuthTokens {
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(at) => now() + EXPIRY_MARGIN.as_secs() >= at,
+            None => false,
+        }
+    }
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl TokenStore for MemoryTokenStore {
+    async fn load(&self, server: &str) -> Result<Option<OAuthTokens>> {
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(tokens.get(server).cloned())
+    }
+
+    async fn save(&self, server: &str, tokens: &OAuthTokens) -> Result<()> {
+        let mut map = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        map.insert(server.to_string(), tokens.clone());
+        Ok(())
+    }
+}
+
+impl OAuthConfig {
+    pub fn new<H>(handler: H) -> Self
+    where
+        H: AuthorizationHandler + 'static,
+    {
+        Self {
+            handler: Arc::new(handler),
+            store: Arc::new(MemoryTokenStore::new()),
+            client_id: None,
+            client_secret: None,
+            client_name: CLIENT_NAME.into(),
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn with_token_store<T>(mut self, store: T) -> Self
+    where
+        T: TokenStore + 'static,
+    {
+        self.store = Arc::new(store);
+        self
+    }
+
+    //
+    // pre-registered client, skips dynamic client registration
+    //
+    pub fn with_client_id<S>(mut self, client_id: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.client_id = Some(client_id.as_ref().into());
+        self
+    }
+
+    pub fn with_client_secret<S>(mut self, client_secret: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.client_secret = Some(client_secret.as_ref().into());
+        self
+    }
+
+    pub fn with_client_name<S>(mut self, client_name: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.client_name = client_name.as_ref().into();
+        self
+    }
+
+    //
+    // defaults to whatever the protected resource metadata advertises
+    //
+    pub fn with_scope<S>(mut self, scope: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.scopes.push(scope.as_ref().into());
+        self
+    }
+}
+
+impl OAuthClient {
+    pub fn new<S>(server: S, config: OAuthConfig) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let state = OAuthState {
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            ..Default::default()
+        };
+
+        Self {
+            http: Client::new(),
+            server: server.as_ref().into(),
+            config,
+            state: tokio::sync::Mutex::new(state),
+        }
+    }
+
+    //
+    // shares the TLS settings of the MCP client
+    //
+    pub(crate) fn with_http_client(mut self, client: Client) -> Self {
+        self.http = client;
+        self
+    }
+
+    async fn get_json<T>(&self, url: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let res = self.http.get(url).header("Accept", "application/json").send().await?;
+
+        if !res.status().is_success() {
+            return Err(Error::HttpFailure);
+        }
+
+        Ok(serde_json::from_str(&res.text().await?)?)
+    }
+
+    async fn first_json<T>(&self, urls: &[String]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        for url in urls {
+            match self.get_json(url).await {
+                Ok(v) => return Ok(v),
+                Err(e) => debug!("{url}: {e}"),
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+
+    async fn discover(&self, state: &mut OAuthState, challenge: Option<&str>) -> Result<()> {
+        let server = Url::parse(&self.server).map_err(|_| Error::InvalidEndpoint)?;
+
+        let resource_urls = match challenge.and_then(|c| challenge_param(c, "resource_metadata")) {
+            Some(url) => vec![url.to_string()],
+            None => well_known(&server, "oauth-protected-resource"),
+        };
+
+        //
+        // servers without protected resource metadata are their own
+        // authorization server
+        //
+        let issuer = match self.first_json::<ResourceMetadata>(&resource_urls).await {
+            Ok(res) => {
+                state.scopes = res.scopes_supported;
+                res.authorization_servers.into_iter().next().ok_or(Error::NotFound)?
+            }
+            Err(_) => server.origin().ascii_serialization(),
+        };
+
+        let issuer = Url::parse(&issuer).map_err(|_| Error::InvalidEndpoint)?;
+
+        let mut metadata_urls = well_known(&issuer, "oauth-authorization-server");
+        metadata_urls.extend(well_known(&issuer, "openid-configuration"));
+
+        let metadata: AuthServerMetadata = self.first_json(&metadata_urls).await?;
+
+        info!("authorization server {}", issuer);
+
+        state.metadata = Some(metadata);
+        Ok(())
+    }
+
+    async fn register(&self, state: &mut OAuthState, metadata: &AuthServerMetadata) -> Result<()> {
+        if state.client_id.is_some() {
+            return Ok(());
+        }
+
+        let endpoint = metadata
+            .registration_endpoint
+            .as_ref()
+            .ok_or_else(|| auth_failure("no client id and no registration endpoint"))?;
+
+        let body = json!({
+            "client_name": self.config.client_name,
+            "redirect_uris": [self.config.handler.redirect_uri()],
+            "grant_types": ["authorization_code", "refresh_token"],
+            "response_types": ["code"],
+            "token_endpoint_auth_method": "none",
+        });
+
+        let res = self
+            .http
+            .post(endpoint)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(auth_failure(format!("registration failed with {}", res.status())));
+        }
+
+        let reg: RegistrationResponse = serde_json::from_str(&res.text().await?)?;
+
+        info!("registered client {}", reg.client_id);
+
+        state.client_id = Some(reg.client_id);
+        state.client_secret = reg.client_secret;
+        Ok(())
+    }
+
+    async fn token_request(
+        &self,
+        state: &OAuthState,
+        metadata: &AuthServerMetadata,
+        mut form: Vec<(&str, String)>,
+    ) -> Result<TokenResponse> {
+        if let Some(client_id) = &state.client_id {
+            form.push(("client_id", client_id.clone()));
+        }
+
+        if let Some(secret) = &state.client_secret {
+            form.push(("client_secret", secret.clone()));
+        }
+
+        form.push(("resource", self.server.clone()));
+
+        let res = self.http.post(&metadata.token_endpoint).form(&form).send().await?;
+
+        let status = res.status();
+        let body = res.text().await?;
+
+        if !status.is_success() {
+            return Err(auth_failure(format!("token endpoint returned {status}: {body}")));
+        }
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    async fn save(&self, state: &mut OAuthState, tokens: OAuthTokens) -> Result<String> {
+        self.config.store.save(&self.server, &tokens).await?;
+
+        let access_token = tokens.access_token.clone();
+        state.tokens = Some(tokens);
+        Ok(access_token)
+    }
+
+    async fn refresh_tokens(&self, state: &mut OAuthState) -> Result<String> {
+        let refresh_token = state
+            .tokens
+            .as_ref()
+            .and_then(|t| t.refresh_token.clone())
+            .ok_or_else(|| auth_failure("no refresh token"))?;
+
+        if state.metadata.is_none() {
+            self.discover(state, None).await?;
+        }
+
+        let metadata = state.metadata.clone().ok_or(Error::NotFound)?;
+
+        let form = vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", refresh_token.clone()),
+        ];
+
+        let res = self.token_request(state, &metadata, form).await?;
+
+        info!("refreshed access token");
+
+        self.save(state, to_tokens(res, Some(refresh_token))).await
+    }
+
+    //
+    // current access token, refreshed when it's about to expire. None when the
+    // user has to go through authorize()
+    //
+    pub async fn access_token(&self) -> Result<Option<String>> {
+        let mut state = self.state.lock().await;
+
+        if !state.loaded {
+            state.tokens = self.config.store.load(&self.server).await?;
+            state.loaded = true;
+        }
+
+        let expired = match &state.tokens {
+            Some(t) => t.is_expired(),
+            None => return Ok(None),
+        };
+
+        if !expired {
+            return Ok(state.tokens.as_ref().map(|t| t.access_token.clone()));
+        }
+
+        match self.refresh_tokens(&mut state).await {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => {
+                warn!("{e}");
+                state.tokens = None;
+                Ok(None)
+            }
+        }
+    }
+
+    //
+    // after a 401, challenge is the WWW-Authenticate header
+    //
+    pub async fn authorize(&self, challenge: Option<&str>) -> Result<String> {
+        let mut state = self.state.lock().await;
+
+        self.discover(&mut state, challenge).await?;
+
+        let metadata = state.metadata.clone().ok_or(Error::NotFound)?;
+
+        self.register(&mut state, &metadata).await?;
+
+        let client_id = state.client_id.clone().ok_or(Error::NotFound)?;
+        let redirect_uri = self.config.handler.redirect_uri();
+
+        let verifier = random_string(32)?;
+        let csrf = random_string(16)?;
+
+        let scopes = match self.config.scopes.is_empty() {
+            true => state.scopes.join(" "),
+            false => self.config.scopes.join(" "),
+        };
+
+        let mut url = Url::parse(&metadata.authorization_endpoint).map_err(|_| Error::InvalidEndpoint)?;
+
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &client_id)
+                .append_pair("redirect_uri", &redirect_uri)
+                .append_pair("code_challenge", &pkce_challenge(&verifier))
+                .append_pair("code_challenge_method", "S256")
+                .append_pair("state", &csrf)
+                .append_pair("resource", &self.server);
+
+            if !scopes.is_empty() {
+                query.append_pair("scope", &scopes);
+            }
+        }
+
+        let redirect = self.config.handler.authorize(url.as_str()).await?;
+        let redirect = Url::parse(&redirect).map_err(|_| Error::InvalidEndpoint)?;
+
+        let params: HashMap<String, String> = redirect.query_pairs().into_owned().collect();
+
+        if let Some(error) = params.get("error") {
+            return Err(auth_failure(error));
+        }
+
+        if params.get("state") != Some(&csrf) {
+            return Err(auth_failure("state mismatch"));
+        }
+
+        let code = params.get("code").ok_or_else(|| auth_failure("no authorization code"))?;
+
+        let form = vec![
+            ("grant_type", "authorization_code".to_string()),
+            ("code", code.clone()),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", verifier),
+        ];
+
+        let res = self.token_request(&state, &metadata, form).await?;
+
+        info!("authorized with {}", metadata.authorization_endpoint);
+
+        self.save(&mut state, to_tokens(res, None)).await
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl CredentialProvider for OAuthClient {
+    async fn headers(&self) -> Result<HeaderMap> {
+        match self.access_token().await? {
+            Some(token) => bearer_headers(token),
+            None => Ok(HeaderMap::new()),
+        }
+    }
+
+    //
+    // the refresh token first, the user only sees the authorization page when
+    // that's turned down or the server asks for more scopes
+    //
+    async fn refresh(&self, challenge: Option<&str>) -> Result<bool> {
+        let insufficient_scope = challenge.and_then(|c| challenge_param(c, "error")) == Some("insufficient_scope");
+
+        if !insufficient_scope {
+            let mut state = self.state.lock().await;
+
+            if state.tokens.as_ref().is_some_and(|t| t.refresh_token.is_some()) {
+                match self.refresh_tokens(&mut state).await {
+                    Ok(_) => return Ok(true),
+                    Err(e) => warn!("{e}"),
+                }
+            }
+        }
+
+        self.authorize(challenge).await?;
+        Ok(true)
+    }
+}
+
+//
+// Receives the redirect on 127.0.0.1, the opener is given the authorization
+// url and is expected to open a browser
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub struct LoopbackRedirectHandler {
+    listener: tokio::sync::Mutex<tokio::net::TcpListener>,
+    port: u16,
+    opener: Box<dyn Fn(&str) + Send + Sync>,
+    timeout: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LoopbackRedirectHandler {
+    //
+    // 0 picks any free port. Without an opener the url is only logged
+    //
+    pub async fn bind(port: u16) -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+        let port = listener.local_addr()?.port();
+
+        Ok(Self {
+            listener: tokio::sync::Mutex::new(listener),
+            port,
+            opener: Box::new(|url| info!("open {url} to authorize")),
+            timeout: REDIRECT_TIMEOUT,
+        })
+    }
+
+    pub fn with_opener<F>(mut self, opener: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.opener = Box::new(opener);
+        self
+    }
+
+    //
+    // how long the user gets to go through the authorization page
+    //
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl AuthorizationHandler for LoopbackRedirectHandler {
+    fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}/callback", self.port)
+    }
+
+    async fn authorize(&self, url: &str) -> Result<String> {
+        use tokio::{
+            io::AsyncWriteExt,
+            time::{Instant, timeout, timeout_at},
+        };
+
+        let listener = self.listener.lock().await;
+
+        (self.opener)(url);
+
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            let (mut stream, _) = match timeout_at(deadline, listener.accept()).await {
+                Ok(v) => v?,
+                Err(_) => return Err(auth_failure("timed out waiting for the redirect")),
+            };
+
+            //
+            // a connection that never finishes its request is given up on
+            // after a while, the next one gets its turn
+            //
+            let request = match timeout(REDIRECT_READ_TIMEOUT, read_head(&mut stream, MAX_REDIRECT_REQUEST)).await {
+                Ok(Ok(v)) => v,
+                Ok(Err(e)) => {
+                    debug!("{e}");
+                    continue;
+                }
+                Err(_) => {
+                    debug!("redirect request timed out");
+                    continue;
+                }
+            };
+
+            //
+            // GET /callback?code=...&state=... HTTP/1.1
+            //
+            let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+            if !path.starts_with("/callback") {
+                stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await?;
+                continue;
+            }
+
+            let body = "Authorization complete, you can close this window";
+            let res = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+
+            stream.write_all(res.as_bytes()).await?;
+
+            return Ok(format!("http://127.0.0.1:{}{path}", self.port));
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use axum::{
+        Form, Json, Router,
+        extract::{Query, State},
+        http::{StatusCode, header},
+        response::{IntoResponse, Response},
+        routing::{get, post},
+    };
+    use serde_json::{Value, json};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{
+        client::{
+            builder::OMcpClientBuilder,
+            oauth::{
+                AuthorizationHandler, LoopbackRedirectHandler, MemoryTokenStore, OAuthConfig, OAuthTokens, TokenStore,
+                pkce_challenge,
+            },
+            types::OMcpServerType,
+        },
+        error::{Error, Result},
+        server::{
+            auth::{BearerAuthenticator, Principal},
+            matrix::OmcpServer,
+        },
+    };
+
+    //
+    // counts the times the user would have been sent to the browser
+    //
+    struct NoBrowser {
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl AuthorizationHandler for NoBrowser {
+        fn redirect_uri(&self) -> String {
+            "http://127.0.0.1:1/callback".to_string()
+        }
+
+        async fn authorize(&self, _url: &str) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(Error::Unauthorized)
+        }
+    }
+
+    //
+    // stand-in authorization server
+    //
+    #[derive(Clone, Default)]
+    struct AuthServer {
+        base: String,
+        challenge: Arc<Mutex<String>>,
+        refreshed: Arc<AtomicU32>,
+    }
+
+    async fn resource_metadata(State(s): State<AuthServer>) -> Json<Value> {
+        Json(json!({"resource": format!("{}/sse", s.base), "authorization_servers": [s.base]}))
+    }
+
+    async fn server_metadata(State(s): State<AuthServer>) -> Json<Value> {
+        Json(json!({
+            "issuer": s.base,
+            "authorization_endpoint": format!("{}/authorize", s.base),
+            "token_endpoint": format!("{}/token", s.base),
+            "registration_endpoint": format!("{}/register", s.base),
+        }))
+    }
+
+    async fn register(body: String) -> Json<Value> {
+        let req: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(req["token_endpoint_auth_method"], "none");
+        Json(json!({"client_id": "client-1"}))
+    }
+
+    async fn authorize(State(s): State<AuthServer>, Query(q): Query<HashMap<String, String>>) -> Response {
+        assert_eq!(q["client_id"], "client-1");
+        assert_eq!(q["code_challenge_method"], "S256");
+
+        *s.challenge.lock().unwrap() = q["code_challenge"].clone();
+
+        let location = format!("{}?code=code-1&state={}", q["redirect_uri"], q["state"]);
+        (StatusCode::FOUND, [(header::LOCATION, location)]).into_response()
+    }
+
+    async fn token(State(s): State<AuthServer>, Form(f): Form<HashMap<String, String>>) -> Response {
+        match f["grant_type"].as_str() {
+            "authorization_code" => {
+                assert_eq!(f["code"], "code-1");
+                assert_eq!(pkce_challenge(&f["code_verifier"]), *s.challenge.lock().unwrap());
+
+                //
+                // already expired so the client has to refresh it
+                //
+                Json(json!({
+                    "access_token": "access-1",
+                    "token_type": "Bearer",
+                    "refresh_token": "refresh-1",
+                    "expires_in": 0
+                }))
+                .into_response()
+            }
+            "refresh_token" => {
+                assert_eq!(f["refresh_token"], "refresh-1");
+                s.refreshed.fetch_add(1, Ordering::SeqCst);
+                Json(json!({"access_token": "access-2", "token_type": "Bearer", "expires_in": 3600})).into_response()
+            }
+            _ => StatusCode::BAD_REQUEST.into_response(),
+        }
+    }
+
+    #[tokio::test]
+    async fn authorization_code_pkce() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = AuthServer {
+            base: base.clone(),
+            ..Default::default()
+        };
+
+        let auth = BearerAuthenticator::new()
+            .with_resource_metadata(format!("{base}/.well-known/oauth-protected-resource"))
+            .with_token("access-2", Principal::new("alice"));
+
+        let server = OmcpServer::<Error>::new().with_authenticator(auth);
+
+        let app = Router::new()
+            .route("/.well-known/oauth-protected-resource", get(resource_metadata))
+            .route("/.well-known/oauth-authorization-server", get(server_metadata))
+            .route("/register", post(register))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .with_state(state.clone())
+            .merge(server.sse_router());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        //
+        // plays the browser, follows the redirect back to the loopback handler
+        //
+        let handler = LoopbackRedirectHandler::bind(0).await.unwrap().with_opener(|url| {
+            let url = url.to_string();
+            tokio::spawn(async move { reqwest::get(url).await });
+        });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("{base}/sse"))
+            .with_oauth(OAuthConfig::new(handler))
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+
+        let tools = client.list_tools().await.unwrap();
+        assert!(tools.is_empty());
+        assert_eq!(state.refreshed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn refresh_token_before_authorize() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = AuthServer {
+            base: base.clone(),
+            ..Default::default()
+        };
+
+        let auth = BearerAuthenticator::new()
+            .with_resource_metadata(format!("{base}/.well-known/oauth-protected-resource"))
+            .with_token("access-2", Principal::new("alice"));
+
+        let server = OmcpServer::<Error>::new().with_authenticator(auth);
+
+        let app = Router::new()
+            .route("/.well-known/oauth-protected-resource", get(resource_metadata))
+            .route("/.well-known/oauth-authorization-server", get(server_metadata))
+            .route("/token", post(token))
+            .with_state(state.clone())
+            .merge(server.sse_router());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        //
+        // not expired as far as the client knows, the server revoked it
+        //
+        let store = MemoryTokenStore::new();
+        let tokens = OAuthTokens {
+            access_token: "revoked".to_string(),
+            refresh_token: Some("refresh-1".to_string()),
+            ..Default::default()
+        };
+        store.save(&format!("{base}/sse"), &tokens).await.unwrap();
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let handler = NoBrowser { calls: calls.clone() };
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("{base}/sse"))
+            .with_oauth(OAuthConfig::new(handler).with_token_store(store))
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+
+        assert_eq!(state.refreshed.load(Ordering::SeqCst), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn loopback_redirect() {
+        let handler = LoopbackRedirectHandler::bind(0)
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_millis(500));
+
+        let uri = handler.redirect_uri();
+        let addr = uri.trim_start_matches("http://").trim_end_matches("/callback").to_string();
+
+        tokio::spawn(async move {
+            //
+            // the request comes in pieces
+            //
+            let mut stream = tokio::net::TcpStream::connect(&addr).await.unwrap();
+            stream.write_all(b"GET /callback?code=c&state=s HTTP/1.1\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stream.write_all(b"Host: 127.0.0.1\r\n\r\n").await.unwrap();
+
+            let mut res = String::new();
+            stream.read_to_string(&mut res).await.unwrap();
+            assert!(res.starts_with("HTTP/1.1 200"));
+        });
+
+        let redirect = handler.authorize("http://auth/").await.unwrap();
+        assert!(redirect.ends_with("/callback?code=c&state=s"));
+
+        //
+        // nobody comes back
+        //
+        assert!(matches!(
+            handler.authorize("http://auth/").await,
+            Err(Error::AuthorizationFailure { .. })
+        ));
+    }
+}
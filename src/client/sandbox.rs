@@ -0,0 +1,263 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[cfg(not(unix))]
+use log::warn;
+use tokio::process::Command;
+
+//
+// Containment for third party servers started by StdioServer. Everything is
+// off by default, the limits are only enforced on unix
+//
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    pub cpu_time: Option<Duration>,
+    pub memory: Option<u64>,
+    pub open_files: Option<u64>,
+    pub process_group: bool,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub env_allowlist: Option<Vec<String>>,
+    pub wrapper: Option<(PathBuf, Vec<String>)>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Sandbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //
+    // RLIMIT_CPU, the child gets SIGXCPU then SIGKILL past it
+    //
+    pub fn with_cpu_time(mut self, limit: Duration) -> Self {
+        self.cpu_time = Some(limit);
+        self
+    }
+
+    //
+    // RLIMIT_AS in bytes
+    //
+    pub fn with_memory(mut self, bytes: u64) -> Self {
+        self.memory = Some(bytes);
+        self
+    }
+
+    //
+    // RLIMIT_NOFILE
+    //
+    pub fn with_open_files(mut self, max: u64) -> Self {
+        self.open_files = Some(max);
+        self
+    }
+
+    //
+    // the child leads a process group of its own and disconnect() signals the
+    // whole group, grandchildren included
+    //
+    pub fn with_process_group(mut self) -> Self {
+        self.process_group = true;
+        self
+    }
+
+    //
+    // needs the privileges to do so, supplementary groups are dropped when
+    // running as root
+    //
+    pub fn with_user(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = Some(uid);
+        self.gid = Some(gid);
+        self
+    }
+
+    //
+    // only these variables of our environment are passed on, see
+    // StdioServer::with_clear_env() for how it combines with the rest
+    //
+    pub fn with_env_allowlist<S>(mut self, names: &[S]) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let names = names.iter().map(|n| n.as_ref().to_string()).collect();
+        self.env_allowlist = Some(names);
+        self
+    }
+
+    //
+    // e.g. bwrap or firejail, the program and its arguments come after the
+    // wrapper arguments
+    //
+    pub fn with_wrapper<P, S>(mut self, program: P, args: &[S]) -> Self
+    where
+        P: AsRef<Path>,
+        S: AsRef<str>,
+    {
+        let args = args.iter().map(|a| a.as_ref().to_string()).collect();
+        self.wrapper = Some((program.as_ref().to_path_buf(), args));
+        self
+    }
+
+    pub(crate) fn command_line(&self, program: &Path, args: &[String]) -> (PathBuf, Vec<String>) {
+        match &self.wrapper {
+            Some((wrapper, wrapper_args)) => {
+                let mut all = wrapper_args.clone();
+                all.push(program.display().to_string());
+                all.extend(args.iter().cloned());
+                (wrapper.clone(), all)
+            }
+            None => (program.to_path_buf(), args.to_vec()),
+        }
+    }
+
+    pub(crate) fn apply(&self, command: &mut Command) {
+        if let Some(names) = &self.env_allowlist {
+            command.env_clear();
+
+            for name in names {
+                if let Ok(value) = env::var(name) {
+                    command.env(name, value);
+                }
+            }
+        }
+
+        self.apply_process(command);
+    }
+
+    #[cfg(unix)]
+    fn apply_process(&self, command: &mut Command) {
+        if self.process_group {
+            command.process_group(0);
+        }
+
+        if let Some(gid) = self.gid {
+            command.gid(gid);
+        }
+
+        if let Some(uid) = self.uid {
+            command.uid(uid);
+        }
+
+        let limits: Vec<_> = [
+            (libc::RLIMIT_CPU, self.cpu_time.map(|d| d.as_secs().max(1))),
+            (libc::RLIMIT_AS, self.memory),
+            (libc::RLIMIT_NOFILE, self.open_files),
+        ]
+        .into_iter()
+        .filter_map(|(resource, limit)| limit.map(|l| (resource, l as libc::rlim_t)))
+        .collect();
+
+        if limits.is_empty() {
+            return;
+        }
+
+        //
+        // SAFETY: runs between fork and exec, setrlimit() is async signal safe
+        // and nothing gets allocated there
+        //
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, limit) in limits.iter() {
+                    let rlimit = libc::rlimit {
+                        rlim_cur: *limit,
+                        rlim_max: *limit,
+                    };
+
+                    if libc::setrlimit(*resource, &rlimit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn apply_process(&self, _command: &mut Command) {
+        if self.process_group || self.uid.is_some() || self.cpu_time.is_some() {
+            warn!("process containment is only supported on unix");
+        }
+    }
+
+    //
+    // signals the group instead of the child alone
+    //
+    #[cfg(unix)]
+    pub(crate) fn signal_target(&self, pid: u32) -> i32 {
+        match self.process_group {
+            true => -(pid as i32),
+            false => pid as i32,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{path::Path, process::Stdio, time::Duration};
+
+    use tokio::process::Command;
+
+    use crate::client::sandbox::Sandbox;
+
+    #[tokio::test]
+    async fn limits_group_env_wrapper() {
+        let sandbox = Sandbox::new()
+            .with_open_files(64)
+            .with_cpu_time(Duration::from_secs(5))
+            .with_process_group()
+            .with_env_allowlist(&["PATH"])
+            .with_wrapper("/usr/bin/env", &["-u", "HOME"]);
+
+        let script = r#"ulimit -n; ulimit -t; echo "$HOME|$PATH"; cut -d' ' -f5 /proc/$$/stat; echo $$"#;
+        let args = ["-c".to_string(), script.to_string()];
+
+        let (program, args) = sandbox.command_line(Path::new("/bin/sh"), &args);
+        assert_eq!(program, Path::new("/usr/bin/env"));
+        assert_eq!(args[..3], ["-u", "HOME", "/bin/sh"]);
+
+        let mut command = Command::new(program);
+        command.args(args).stdout(Stdio::piped());
+        sandbox.apply(&mut command);
+
+        let output = command.output().await.unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+
+        assert_eq!(lines[0], "64");
+        assert_eq!(lines[1], "5");
+        assert!(lines[2].starts_with('|'));
+        assert!(lines[2].len() > 1);
+        assert_eq!(lines[3], lines[4]);
+    }
+
+    #[tokio::test]
+    async fn memory_and_user() {
+        //
+        // root can become anybody, everybody else only themselves. SAFETY:
+        // getuid() and getgid() can't fail
+        //
+        let (uid, gid) = match unsafe { libc::getuid() } {
+            0 => (65534, 65534),
+            uid => (uid, unsafe { libc::getgid() }),
+        };
+
+        let sandbox = Sandbox::new().with_memory(512 * 1024 * 1024).with_user(uid, gid);
+
+        let mut command = Command::new("/bin/sh");
+        command.args(["-c", "ulimit -v; id -u; id -g"]).stdout(Stdio::piped());
+        sandbox.apply(&mut command);
+
+        let output = command.output().await.unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+
+        assert_eq!(lines, ["524288".to_string(), uid.to_string(), gid.to_string()]);
+    }
+}
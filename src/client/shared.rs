@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::{
+    client::io::OMcpClientTrait,
+    error::Result,
+    types::{McpParams, McpTool},
+};
+
+//
+// Cloneable client over one connection. Tool calls from different tasks run
+// side by side when the client has a caller(), everything else takes turns
+//
+#[derive(Clone)]
+pub struct SharedClient {
+    inner: Arc<Mutex<Box<dyn OMcpClientTrait>>>,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// IMPL
+///////////////////////////////////////////////////////////////////////////////
+
+impl SharedClient {
+    pub fn new(client: Box<dyn OMcpClientTrait>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(client)),
+        }
+    }
+
+    pub async fn connect(&self) -> Result<()> {
+        self.inner.lock().await.connect().await
+    }
+
+    pub async fn disconnect(&self) -> Result<()> {
+        self.inner.lock().await.disconnect().await
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>> {
+        self.inner.lock().await.list_tools().await
+    }
+
+    pub async fn call(&self, mcp_params: &McpParams) -> Result<String> {
+        //
+        // the lock is only held to pick up the caller
+        //
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let caller = self.inner.lock().await.caller();
+
+            if let Some(caller) = caller {
+                return caller.call(mcp_params).await;
+            }
+        }
+
+        self.inner.lock().await.call(mcp_params).await
+    }
+}
+
+impl From<Box<dyn OMcpClientTrait>> for SharedClient {
+    fn from(client: Box<dyn OMcpClientTrait>) -> Self {
+        Self::new(client)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TEST
+///////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use tokio::{net::TcpListener, sync::Barrier, task::JoinSet, time::timeout};
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, io::OMcpClientTrait, shared::SharedClient, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, handle::OmcpServerHandle, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("echo {}", params.get_string("text")?))
+        }
+    }
+
+    //
+    // only returns once the other call is in flight too
+    //
+    struct BarrierTool {
+        barrier: Arc<Barrier>,
+    }
+
+    #[async_trait]
+    impl BakedMcpToolTrait for BarrierTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            self.barrier.wait().await;
+            Ok("done".to_string())
+        }
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<Box<dyn OMcpClientTrait>>();
+        assert_send_sync::<SharedClient>();
+        assert_send_sync::<OmcpServerHandle<crate::error::Error>>();
+    }
+
+    //
+    // every task gets its own answer back over the one connection
+    //
+    #[tokio::test]
+    async fn spawn() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("http://{addr}/sse"))
+            .build_shared()
+            .unwrap();
+
+        client.connect().await.unwrap();
+
+        let mut tasks = JoinSet::new();
+
+        for i in 0..8 {
+            let client = client.clone();
+
+            tasks.spawn(async move {
+                let tools = client.list_tools().await?;
+
+                let mut params = McpParams::new("echo");
+                params.add_argument("text", format!("task {i}").into());
+
+                let res = client.call(&params).await?;
+                Ok::<_, Error>((i, tools.len(), res))
+            });
+        }
+
+        while let Some(res) = tasks.join_next().await {
+            let (i, tools, res) = res.unwrap().unwrap();
+            assert_eq!(tools, 1);
+            assert!(res.contains(&format!("echo task {i}")));
+        }
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn calls_overlap() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool(
+            "barrier",
+            BarrierTool {
+                barrier: Arc::new(Barrier::new(2)),
+            },
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("http://{addr}/sse"))
+            .with_tools_cache()
+            .build_shared()
+            .unwrap();
+
+        client.connect().await.unwrap();
+
+        let mut tasks = JoinSet::new();
+
+        for _ in 0..2 {
+            let client = client.clone();
+            tasks.spawn(async move { client.call(&McpParams::new("barrier")).await });
+        }
+
+        //
+        // serialized calls would leave the first one waiting forever
+        //
+        let done = timeout(Duration::from_secs(5), tasks.join_all()).await.unwrap();
+
+        for res in done {
+            assert!(res.unwrap().contains("done"));
+        }
+
+        client.disconnect().await.unwrap();
+    }
+}
@@ -1,42 +1,56 @@
-use std::{collections::HashMap, sync::atomic::AtomicU64};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
 
+#[cfg(not(target_arch = "wasm32"))]
 use async_trait::async_trait;
+#[cfg(not(target_arch = "wasm32"))]
 use bytes::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
 use futures_util::{Stream, StreamExt};
-use log::{debug, error};
+use log::debug;
+#[cfg(not(target_arch = "wasm32"))]
+use log::error;
 
-use reqwest::{Client, Response, header::HeaderMap};
-use serde_json::Value;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Response;
 
+use crate::{
+    client::types::{SseEvent, SseWireEvent},
+    error::{Error, Result},
+};
+#[cfg(not(target_arch = "wasm32"))]
 use crate::{
     client::{
         builder::OMcpClientBuilder,
-        io::OMcpClientTrait,
-        types::{SseEvent, SseEventEndpoint, SseWireEvent},
+        io::{OMcpCallTrait, OMcpClientTrait},
     },
-    error::{Error, Result},
-    json_rpc::{JsonRPCInitParams, JsonRPCMessage, JsonRPCMessageBuilder, JsonRPCParameters},
+    transport::{session::McpSession, sse::SseConnector},
     types::{McpParams, McpTool},
 };
 
-#[derive(Debug)]
-enum SseClientState {
-    Uninitialized,
-    Initialized,
-    Ready,
-}
+#[cfg(not(target_arch = "wasm32"))]
+type BytesStream = std::pin::Pin<Box<dyn Stream<Item = core::result::Result<Bytes, reqwest::Error>> + Send + Sync>>;
 
-//type BytesStream = Pin<Box<dyn Stream<Item = core::result::Result<Bytes, reqwest::Error>> + Send>>;
-type BytesStream = std::pin::Pin<Box<dyn Stream<Item = core::result::Result<Bytes, reqwest::Error>>>>;
+//
+// GET side of an SSE connection, the legacy transport and the Streamable
+// HTTP streams both read their events with it
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct SseReader {
+    server: String,
+    stream: BytesStream,
+    pending: Vec<u8>,
+}
 
+//
+// OMcpServerType::Sse, the HTTP+SSE transport. connect() opens a new GET
+// stream every time, the server ends the session once it's gone. call()
+// fails with Err when the message can't be delivered, not Ok("Error: ...")
+//
+#[cfg(not(target_arch = "wasm32"))]
 pub struct SseClient {
-    client: Client,
-    server: String,
-    headers: HeaderMap,
-    endpoint: Option<SseEventEndpoint>,
-    state: SseClientState,
-    msg_id: AtomicU64,
-    stream: Option<BytesStream>,
+    connector: SseConnector,
+    session: Option<Arc<McpSession>>,
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -50,270 +64,143 @@ where
 {
     let data = str::from_utf8(data.as_ref())?;
 
-    let lines: Vec<&str> = data.lines().collect();
-
     let mut wire = SseWireEvent::new(server.as_ref());
+    let mut data_lines: Vec<&str> = Vec::new();
 
-    for line in lines {
+    for line in data.lines() {
         if line.is_empty() {
             continue;
         }
 
-        if line.starts_with(": ") {
+        if line.starts_with(':') {
             debug!("ignoring {line}");
             continue;
         }
 
-        if let Some(event) = line.strip_prefix("event: ") {
-            debug!("{event}");
-            wire.event = event;
-        } else if let Some(data) = line.strip_prefix("data: ") {
-            debug!("{data}");
-            wire.data = data;
-        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
 
-        if !wire.data.is_empty() && !wire.event.is_empty() {
-            let event: SseEvent = wire.try_into()?;
-            return Ok(event);
+        match field {
+            "event" => wire.event = value,
+            "data" => data_lines.push(value),
+            _ => debug!("ignoring {line}"),
         }
     }
 
-    Err(Error::NotFound)
-}
-
-async fn sse_http_connect<U>(client: &Client, url: U, headers: &HeaderMap) -> Result<Response>
-where
-    U: AsRef<str>,
-{
-    let headers_clone = headers.clone();
-
-    let response = client.get(url.as_ref()).headers(headers_clone).send().await?;
-
-    match response.status().is_success() {
-        true => Ok(response),
-        false => Err(Error::ConnectionFailure),
+    if data_lines.is_empty() {
+        return Err(Error::NotFound);
     }
-}
-
-fn build_init_message() -> Result<JsonRPCMessage> {
-    let init_params = JsonRPCInitParams::new();
-    let init_string = serde_json::to_string(&init_params)?;
 
-    let params: HashMap<String, Value> = serde_json::from_str(&init_string)?;
+    //
+    // events without a name are messages
+    //
+    if wire.event.is_empty() {
+        wire.event = "message";
+    }
 
-    let b = JsonRPCMessageBuilder::new()
-        .with_id(1)
-        .with_method("initialize")
-        .with_parameter(params);
+    let data = data_lines.join("\n");
+    wire.data = &data;
 
-    Ok(b.build())
+    let event: SseEvent = wire.try_into()?;
+    Ok(event)
 }
 
-async fn read_all(stream: &mut BytesStream) -> Result<Vec<u8>> {
-    let mut vec: Vec<u8> = Vec::new();
-
-    loop {
-        match stream.next().await {
-            Some(Ok(v)) => {
-                let data = v.to_vec();
-                vec.extend(data);
-
-                if vec.ends_with(b"\r\n\r\n") {
-                    break;
-                }
-            }
-            Some(Err(e)) => {
-                error!("{e}");
-                return Err(e.into());
-            }
-            None => break,
-        }
-    }
-
-    Ok(vec)
+//
+// one event per blank line separated block
+//
+pub(crate) fn sse_next_block(pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let end = pending.windows(2).position(|w| w == b"\n\n")?;
+    let mut block: Vec<u8> = pending.drain(..end + 2).collect();
+    block.truncate(end);
+    Some(block)
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 // IMPL
 ///////////////////////////////////////////////////////////////////////////////
 
-impl SseClient {
-    pub fn from_builder(builder: OMcpClientBuilder) -> Self {
-        SseClient {
-            client: Client::new(),
-            server: builder.url,
-            headers: builder.headers,
-            endpoint: None,
-            state: SseClientState::Uninitialized,
-            msg_id: AtomicU64::new(1),
-            stream: None,
-        }
-    }
-
-    pub async fn recv_message(&mut self) -> Result<JsonRPCMessage> {
-        let data = match self.stream.as_mut() {
-            Some(v) => read_all(v).await?,
-            None => return Err(Error::NotConnected),
-        };
-
-        match self.sse_parse(data)? {
-            SseEvent::Endpoint(_e) => Err(Error::NotConnected),
-            SseEvent::JsonRpcMessage(msg) => Ok(*msg),
-        }
-    }
-    pub async fn send_message<M>(&self, msg: M) -> Result<()>
+#[cfg(not(target_arch = "wasm32"))]
+impl SseReader {
+    pub(crate) fn new<S>(server: S, response: Response) -> Self
     where
-        M: AsRef<JsonRPCMessage>,
+        S: AsRef<str>,
     {
-        match &self.endpoint {
-            Some(endpoint) => {
-                //
-                // we have to use a different http connection for this one
-                //
-                let json_msg = serde_json::to_string_pretty(msg.as_ref())?;
-
-                debug!("sending: {json_msg}");
-
-                let headers = self.headers.clone();
-
-                self.client
-                    .post(&endpoint.url)
-                    .header("Content-Type", "application/json")
-                    .headers(headers)
-                    .body(json_msg)
-                    .send()
-                    .await?;
-
-                Ok(())
-            }
-            None => Err(Error::NotConnected),
+        Self {
+            server: server.as_ref().to_string(),
+            stream: Box::pin(response.bytes_stream()),
+            pending: Vec::new(),
         }
     }
 
-    fn sse_parse<D>(&mut self, data: D) -> Result<SseEvent>
-    where
-        D: AsRef<[u8]>,
-    {
-        sse_parse_wire(&self.server, data)
-    }
-
-    //
-    // This'll also handle reconnections
-    //
-    async fn init_connection(&mut self) -> Result<()> {
+    pub(crate) async fn next_event(&mut self) -> Result<SseEvent> {
         loop {
-            //
-            // server sends a hello message first
-            //
-            let data = match self.stream.as_mut() {
-                Some(v) => read_all(v).await?,
-                None => break Err(Error::NotConnected),
-            };
-
-            let event = self.sse_parse(data)?;
-
-            match event {
-                SseEvent::Endpoint(e) => {
-                    self.state = SseClientState::Uninitialized;
-                    self.endpoint = Some(e);
+            while let Some(block) = sse_next_block(&mut self.pending) {
+                match sse_parse_wire(&self.server, block) {
+                    Ok(event) => return Ok(event),
+                    Err(Error::NotFound) => continue,
+                    Err(e) => return Err(e),
                 }
-                SseEvent::JsonRpcMessage(_msg) => {}
             }
 
-            //
-            // we have a msg
-            //
-            match self.state {
-                SseClientState::Uninitialized => {
-                    self.state = SseClientState::Initialized;
-                    self.send_hello().await?;
-                }
-                SseClientState::Initialized => {
-                    self.send_initialized().await?;
-                    self.state = SseClientState::Ready;
-                    break Ok(());
-                }
-                SseClientState::Ready => {
-                    break Ok(());
+            match self.stream.next().await {
+                //
+                // \r\n and \n are both valid line endings, CRs never show up
+                // in the JSON payload
+                //
+                Some(Ok(v)) => self.pending.extend(v.iter().filter(|b| **b != b'\r')),
+                Some(Err(e)) => {
+                    error!("{e}");
+                    return Err(e.into());
                 }
+                None => return Err(Error::Eof),
             }
         }
     }
+}
 
-    async fn send_hello(&self) -> Result<()> {
-        let msg = build_init_message()?;
-        self.send_message(msg).await
-    }
-
-    async fn send_initialized(&self) -> Result<()> {
-        let msg = JsonRPCMessageBuilder::new().with_method("notifications/initialized").build();
-        self.send_message(msg).await
+#[cfg(not(target_arch = "wasm32"))]
+impl SseClient {
+    pub fn from_builder(builder: OMcpClientBuilder) -> Result<Self> {
+        Ok(Self {
+            connector: SseConnector::from_builder(builder)?,
+            session: None,
+        })
     }
 
-    async fn send_list_tools(&self) -> Result<()> {
-        let msg = JsonRPCMessageBuilder::new().with_id(2).with_method("tools/list").build();
-        self.send_message(msg).await
+    fn session(&self) -> Result<&McpSession> {
+        self.session.as_deref().ok_or(Error::NotConnected)
     }
 }
 
-#[async_trait(?Send)]
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
 impl OMcpClientTrait for SseClient {
     async fn connect(&mut self) -> Result<()> {
-        let response = sse_http_connect(&self.client, &self.server, &self.headers).await?;
+        self.disconnect().await?;
 
-        let stream = response.bytes_stream();
-
-        self.stream = Some(Box::pin(stream));
-
-        self.init_connection().await?;
+        let session = McpSession::new(self.connector.connect().await?);
+        session.initialize().await?;
 
+        self.session = Some(Arc::new(session));
         Ok(())
     }
     async fn disconnect(&mut self) -> Result<()> {
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+
         Ok(())
     }
     async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
-        self.send_list_tools().await?;
-
-        let msg = self.recv_message().await?;
-
-        let mut results = msg.result.ok_or(Error::NotFound)?;
-
-        let tool_value = results.remove("tools").ok_or(Error::NotFound)?;
-
-        let tools: Vec<McpTool> = match serde_json::from_value(tool_value) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("{e}");
-                return Err(e.into());
-            }
-        };
-
-        Ok(tools)
+        self.session()?.list_tools().await
     }
-    async fn call(&mut self, mcp_params: &McpParams) -> Result<String> {
-        let id = self.msg_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-
-        let params: JsonRPCParameters = mcp_params.as_ref().try_into()?;
-
-        let msg = JsonRPCMessageBuilder::new()
-            .with_id(id)
-            .with_method("tools/call")
-            .with_parameter(params)
-            .build();
-
-        if let Err(e) = self.send_message(msg).await {
-            error!("{e}");
-            return Ok(format!("Error: {e}"));
-        }
-
-        match self.recv_message().await {
-            Ok(v) => {
-                let results = v.result.ok_or(Error::NotFound)?;
-                let results = serde_json::to_string_pretty(&results)?;
-                Ok(results)
-            }
-            Err(e) => Ok(format!("Error: {e}")),
-        }
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        self.session()?.call_tool(params).await
+    }
+    fn take_tools_changed(&mut self) -> bool {
+        self.session.as_ref().is_some_and(|s| s.take_tools_changed())
+    }
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        Some(self.session.clone()?)
     }
 }
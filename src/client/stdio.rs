@@ -0,0 +1,739 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::{
+        builder::OMcpClientBuilder,
+        io::{OMcpCallTrait, OMcpClientTrait},
+        sandbox::Sandbox,
+    },
+    error::{Error, Result},
+    transport::{io::IoTransport, session::McpSession},
+    types::{McpParams, McpTool},
+};
+use async_trait::async_trait;
+use log::{Level, debug, error, info, log, warn};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, ChildStderr, Command},
+    select,
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+//
+// exponential backoff between restarts, the count starts over once the child
+// stayed up longer than max_backoff
+//
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    pub max_restarts: Option<u32>,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+pub struct StdioServer {
+    pub program: PathBuf,
+    pub cwd: PathBuf,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub clear_env: bool,
+    pub restart: Option<RestartPolicy>,
+    pub shutdown_timeout: Duration,
+    pub stderr_level: Level,
+    pub sandbox: Sandbox,
+    shared: Arc<StdioShared>,
+    supervisor: Option<(CancellationToken, JoinHandle<()>)>,
+}
+
+//
+// state of the current child, replaced on every restart
+//
+struct StdioShared {
+    session: Mutex<Option<Arc<McpSession>>>,
+    exit: watch::Sender<Option<String>>,
+}
+
+//
+// calls through whichever child is current, restarts included
+//
+struct StdioCaller {
+    shared: Arc<StdioShared>,
+    grace: Duration,
+}
+
+//
+// what's needed to start the child again after a crash
+//
+#[derive(Clone)]
+struct SpawnConfig {
+    program: PathBuf,
+    cwd: PathBuf,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    clear_env: bool,
+    shutdown_timeout: Duration,
+    stderr_level: Level,
+    sandbox: Sandbox,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn describe_exit(status: std::io::Result<ExitStatus>) -> String {
+    match status {
+        Ok(v) => v.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+async fn forward_stderr(name: String, stderr: ChildStderr, level: Level) {
+    let mut lines = BufReader::new(stderr).lines();
+
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => log!(level, "{name}: {line}"),
+            Ok(None) => break,
+            Err(e) => {
+                debug!("{e}");
+                break;
+            }
+        }
+    }
+}
+
+//
+// close stdin and give it some time, then SIGTERM, then SIGKILL. With a
+// process group the signals go to the whole group and whatever is left of it
+// once the child is gone is killed too
+//
+async fn terminate(child: &mut Child, grace: Duration, sandbox: &Sandbox) {
+    #[cfg(unix)]
+    let target = child.id().map(|pid| sandbox.signal_target(pid));
+    #[cfg(not(unix))]
+    let _ = sandbox;
+
+    if timeout(grace, child.wait()).await.is_err() {
+        #[cfg(unix)]
+        if let Some(target) = target {
+            signal(target, libc::SIGTERM);
+        }
+
+        if timeout(grace, child.wait()).await.is_err() {
+            warn!("killing {:?}", child.id());
+
+            if let Err(e) = child.kill().await {
+                error!("{e}");
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    if let Some(target) = target.filter(|t| *t < 0) {
+        signal(target, libc::SIGKILL);
+    }
+}
+
+#[cfg(unix)]
+fn signal(target: i32, sig: libc::c_int) {
+    //
+    // SAFETY: kill(2) on our own child or the group it leads
+    //
+    unsafe { libc::kill(target as libc::pid_t, sig) };
+}
+
+async fn supervise(
+    config: SpawnConfig,
+    restart: Option<RestartPolicy>,
+    shared: Arc<StdioShared>,
+    shutdown: CancellationToken,
+    mut child: Child,
+) {
+    let name = config.name();
+    let mut attempts = 0;
+
+    loop {
+        let started = Instant::now();
+
+        #[cfg(unix)]
+        let group = child.id().map(|pid| config.sandbox.signal_target(pid)).filter(|t| *t < 0);
+
+        let status = select! {
+            status = child.wait() => describe_exit(status),
+            _ = shutdown.cancelled() => {
+                //
+                // closing the session closes the child's stdin
+                //
+                if let Some(session) = lock(&shared.session).take() {
+                    session.close();
+                }
+
+                terminate(&mut child, config.shutdown_timeout, &config.sandbox).await;
+                return
+            }
+        };
+
+        warn!("{name} exited: {status}");
+
+        //
+        // whatever it started doesn't outlive it
+        //
+        #[cfg(unix)]
+        if let Some(target) = group {
+            signal(target, libc::SIGKILL);
+        }
+
+        shared.exited(status);
+
+        let policy = match &restart {
+            Some(v) => v,
+            None => return,
+        };
+
+        if started.elapsed() > policy.max_backoff {
+            attempts = 0;
+        }
+
+        child = loop {
+            if !policy.allows(attempts) {
+                error!("{name}: giving up after {attempts} restarts");
+                return;
+            }
+
+            let delay = policy.backoff(attempts);
+            attempts += 1;
+
+            select! {
+                _ = sleep(delay) => {}
+                _ = shutdown.cancelled() => return,
+            }
+
+            match config.start(&shared).await {
+                Ok(v) => break v,
+                Err(e) => error!("{name}: {e}"),
+            }
+        };
+
+        info!("{name} restarted");
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: Some(5),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn allows(&self, attempts: u32) -> bool {
+        self.max_restarts.is_none_or(|max| attempts < max)
+    }
+
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for StdioShared {
+    fn default() -> Self {
+        Self {
+            session: Mutex::new(None),
+            exit: watch::channel(None).0,
+        }
+    }
+}
+
+impl StdioShared {
+    fn exited<S>(&self, status: S)
+    where
+        S: AsRef<str>,
+    {
+        //
+        // status first, callers woken up by drop_pending() look for it
+        //
+        self.exit.send_replace(Some(status.as_ref().to_string()));
+
+        if let Some(session) = lock(&self.session).take() {
+            session.close();
+        }
+    }
+
+    //
+    // ProcessExited with the exit status when the child died with the request
+    // pending. The session sees stdout closing before the supervisor reaps
+    // the child, it gets a moment to record the status
+    //
+    async fn map_error(&self, e: Error, grace: Duration) -> Error {
+        if !matches!(e, Error::NotConnected) {
+            return e;
+        }
+
+        let mut exit = self.exit.subscribe();
+        let _ = timeout(grace, exit.wait_for(|s| s.is_some())).await;
+        self.exit_error()
+    }
+
+    fn exit_error(&self) -> Error {
+        match self.exit.borrow().clone() {
+            Some(status) => Error::ProcessExited { status },
+            None => Error::NotConnected,
+        }
+    }
+}
+
+#[async_trait]
+impl OMcpCallTrait for StdioCaller {
+    async fn call(&self, params: &McpParams) -> Result<String> {
+        let session = lock(&self.shared.session).clone();
+        let session = session.ok_or_else(|| self.shared.exit_error())?;
+
+        match session.call_tool(params).await {
+            Err(e) => Err(self.shared.map_error(e, self.grace).await),
+            res => res,
+        }
+    }
+}
+
+impl SpawnConfig {
+    fn name(&self) -> String {
+        match self.program.file_name() {
+            Some(v) => v.to_string_lossy().to_string(),
+            None => self.program.display().to_string(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let (program, args) = self.sandbox.command_line(&self.program, &self.args);
+        let mut command = Command::new(program);
+
+        command
+            .args(args)
+            .current_dir(&self.cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        self.sandbox.apply(&mut command);
+
+        if self.clear_env {
+            command.env_clear();
+        }
+
+        command.envs(&self.env);
+        command
+    }
+
+    //
+    // spawns and initializes the child, its session is the current one on
+    // success
+    //
+    async fn start(&self, shared: &StdioShared) -> Result<Child> {
+        let mut child = self.command().spawn()?;
+
+        let stdin = child.stdin.take().ok_or(Error::NotConnected)?;
+        let stdout = child.stdout.take().ok_or(Error::NotConnected)?;
+
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(forward_stderr(self.name(), stderr, self.stderr_level));
+        }
+
+        let session = Arc::new(McpSession::new(IoTransport::new(stdout, stdin)));
+
+        let res = select! {
+            res = session.initialize() => res.map(|_| ()),
+            status = child.wait() => Err(Error::ProcessExited { status: describe_exit(status) }),
+        };
+
+        match res {
+            Ok(_) => {
+                shared.exit.send_replace(None);
+                *lock(&shared.session) = Some(session);
+                Ok(child)
+            }
+            Err(Error::ProcessExited { status }) => {
+                shared.exited(&status);
+                Err(Error::ProcessExited { status })
+            }
+            Err(e) => {
+                terminate(&mut child, Duration::ZERO, &self.sandbox).await;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl StdioServer {
+    pub fn new<P>(program: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let cwd = env::current_dir()?;
+
+        Ok(Self {
+            program: program.as_ref().to_path_buf(),
+            cwd,
+            args: Vec::new(),
+            env: HashMap::new(),
+            clear_env: false,
+            restart: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            stderr_level: Level::Info,
+            sandbox: Sandbox::default(),
+            shared: Arc::new(StdioShared::default()),
+            supervisor: None,
+        })
+    }
+
+    //
+    // what OMcpClientBuilder::build() uses for OMcpServerType::Stdio
+    //
+    pub fn from_builder(builder: OMcpClientBuilder) -> Self {
+        let options = builder.stdio;
+
+        let cwd = match options.cwd {
+            Some(v) => v,
+            None => env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        };
+
+        Self {
+            program: options.command,
+            cwd,
+            args: options.args,
+            env: options.env,
+            clear_env: options.clear_env,
+            restart: options.restart,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            stderr_level: Level::Info,
+            sandbox: options.sandbox,
+            shared: Arc::new(StdioShared::default()),
+            supervisor: None,
+        }
+    }
+
+    pub fn with_args(&mut self, args: &[String]) {
+        self.args = args.to_vec();
+    }
+
+    pub fn with_arg<S>(&mut self, arg: S)
+    where
+        S: AsRef<str>,
+    {
+        self.args.push(arg.as_ref().to_string());
+    }
+
+    pub fn set_working_directory<P>(&mut self, cwd: P)
+    where
+        P: AsRef<Path>,
+    {
+        self.cwd = cwd.as_ref().to_path_buf();
+    }
+
+    pub fn with_env<K, V>(&mut self, key: K, value: V)
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.env.insert(key.as_ref().to_string(), value.as_ref().to_string());
+    }
+
+    //
+    // the child only sees what was set with with_env(). By default it
+    // inherits our whole environment, a sandbox env_allowlist narrows that
+    // down to the listed variables and clear_env drops those as well when
+    // both are set. with_env() always applies on top
+    //
+    pub fn with_clear_env(&mut self) {
+        self.clear_env = true;
+    }
+
+    //
+    // the child is started again, and initialized, when it dies
+    //
+    pub fn with_restart(&mut self, policy: RestartPolicy) {
+        self.restart = Some(policy);
+    }
+
+    //
+    // how long disconnect() waits after closing stdin, and again after SIGTERM
+    //
+    pub fn with_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+    }
+
+    //
+    // stderr lines of the child are logged at that level
+    //
+    pub fn with_stderr_level(&mut self, level: Level) {
+        self.stderr_level = level;
+    }
+
+    //
+    // resource limits, process group, uid/gid, env allowlist and wrapper
+    // applied when the child is spawned
+    //
+    pub fn with_sandbox(&mut self, sandbox: Sandbox) {
+        self.sandbox = sandbox;
+    }
+
+    fn spawn_config(&self) -> SpawnConfig {
+        SpawnConfig {
+            program: self.program.clone(),
+            cwd: self.cwd.clone(),
+            args: self.args.clone(),
+            env: self.env.clone(),
+            clear_env: self.clear_env,
+            shutdown_timeout: self.shutdown_timeout,
+            stderr_level: self.stderr_level,
+            sandbox: self.sandbox.clone(),
+        }
+    }
+
+    fn session(&self) -> Result<Arc<McpSession>> {
+        let session = lock(&self.shared.session).clone();
+        session.ok_or_else(|| self.shared.exit_error())
+    }
+}
+
+impl Drop for StdioServer {
+    fn drop(&mut self) {
+        //
+        // the supervisor shuts the child down on its own
+        //
+        if let Some((shutdown, _)) = &self.supervisor {
+            shutdown.cancel();
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl OMcpClientTrait for StdioServer {
+    async fn connect(&mut self) -> Result<()> {
+        self.disconnect().await?;
+
+        let config = self.spawn_config();
+        let shutdown = CancellationToken::new();
+
+        let child = config.start(&self.shared).await?;
+
+        let supervisor = supervise(
+            config,
+            self.restart.clone(),
+            self.shared.clone(),
+            shutdown.clone(),
+            child,
+        );
+
+        self.supervisor = Some((shutdown, tokio::spawn(supervisor)));
+        Ok(())
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some((shutdown, task)) = self.supervisor.take() {
+            shutdown.cancel();
+            task.await?;
+        }
+
+        if let Some(session) = lock(&self.shared.session).take() {
+            session.close();
+        }
+        Ok(())
+    }
+
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        match self.session()?.list_tools().await {
+            Err(e) => Err(self.shared.map_error(e, self.shutdown_timeout).await),
+            res => res,
+        }
+    }
+
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        match self.session()?.call_tool(params).await {
+            Err(e) => Err(self.shared.map_error(e, self.shutdown_timeout).await),
+            res => res,
+        }
+    }
+
+    fn take_tools_changed(&mut self) -> bool {
+        let session = lock(&self.shared.session).clone();
+        session.is_some_and(|s| s.take_tools_changed())
+    }
+
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        self.supervisor.as_ref()?;
+
+        Some(Arc::new(StdioCaller {
+            shared: self.shared.clone(),
+            grace: self.shutdown_timeout,
+        }))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        client::{
+            builder::OMcpClientBuilder,
+            io::OMcpClientTrait,
+            sandbox::Sandbox,
+            stdio::{RestartPolicy, StdioServer},
+            types::OMcpServerType,
+        },
+        error::Error,
+        types::McpParams,
+    };
+
+    //
+    // answers initialize and tools/list then dies on the next request
+    //
+    const SCRIPT: &str = r#"
+        read l; echo '{"jsonrpc":"2.0","id":1,"result":{"capabilities":{}}}'
+        read l; read l
+        echo "listing for $HOME" >&2
+        echo "{\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"tools\":[{\"name\":\"$GREETING$HOME\"}]}}"
+        read l; exit 3
+    "#;
+
+    #[tokio::test]
+    async fn exit_and_restart() {
+        let mut server = StdioServer::new("/bin/sh").unwrap();
+        server.with_arg("-c");
+        server.with_arg(SCRIPT);
+        server.with_clear_env();
+        server.with_env("GREETING", "hello");
+        server.with_restart(RestartPolicy {
+            max_restarts: Some(3),
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+        });
+
+        server.connect().await.unwrap();
+
+        //
+        // HOME is gone with the rest of the environment
+        //
+        let tools = server.list_tools().await.unwrap();
+        assert_eq!(tools[0].name, "hello");
+
+        let params = McpParams::new("echo");
+
+        match server.call(&params).await {
+            Err(Error::ProcessExited { status }) => assert!(status.contains('3')),
+            e => panic!("{e:?}"),
+        }
+
+        let mut tools = server.list_tools().await;
+
+        for _ in 0..100 {
+            if tools.is_ok() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            tools = server.list_tools().await;
+        }
+
+        assert_eq!(tools.unwrap()[0].name, "hello");
+
+        server.disconnect().await.unwrap();
+        assert!(matches!(server.list_tools().await, Err(Error::NotConnected)));
+    }
+
+    //
+    // the process group goes down with the child even when nobody asked
+    //
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn group_killed_on_exit() {
+        let pid_file = std::env::temp_dir().join(format!("omcp-group-{}", std::process::id()));
+
+        let script = format!(
+            r#"
+            sleep 60 & echo $! > {}
+            read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"capabilities":{{}}}}}}'
+            read l; read l; exit 0
+        "#,
+            pid_file.display()
+        );
+
+        let mut server = StdioServer::new("/bin/sh").unwrap();
+        server.with_arg("-c");
+        server.with_arg(script);
+        server.with_sandbox(Sandbox::new().with_process_group());
+
+        server.connect().await.unwrap();
+        assert!(matches!(server.list_tools().await, Err(Error::ProcessExited { .. })));
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::fs::remove_file(&pid_file).unwrap();
+
+        //
+        // gone, or a zombie nobody reaped yet
+        //
+        let dead = || match std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())) {
+            Ok(stat) => stat.rsplit(')').next().is_some_and(|s| s.trim_start().starts_with('Z')),
+            Err(_) => true,
+        };
+
+        for _ in 0..100 {
+            if dead() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(dead());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn clear_env_and_allowlist() {
+        let mut server = StdioServer::new("/bin/sh").unwrap();
+        server.with_arg("-c");
+        server.with_arg(r#"echo "$HOME|$GREETING""#);
+        server.with_env("GREETING", "hello");
+        server.with_sandbox(Sandbox::new().with_env_allowlist(&["HOME"]));
+
+        let output = |server: &StdioServer| {
+            let mut command = server.spawn_config().command();
+            async move { String::from_utf8(command.output().await.unwrap().stdout).unwrap() }
+        };
+
+        let stdout = output(&server).await;
+        assert!(stdout.len() > "|hello\n".len());
+        assert!(stdout.ends_with("|hello\n"));
+
+        server.with_clear_env();
+        assert_eq!(output(&server).await, "|hello\n");
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::Stdio).with_stdio_clear_env();
+        assert!(StdioServer::from_builder(builder).clear_env);
+    }
+}
@@ -4,10 +4,19 @@ use crate::{
 };
 
 use log::{debug, error};
+use reqwest::Url;
 
 #[derive(Debug)]
 pub enum OMcpServerType {
     Sse,
+    #[cfg(any(not(target_arch = "wasm32"), feature = "browser"))]
+    Http,
+    #[cfg(not(target_arch = "wasm32"))]
+    Stdio,
+    #[cfg(not(target_arch = "wasm32"))]
+    WebSocket,
+    #[cfg(unix)]
+    Unix,
     Baked,
 }
 
@@ -23,19 +32,16 @@ impl SseEventEndpoint {
         S: AsRef<str>,
         E: AsRef<str>,
     {
-        let root = endpoint.as_ref().split("/").nth(1).ok_or(Error::InvalidEndpoint)?;
-
-        let root = format!("/{root}");
-
-        let server = server.as_ref();
-
-        let (base, _) = server.split_once(&root).ok_or(Error::InvalidEndpoint)?;
-
-        let url = format!("{base}{}", endpoint.as_ref());
+        //
+        // the endpoint is relative to the SSE url
+        //
+        let url = Url::parse(server.as_ref())
+            .and_then(|u| u.join(endpoint.as_ref()))
+            .map_err(|_| Error::InvalidEndpoint)?;
 
         Ok(Self {
             endpoint: endpoint.as_ref().into(),
-            url,
+            url: url.into(),
         })
     }
 }
@@ -0,0 +1,137 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{
+    client::{
+        builder::OMcpClientBuilder,
+        io::{OMcpCallTrait, OMcpClientTrait},
+    },
+    error::{Error, Result},
+    transport::{session::McpSession, unix::UnixTransport},
+    types::{McpParams, McpTool},
+};
+
+//
+// OMcpServerType::Unix, a server on the same host listening with
+// OmcpServer::unix_loop(). connect() opens a new connection every time
+//
+pub struct UnixClient {
+    path: PathBuf,
+    session: Option<Arc<McpSession>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl UnixClient {
+    pub fn from_builder(builder: OMcpClientBuilder) -> Self {
+        Self {
+            path: builder.unix_path,
+            session: None,
+        }
+    }
+
+    fn session(&self) -> Result<&McpSession> {
+        self.session.as_deref().ok_or(Error::NotConnected)
+    }
+}
+
+#[async_trait]
+impl OMcpClientTrait for UnixClient {
+    async fn connect(&mut self) -> Result<()> {
+        self.disconnect().await?;
+
+        let session = McpSession::new(UnixTransport::connect(&self.path).await?);
+        session.initialize().await?;
+
+        self.session = Some(Arc::new(session));
+        Ok(())
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+
+        Ok(())
+    }
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        self.session()?.list_tools().await
+    }
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        self.session()?.call_tool(params).await
+    }
+    fn take_tools_changed(&mut self) -> bool {
+        self.session.as_ref().is_some_and(|s| s.take_tools_changed())
+    }
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        Some(self.session.clone()?)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::net::UnixListener;
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer, unix::UnixServerOptions},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("echo {}", params.tool_name))
+        }
+    }
+
+    #[tokio::test]
+    async fn builder_unix() {
+        let path = std::env::temp_dir().join(format!("omcp-client-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move { server.unix_serve(listener, UnixServerOptions::new()).await });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Unix)
+            .with_unix_path(&path)
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+
+        //
+        // every connection is a session of its own
+        //
+        let mut other = OMcpClientBuilder::new(OMcpServerType::Unix)
+            .with_unix_path(&path)
+            .build()
+            .unwrap();
+
+        other.connect().await.unwrap();
+
+        assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+
+        let res = other.call(&McpParams::new("echo")).await.unwrap();
+        assert!(res.contains("echo echo"));
+
+        client.disconnect().await.unwrap();
+        assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+        assert_eq!(other.list_tools().await.unwrap().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    client::{
+        builder::OMcpClientBuilder,
+        io::{OMcpCallTrait, OMcpClientTrait},
+    },
+    error::{Error, Result},
+    transport::{session::McpSession, ws::WsConnector},
+    types::{McpParams, McpTool},
+};
+
+//
+// OMcpServerType::WebSocket, a single socket carries everything both ways.
+// connect() opens a new one every time
+//
+pub struct WsClient {
+    connector: WsConnector,
+    session: Option<Arc<McpSession>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl WsClient {
+    pub fn from_builder(builder: OMcpClientBuilder) -> Result<Self> {
+        Ok(Self {
+            connector: WsConnector::from_builder(builder)?,
+            session: None,
+        })
+    }
+
+    fn session(&self) -> Result<&McpSession> {
+        self.session.as_deref().ok_or(Error::NotConnected)
+    }
+}
+
+#[async_trait]
+impl OMcpClientTrait for WsClient {
+    async fn connect(&mut self) -> Result<()> {
+        self.disconnect().await?;
+
+        let session = McpSession::new(self.connector.connect().await?);
+        session.initialize().await?;
+
+        self.session = Some(Arc::new(session));
+        Ok(())
+    }
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+
+        Ok(())
+    }
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>> {
+        self.session()?.list_tools().await
+    }
+    async fn call(&mut self, params: &McpParams) -> Result<String> {
+        self.session()?.call_tool(params).await
+    }
+    fn take_tools_changed(&mut self) -> bool {
+        self.session.as_ref().is_some_and(|s| s.take_tools_changed())
+    }
+    fn caller(&self) -> Option<Arc<dyn OMcpCallTrait>> {
+        Some(self.session.clone()?)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer, ws::WsServerOptions},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("echo {}", params.tool_name))
+        }
+    }
+
+    #[tokio::test]
+    async fn builder_ws() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let options = WsServerOptions::new()
+            .with_path("/mcp")
+            .with_ping_interval(Duration::from_millis(50));
+
+        tokio::spawn(async move { server.ws_serve(listener, options).await });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::WebSocket)
+            .with_ws_url(format!("ws://{addr}/mcp"))
+            .with_ws_ping_interval(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+
+        client.connect().await.unwrap();
+
+        //
+        // a few rounds of pings both ways, the session has to survive them
+        //
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+
+        let res = client.call(&McpParams::new("echo")).await.unwrap();
+        assert!(res.contains("echo echo"));
+
+        client.disconnect().await.unwrap();
+        assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+    }
+}
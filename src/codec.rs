@@ -0,0 +1,209 @@
+use log::{error, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+};
+
+const IO_BUFFER_SIZE: usize = 8 * 1024;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 4 * 1024 * 1024;
+
+//
+// Newline delimited JSON, one message per line. Partial reads are kept
+// around so read_line() can be cancelled by select!
+//
+pub struct LineReader<R> {
+    stream: R,
+    buffer: Vec<u8>,
+    pending: Vec<u8>,
+    scanned: usize,
+    max_line_length: usize,
+    discarding: bool,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PUBLIC FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+pub async fn write_line<W>(stream: &mut W, msg: &JsonRPCMessage) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    //
+    // serde_json escapes newlines in strings, a message is always one line
+    //
+    let mut data = serde_json::to_vec(msg)?;
+    data.push(b'\n');
+
+    stream.write_all(&data).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+//
+// an HTTP/1 head up to the blank line ending the headers, for the OAuth
+// redirect and the proxy's answer to CONNECT. Neither sends a body before
+// we've written again, so nothing past the head gets lost
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn read_head<R>(stream: &mut R, max: usize) -> Result<String>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > max {
+            return Err(Error::MessageTooLarge);
+        }
+
+        let len = stream.read(&mut buf).await?;
+
+        if len == 0 {
+            return Err(Error::Eof);
+        }
+
+        head.extend(&buf[..len]);
+    }
+
+    Ok(String::from_utf8_lossy(&head).to_string())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl<R> LineReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    pub fn new(stream: R) -> Self {
+        Self {
+            stream,
+            buffer: vec![0u8; IO_BUFFER_SIZE],
+            pending: Vec::new(),
+            scanned: 0,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            discarding: false,
+        }
+    }
+
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    //
+    // Some(Ok) for a line, Some(Err) when an oversized line was dropped. What
+    // was already searched for a newline isn't searched again
+    //
+    fn next_line(&mut self) -> Option<Result<String>> {
+        loop {
+            let end = match self.pending[self.scanned..].iter().position(|b| *b == b'\n') {
+                Some(v) => self.scanned + v,
+                None => {
+                    self.scanned = self.pending.len();
+
+                    if self.pending.len() > self.max_line_length {
+                        self.pending.clear();
+                        self.scanned = 0;
+                        self.discarding = true;
+                    }
+                    return None;
+                }
+            };
+
+            let mut line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.scanned = 0;
+
+            if self.discarding || line.len().saturating_sub(1) > self.max_line_length {
+                self.discarding = false;
+                return Some(Err(Error::MessageTooLarge));
+            }
+
+            line.pop();
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+
+            return Some(String::from_utf8(line).map_err(|e| e.into()));
+        }
+    }
+
+    pub async fn read_line(&mut self) -> Result<String> {
+        loop {
+            match self.next_line() {
+                Some(Err(Error::MessageTooLarge)) => {
+                    warn!("dropped a message larger than {} bytes", self.max_line_length);
+                    return Err(Error::MessageTooLarge);
+                }
+                Some(line) => return line,
+                None => {}
+            }
+
+            match self.stream.read(&mut self.buffer).await {
+                Ok(0) => return Err(Error::Eof),
+                Ok(len) => self.pending.extend(&self.buffer[0..len]),
+                Err(e) => {
+                    error!("{e}");
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{codec::LineReader, error::Error};
+
+    #[tokio::test]
+    async fn many_per_read() {
+        let data: &[u8] = b"{\"a\":1}\n\r\n{\"b\":2}\r\n{\"c\":";
+        let mut reader = LineReader::new(data);
+
+        assert_eq!(reader.read_line().await.unwrap(), "{\"a\":1}");
+        assert_eq!(reader.read_line().await.unwrap(), "{\"b\":2}");
+        assert!(matches!(reader.read_line().await, Err(Error::Eof)));
+    }
+
+    #[tokio::test]
+    async fn partial_reads() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut reader = LineReader::new(server);
+
+        let task = tokio::spawn(async move {
+            let mut client = client;
+            client.write_all(b"{\"a\":").await.unwrap();
+            client.write_all(b"1}\n").await.unwrap();
+        });
+
+        assert_eq!(reader.read_line().await.unwrap(), "{\"a\":1}");
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized() {
+        let data: &[u8] = b"{\"too\":\"long\"}\n{}\n";
+        let mut reader = LineReader::new(data).with_max_line_length(4);
+
+        assert!(matches!(reader.read_line().await, Err(Error::MessageTooLarge)));
+        assert_eq!(reader.read_line().await.unwrap(), "{}");
+
+        //
+        // no limit at all, what the HTTP sessions use
+        //
+        let mut reader = LineReader::new(data).with_max_line_length(usize::MAX);
+        assert_eq!(reader.read_line().await.unwrap(), "{\"too\":\"long\"}");
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client::{builder::OMcpClientBuilder, io::OMcpClientTrait, types::OMcpServerType},
+    error::{Error, Result},
+};
+
+//
+// The mcpServers map every host uses (Claude Desktop, Cursor, VS Code). The
+// TOML flavor is the same thing under [mcp_servers.<name>]
+//
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct McpConfig {
+    #[serde(rename = "mcpServers", alias = "mcp_servers", alias = "servers", default)]
+    pub servers: BTreeMap<String, McpServerConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum McpServerConfig {
+    Stdio(StdioServerConfig),
+    Http(HttpServerConfig),
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StdioServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HttpServerConfig {
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    //
+    // "sse", "http" (Streamable HTTP) or "ws" when set, VS Code style
+    //
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+
+//
+// ${VAR}, ${VAR:-default} and VS Code's ${env:VAR}
+//
+fn expand<F>(value: &str, lookup: &F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+
+        let end = match rest[start..].find('}') {
+            Some(v) => start + v,
+            None => {
+                //
+                // not a variable, leave it alone
+                //
+                out.push_str(&rest[start..]);
+                return Ok(out);
+            }
+        };
+
+        let expr = &rest[start + 2..end];
+        let expr = expr.strip_prefix("env:").unwrap_or(expr);
+
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+
+        match (lookup(name), default) {
+            (Some(v), _) => out.push_str(&v),
+            (None, Some(d)) => out.push_str(d),
+            (None, None) => return Err(Error::EnvVarNotFound { name: name.to_string() }),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn expand_map<F>(map: &HashMap<String, String>, lookup: &F) -> Result<HashMap<String, String>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    map.iter().map(|(k, v)| Ok((k.clone(), expand(v, lookup)?))).collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl McpConfig {
+    pub fn from_json<S>(data: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        Ok(serde_json::from_str(data.as_ref())?)
+    }
+
+    pub fn from_toml<S>(data: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        Ok(toml::from_str(data.as_ref())?)
+    }
+
+    //
+    // .toml files are TOML, everything else JSON. Variables are expanded from
+    // the process environment
+    //
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let data = std::fs::read_to_string(path.as_ref())?;
+
+        let config = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(data)?,
+            _ => Self::from_json(data)?,
+        };
+
+        config.expand_env()
+    }
+
+    pub fn expand_env(&self) -> Result<Self> {
+        self.expand_with(|name| std::env::var(name).ok())
+    }
+
+    pub fn expand_with<F>(&self, lookup: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let servers = self
+            .servers
+            .iter()
+            .map(|(name, server)| Ok((name.clone(), server.expand_with(&lookup)?)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        Ok(Self { servers })
+    }
+
+    pub fn build<S>(&self, name: S) -> Result<Box<dyn OMcpClientTrait>>
+    where
+        S: AsRef<str>,
+    {
+        let server = self.servers.get(name.as_ref()).ok_or(Error::NotFound)?;
+        server.builder()?.build()
+    }
+
+    pub fn build_all(&self) -> Result<BTreeMap<String, Box<dyn OMcpClientTrait>>> {
+        self.servers
+            .iter()
+            .map(|(name, server)| Ok((name.clone(), server.builder()?.build()?)))
+            .collect()
+    }
+}
+
+impl McpServerConfig {
+    pub fn expand_with<F>(&self, lookup: &F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let server = match self {
+            Self::Stdio(s) => Self::Stdio(StdioServerConfig {
+                command: expand(&s.command, lookup)?,
+                args: s.args.iter().map(|a| expand(a, lookup)).collect::<Result<_>>()?,
+                env: expand_map(&s.env, lookup)?,
+                cwd: s.cwd.as_ref().map(|c| expand(c, lookup)).transpose()?,
+            }),
+            Self::Http(h) => Self::Http(HttpServerConfig {
+                url: expand(&h.url, lookup)?,
+                headers: expand_map(&h.headers, lookup)?,
+                transport: h.transport.clone(),
+            }),
+        };
+
+        Ok(server)
+    }
+
+    pub fn builder(&self) -> Result<OMcpClientBuilder> {
+        match self {
+            Self::Http(h) => {
+                let server_type = match h.transport.as_deref() {
+                    None | Some("sse") => OMcpServerType::Sse,
+                    #[cfg(any(not(target_arch = "wasm32"), feature = "browser"))]
+                    Some("http") => OMcpServerType::Http,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    Some("ws") => OMcpServerType::WebSocket,
+                    Some(t) => return Err(Error::UnsupportedTransport { name: t.to_string() }),
+                };
+
+                let mut builder = OMcpClientBuilder::new(server_type).with_sse_url(&h.url);
+
+                for (k, v) in h.headers.iter() {
+                    builder = builder.with_sse_header(k, v)?;
+                }
+
+                Ok(builder)
+            }
+            #[cfg(target_arch = "wasm32")]
+            Self::Stdio(_) => Err(Error::UnsupportedTransport { name: "stdio".into() }),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Stdio(s) => {
+                let mut builder = OMcpClientBuilder::new(OMcpServerType::Stdio)
+                    .with_stdio_command(&s.command)
+                    .with_stdio_args(&s.args);
+
+                for (k, v) in s.env.iter() {
+                    builder = builder.with_stdio_env(k, v);
+                }
+
+                if let Some(cwd) = &s.cwd {
+                    builder = builder.with_stdio_cwd(cwd);
+                }
+
+                Ok(builder)
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{McpConfig, McpServerConfig},
+        error::Error,
+    };
+
+    const JSON: &str = r#"{
+        "mcpServers": {
+            "files": {
+                "command": "npx",
+                "args": ["-y", "@modelcontextprotocol/server-filesystem", "${HOME}/src"],
+                "env": {"LOG": "${LOG_LEVEL:-info}"}
+            },
+            "ha": {
+                "url": "http://${HA_HOST}:8123/mcp_server/sse",
+                "headers": {"Authorization": "Bearer ${env:HA_TOKEN}"}
+            }
+        }
+    }"#;
+
+    const TOML: &str = r#"
+        [mcp_servers.files]
+        command = "npx"
+        args = ["-y", "@modelcontextprotocol/server-filesystem"]
+
+        [mcp_servers.ha]
+        url = "http://localhost:8123/mcp_server/sse"
+    "#;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".into()),
+            "HA_HOST" => Some("ha.local".into()),
+            "HA_TOKEN" => Some("secret".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parse_and_expand() {
+        let config = McpConfig::from_json(JSON).unwrap().expand_with(lookup).unwrap();
+
+        match &config.servers["files"] {
+            McpServerConfig::Stdio(s) => {
+                assert_eq!(s.args[2], "/home/me/src");
+                assert_eq!(s.env["LOG"], "info");
+            }
+            _ => panic!("not stdio"),
+        }
+
+        match &config.servers["ha"] {
+            McpServerConfig::Http(h) => {
+                assert_eq!(h.url, "http://ha.local:8123/mcp_server/sse");
+                assert_eq!(h.headers["Authorization"], "Bearer secret");
+            }
+            _ => panic!("not http"),
+        }
+
+        let missing = McpConfig::from_json(JSON).unwrap().expand_with(|_| None);
+        assert!(matches!(missing, Err(Error::EnvVarNotFound { .. })));
+
+        let toml = McpConfig::from_toml(TOML).unwrap();
+        assert_eq!(toml.servers.len(), 2);
+
+        assert!(toml.build("ha").is_ok());
+        assert!(toml.build("files").is_ok());
+        assert!(matches!(toml.build("nope"), Err(Error::NotFound)));
+    }
+}
@@ -25,6 +25,7 @@ pub enum Error {
     ReadFailure,
     EndpointMissing,
     Eof,
+    MessageTooLarge,
     ConnectionStateFailure,
     NotFound,
     ParameterNotFound,
@@ -33,6 +34,44 @@ pub enum Error {
     FunctionCallFailure {
         error: String,
     },
+    JsonRpcError {
+        code: i64,
+        message: String,
+    },
+    InvalidMessage {
+        code: i64,
+        id: Option<u64>,
+        message: String,
+    },
+    CapabilityMissing {
+        name: String,
+    },
+    Cancelled,
+    TooManySessions,
+    Unauthorized,
+    AuthorizationFailure {
+        error: String,
+    },
+    EnvVarNotFound {
+        name: String,
+    },
+    UnsupportedTransport {
+        name: String,
+    },
+    ProcessExited {
+        status: String,
+    },
+    InvalidServerName {
+        name: String,
+    },
+    //
+    // from a transport's recv(), the request with that id failed on its own
+    // and the connection is still good
+    //
+    RequestFailed {
+        id: u64,
+        error: Box<Error>,
+    },
 
     //
     // 2nd party
@@ -59,6 +98,33 @@ pub enum Error {
     HeaderValue(reqwest::header::InvalidHeaderValue),
     #[from]
     Serialization(serde_json::Error),
+    #[from]
+    Toml(toml::de::Error),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[from]
+    Axum(axum::Error),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[from]
+    WebSocket(tokio_tungstenite::tungstenite::Error),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[from]
+    NativeTls(native_tls::Error),
+    #[cfg(all(target_arch = "wasm32", feature = "browser"))]
+    Js {
+        error: String,
+    },
+}
+
+//
+// whatever fetch() or EventSource threw
+//
+#[cfg(all(target_arch = "wasm32", feature = "browser"))]
+impl From<wasm_bindgen::JsValue> for Error {
+    fn from(value: wasm_bindgen::JsValue) -> Self {
+        Error::Js {
+            error: format!("{value:?}"),
+        }
+    }
 }
 
 impl core::fmt::Display for Error {
@@ -0,0 +1,791 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, MutexGuard, Weak,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use log::{debug, error, warn};
+use serde_json::{Map, Value, json};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    process::Command,
+    select,
+    sync::mpsc,
+};
+
+use crate::{
+    client::{builder::OMcpClientBuilder, types::OMcpServerType},
+    config::{McpServerConfig, StdioServerConfig},
+    error::{Error, Result},
+    json_rpc::{JSON_RPC_INTERNAL_ERROR, JsonRPCMessage, JsonRPCMessageBuilder, JsonRPCParameters},
+    server::{
+        context::{ToolContext, parse_request_id},
+        forward::McpForwarder,
+        handle::OmcpServerHandle,
+        matrix::OmcpServer,
+    },
+    transport::{
+        Transport, child::ChildTransport, http::HttpConnector, io::IoTransport, session::McpSession, sse::SseTransport,
+        ws::WebSocketTransport,
+    },
+    types::{BakedMcpToolTrait, McpParams, McpTool},
+};
+
+//
+// Re-exports one upstream server through an OmcpServer, e.g. a stdio only
+// server over HTTP or a remote SSE server to a stdio host. Tools are
+// registered on the server, everything else (resources, prompts, ...) is
+// forwarded as is. Notifications, server to client requests and
+// cancellations go through in both directions. There's a single upstream
+// server per gateway, each server to re-export needs its own
+//
+pub struct Gateway {
+    inner: Arc<GatewayInner>,
+}
+
+struct GatewayInner {
+    upstream: McpSession,
+    capabilities: Mutex<Map<String, Value>>,
+    handle: OmcpServerHandle<Error>,
+    tools: Mutex<HashMap<String, Value>>,
+    sessions: Mutex<Vec<Arc<ToolContext>>>,
+    progress: Mutex<HashMap<u64, mpsc::UnboundedSender<JsonRPCParameters>>>,
+    next_token: AtomicU64,
+}
+
+struct GatewayForwarder {
+    inner: Arc<GatewayInner>,
+}
+
+//
+// only there so the upstream tools are listed, calls to them go through
+// GatewayForwarder and the upstream result reaches the client as is
+//
+struct ProxyTool {}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn wire_to_tool(value: &Value) -> Option<McpTool> {
+    let name = value.get("name")?.as_str()?.to_string();
+
+    let description = value
+        .get("description")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    //
+    // McpToolSchema only knows a subset of JSON schema, anything fancier ends
+    // up as a plain object
+    //
+    let input_schema = value.get("inputSchema").and_then(|v| serde_json::from_value(v.clone()).ok());
+
+    Some(McpTool {
+        name,
+        description,
+        input_schema,
+    })
+}
+
+fn to_params(params: Value) -> Result<Map<String, Value>> {
+    match params {
+        Value::Object(v) => Ok(v),
+        Value::Null => Ok(Map::new()),
+        _ => Err(Error::ParameterInvalidFormat),
+    }
+}
+
+//
+// what the upstream server sends on its own, until either side is gone
+//
+async fn incoming_pump(inner: Weak<GatewayInner>, mut incoming: mpsc::UnboundedReceiver<JsonRPCMessage>) {
+    while let Some(msg) = incoming.recv().await {
+        match inner.upgrade() {
+            Some(inner) => inner.incoming(msg),
+            None => break,
+        }
+    }
+
+    warn!("upstream disconnected");
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl GatewayInner {
+    fn new(upstream: McpSession) -> Arc<Self> {
+        let inner = Self {
+            upstream,
+            capabilities: Mutex::new(Map::new()),
+            handle: OmcpServerHandle::new(),
+            tools: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(Vec::new()),
+            progress: Mutex::new(HashMap::new()),
+            next_token: AtomicU64::new(1),
+        };
+
+        Arc::new(inner)
+    }
+
+    //
+    // tools and logging are answered by our own server
+    //
+    fn set_capabilities(&self, init_result: &Map<String, Value>) {
+        let mut capabilities = match init_result.get("capabilities") {
+            Some(Value::Object(v)) => v.clone(),
+            _ => Map::new(),
+        };
+
+        capabilities.remove("tools");
+        capabilities.remove("logging");
+
+        *lock(&self.capabilities) = capabilities;
+    }
+
+    async fn refresh_tools(self: &Arc<Self>) -> Result<()> {
+        let mut listed = HashMap::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({ "cursor": c }),
+                None => json!({}),
+            };
+
+            let mut res = self.upstream.request("tools/list", params).await?;
+
+            if let Some(Value::Array(tools)) = res.remove("tools") {
+                for tool in tools {
+                    if let Some(name) = tool.get("name").and_then(|v| v.as_str()) {
+                        listed.insert(name.to_string(), tool.clone());
+                    }
+                }
+            }
+
+            cursor = match res.remove("nextCursor") {
+                Some(Value::String(v)) => Some(v),
+                _ => break,
+            };
+        }
+
+        let mut tools = lock(&self.tools);
+
+        //
+        // only what changed so the client gets a single list_changed
+        //
+        for name in tools.keys() {
+            if !listed.contains_key(name) {
+                self.handle.remove_tool(name);
+            }
+        }
+
+        for (name, wire) in listed.iter() {
+            if tools.get(name) == Some(wire) {
+                continue;
+            }
+
+            match wire_to_tool(wire) {
+                Some(tool) => self.handle.add_mcp_tool(tool, ProxyTool {}),
+                None => warn!("invalid tool {name}"),
+            }
+        }
+
+        *tools = listed;
+        Ok(())
+    }
+
+    //
+    // the sessions that are still around
+    //
+    fn sessions(&self) -> MutexGuard<'_, Vec<Arc<ToolContext>>> {
+        let mut sessions = lock(&self.sessions);
+        sessions.retain(|ctx| ctx.is_connected());
+        sessions
+    }
+
+    fn add_session(&self, ctx: ToolContext) {
+        self.sessions().push(Arc::new(ctx));
+    }
+
+    //
+    // a gateway usually fronts a single client, requests coming from the
+    // upstream server go to the most recent one
+    //
+    fn latest_session(&self) -> Option<Arc<ToolContext>> {
+        self.sessions().last().cloned()
+    }
+
+    fn broadcast(&self, method: &str, params: Value) {
+        //
+        // sessions that are gone can't be notified anymore
+        //
+        lock(&self.sessions).retain(|ctx| ctx.notify(method, params.clone()).is_ok());
+    }
+
+    fn incoming(self: Arc<Self>, msg: JsonRPCMessage) {
+        let params = msg.parameters.clone().map(|p| json!(p)).unwrap_or(Value::Null);
+
+        //
+        // responses never get here, the session matches them with our requests
+        //
+        match (msg.id, msg.method.as_deref()) {
+            (None, Some(method)) => self.upstream_notification(method, params),
+            (Some(id), Some(method)) => {
+                let method = method.to_string();
+                tokio::spawn(async move { self.upstream_request(id, method, params).await });
+            }
+            _ => warn!("ignoring message without a method"),
+        }
+    }
+
+    fn upstream_notification(self: Arc<Self>, method: &str, params: Value) {
+        match method {
+            "notifications/tools/list_changed" => {
+                tokio::spawn(async move {
+                    if let Err(e) = self.refresh_tools().await {
+                        error!("{e}");
+                    }
+                });
+            }
+            "notifications/progress" => {
+                let token = params.get("progressToken").and_then(|v| v.as_u64());
+
+                let sender = match token {
+                    Some(token) => lock(&self.progress).get(&token).cloned(),
+                    None => None,
+                };
+
+                match (sender, serde_json::from_value(params)) {
+                    (Some(sender), Ok(params)) => {
+                        let _ = sender.send(params);
+                    }
+                    _ => debug!("progress for a request that's gone"),
+                }
+            }
+            "notifications/cancelled" => {
+                if let Some(id) = parse_request_id(&params) {
+                    self.upstream.cancel_call(id);
+                }
+            }
+            _ => self.broadcast(method, params),
+        }
+    }
+
+    //
+    // sampling, roots, elicitation, ... asked by the upstream server
+    //
+    async fn upstream_request(&self, id: u64, method: String, params: Value) {
+        let res = match method.as_str() {
+            "ping" => Ok(Map::new()),
+            _ => self.ask_client(id, &method, params).await,
+        };
+
+        let msg = match res {
+            Ok(v) => JsonRPCMessageBuilder::new()
+                .with_id(id)
+                .with_result(v.into_iter().collect())
+                .build(),
+            Err(Error::Cancelled) => return,
+            Err(Error::JsonRpcError { code, message }) => {
+                JsonRPCMessageBuilder::new().with_id(id).with_error(code, message).build()
+            }
+            Err(e) => JsonRPCMessageBuilder::new()
+                .with_id(id)
+                .with_error(JSON_RPC_INTERNAL_ERROR, e.to_string())
+                .build(),
+        };
+
+        if let Err(e) = self.upstream.respond(msg) {
+            error!("{e}");
+        }
+    }
+
+    async fn ask_client(&self, id: u64, method: &str, params: Value) -> Result<Map<String, Value>> {
+        let session = self.latest_session().ok_or(Error::NotConnected)?;
+        let cancellation = self.upstream.start_call(id);
+
+        //
+        // dropping the request lets the client know it was cancelled
+        //
+        let res = select! {
+            res = session.request(method, params) => res,
+            _ = cancellation.cancelled() => Err(Error::Cancelled),
+        };
+
+        self.upstream.end_call(id);
+        res
+    }
+
+    //
+    // the client's progress token is swapped for one of ours, the upstream
+    // server could see requests from several clients using the same token
+    //
+    async fn forward(&self, ctx: &ToolContext, method: &str, params: Value) -> Result<Map<String, Value>> {
+        let mut params = to_params(params)?;
+        let (tx, mut progress) = mpsc::unbounded_channel();
+
+        let token = match ctx.progress_token() {
+            Some(_) => {
+                let token = self.next_token.fetch_add(1, Ordering::SeqCst);
+                lock(&self.progress).insert(token, tx);
+
+                let meta = params.entry("_meta").or_insert_with(|| json!({}));
+
+                if let Some(meta) = meta.as_object_mut() {
+                    meta.insert("progressToken".into(), json!(token));
+                }
+
+                Some(token)
+            }
+            None => None,
+        };
+
+        let request = self.upstream.request(method, Value::Object(params));
+        tokio::pin!(request);
+
+        let res = loop {
+            select! {
+                res = &mut request => break res,
+                Some(p) = progress.recv() => {
+                    let value = p.get("progress").and_then(|v| v.as_f64()).unwrap_or_default();
+                    let total = p.get("total").and_then(|v| v.as_f64());
+                    let message = p.get("message").and_then(|v| v.as_str());
+
+                    if let Err(e) = ctx.progress(value, total, message) {
+                        warn!("{e}");
+                    }
+                }
+                _ = ctx.cancellation().cancelled() => break Err(Error::Cancelled),
+            }
+        };
+
+        if let Some(token) = token {
+            lock(&self.progress).remove(&token);
+        }
+
+        res
+    }
+}
+
+impl Gateway {
+    pub async fn connect(config: &McpServerConfig) -> Result<Self> {
+        let builder = match config {
+            McpServerConfig::Stdio(s) => return Self::spawn(s).await,
+            McpServerConfig::Http(_) => config.builder()?,
+        };
+
+        match builder.server_type {
+            OMcpServerType::Sse => Self::connect_sse(builder).await,
+            OMcpServerType::Http => Self::connect_http(builder).await,
+            OMcpServerType::WebSocket => Self::connect_ws(builder).await,
+            t => Err(Error::UnsupportedTransport { name: format!("{t:?}") }),
+        }
+    }
+
+    //
+    // the child is killed with the gateway
+    //
+    pub async fn spawn(config: &StdioServerConfig) -> Result<Self> {
+        let mut command = Command::new(&config.command);
+        command.args(&config.args).envs(&config.env);
+
+        if let Some(cwd) = &config.cwd {
+            command.current_dir(cwd);
+        }
+
+        Self::connect_transport(ChildTransport::spawn(command)?).await
+    }
+
+    //
+    // newline delimited JSON-RPC over any pair of streams
+    //
+    pub async fn connect_io<R, W>(reader: R, writer: W) -> Result<Self>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::connect_transport(IoTransport::new(reader, writer)).await
+    }
+
+    pub async fn connect_sse(builder: OMcpClientBuilder) -> Result<Self> {
+        Self::connect_transport(SseTransport::connect(builder).await?).await
+    }
+
+    pub async fn connect_http(builder: OMcpClientBuilder) -> Result<Self> {
+        Self::connect_transport(HttpConnector::from_builder(builder)?.transport()).await
+    }
+
+    pub async fn connect_ws(builder: OMcpClientBuilder) -> Result<Self> {
+        Self::connect_transport(WebSocketTransport::connect(builder).await?).await
+    }
+
+    //
+    // the initialize handshake and the first tools/list happen here, the
+    // upstream server is up and running once this returns
+    //
+    pub async fn connect_transport<T>(transport: T) -> Result<Self>
+    where
+        T: Transport + 'static,
+    {
+        let (upstream, incoming) = McpSession::with_incoming(transport);
+        let inner = GatewayInner::new(upstream);
+
+        tokio::spawn(incoming_pump(Arc::downgrade(&inner), incoming));
+
+        let res = inner.upstream.initialize().await?;
+        inner.set_capabilities(&res);
+        inner.refresh_tools().await?;
+
+        Ok(Self { inner })
+    }
+
+    //
+    // serve it with io_loop(), http_loop() or sse_loop(). Every session
+    // shares the upstream connection
+    //
+    pub fn server(&self) -> OmcpServer<Error> {
+        let forwarder = GatewayForwarder {
+            inner: self.inner.clone(),
+        };
+
+        OmcpServer::with_handle(self.inner.handle.clone()).with_forwarder(forwarder)
+    }
+}
+
+#[async_trait]
+impl McpForwarder for GatewayForwarder {
+    fn capabilities(&self) -> Map<String, Value> {
+        lock(&self.inner.capabilities).clone()
+    }
+
+    fn session_started(&self, ctx: ToolContext) {
+        self.inner.add_session(ctx);
+    }
+
+    async fn request(&self, ctx: &ToolContext, method: &str, params: Value) -> Result<Map<String, Value>> {
+        self.inner.forward(ctx, method, params).await
+    }
+
+    fn forwards_tool(&self, name: &str) -> bool {
+        lock(&self.inner.tools).contains_key(name)
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        if let Err(e) = self.inner.upstream.notify(method, params) {
+            error!("{e}");
+        }
+    }
+}
+
+#[async_trait]
+impl BakedMcpToolTrait for ProxyTool {
+    type Error = Error;
+
+    async fn call(&self, _ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+        Err(Error::NotImplemented)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use serde_json::{Map, Value, json};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+
+    use crate::{
+        error::{Error, Result},
+        gateway::Gateway,
+        json_rpc::{JsonRPCMessage, JsonRPCMessageBuilder},
+        server::{context::ToolContext, forward::McpForwarder, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct RootsTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for RootsTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            ctx.log(log::Level::Info, json!("listing"))?;
+            let roots = ctx.list_roots().await?;
+            Ok(format!("{}", roots.len()))
+        }
+    }
+
+    //
+    // reports progress and hangs until it gets cancelled
+    //
+    struct SlowTool {
+        dropped: Arc<AtomicBool>,
+    }
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl BakedMcpToolTrait for SlowTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            let _flag = DropFlag(self.dropped.clone());
+            ctx.progress(0.5, Some(1.0), Some("halfway"))?;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok("done".into())
+        }
+    }
+
+    struct Resources {}
+
+    #[async_trait]
+    impl McpForwarder for Resources {
+        fn capabilities(&self) -> Map<String, Value> {
+            json!({"resources": {}}).as_object().cloned().unwrap()
+        }
+
+        async fn request(&self, _ctx: &ToolContext, method: &str, params: Value) -> Result<Map<String, Value>> {
+            match method {
+                "resources/read" => {
+                    let contents = json!({"contents": [{"uri": params["uri"], "text": "hello"}]});
+                    Ok(contents.as_object().cloned().unwrap())
+                }
+                "tools/call" => {
+                    let result = json!({
+                        "content": [{"type": "image", "data": "AA==", "mimeType": "image/png"}],
+                        "structuredContent": {"width": 1},
+                        "isError": false,
+                    });
+                    Ok(result.as_object().cloned().unwrap())
+                }
+                _ => Err(Error::JsonRpcError {
+                    code: -32601,
+                    message: method.into(),
+                }),
+            }
+        }
+
+        fn forwards_tool(&self, name: &str) -> bool {
+            name == "image"
+        }
+    }
+
+    async fn read_messages<R>(reader: &mut R, data: &mut Vec<u8>, count: usize) -> Vec<JsonRPCMessage>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            let mut stream = serde_json::Deserializer::from_slice(data).into_iter::<JsonRPCMessage>();
+            let messages: Vec<JsonRPCMessage> = stream.by_ref().take(count).filter_map(|m| m.ok()).collect();
+
+            if messages.len() == count {
+                let offset = stream.byte_offset();
+                data.drain(..offset);
+                break messages;
+            }
+
+            let len = reader.read(&mut buffer).await.unwrap();
+            data.extend(&buffer[..len]);
+        }
+    }
+
+    async fn send(writer: &mut WriteHalf<DuplexStream>, id: Option<u64>, method: &str, params: Value) {
+        let mut builder = JsonRPCMessageBuilder::new().with_method(method);
+
+        if let Some(id) = id {
+            builder = builder.with_id(id);
+        }
+
+        if !params.is_null() {
+            builder = builder.with_parameter(serde_json::from_value(params).unwrap());
+        }
+
+        let mut data = serde_json::to_vec(&builder.build()).unwrap();
+        data.push(b'\n');
+        writer.write_all(&data).await.unwrap();
+    }
+
+    async fn start(dropped: Arc<AtomicBool>) -> (Gateway, ReadHalf<DuplexStream>, WriteHalf<DuplexStream>) {
+        let mut upstream = OmcpServer::<Error>::new().with_forwarder(Resources {});
+        upstream.add_tool("roots", RootsTool {});
+        upstream.add_tool("slow", SlowTool { dropped });
+        upstream.add_tool("image", RootsTool {});
+
+        let (up_client, up_server) = tokio::io::duplex(4096);
+        let (up_read, up_write) = tokio::io::split(up_server);
+
+        tokio::spawn(async move { upstream.serve(up_read, up_write).await });
+
+        let (reader, writer) = tokio::io::split(up_client);
+        let gateway = Gateway::connect_io(reader, writer).await.unwrap();
+
+        let (client_read, client_write) = client(&gateway);
+        (gateway, client_read, client_write)
+    }
+
+    fn client(gateway: &Gateway) -> (ReadHalf<DuplexStream>, WriteHalf<DuplexStream>) {
+        let (client, server_io) = tokio::io::duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server_io);
+
+        let mut server = gateway.server();
+        tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        tokio::io::split(client)
+    }
+
+    #[tokio::test]
+    async fn reexport_upstream() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (_gateway, mut reader, mut writer) = start(dropped.clone()).await;
+        let mut data = Vec::new();
+
+        send(
+            &mut writer,
+            Some(1),
+            "initialize",
+            json!({"capabilities": {"roots": {}}}),
+        )
+        .await;
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        let capabilities = &res[0].result.as_ref().unwrap()["capabilities"];
+        assert!(capabilities.get("resources").is_some());
+        assert!(capabilities.get("tools").is_some());
+
+        send(&mut writer, Some(2), "tools/list", Value::Null).await;
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        assert_eq!(res[0].result.as_ref().unwrap()["tools"].as_array().unwrap().len(), 3);
+
+        //
+        // upstream notification and request make it to the client
+        //
+        send(&mut writer, Some(3), "tools/call", json!({"name": "roots"})).await;
+        let res = read_messages(&mut reader, &mut data, 2).await;
+        assert_eq!(res[0].method.as_deref(), Some("notifications/message"));
+        assert_eq!(res[1].method.as_deref(), Some("roots/list"));
+
+        let roots = JsonRPCMessageBuilder::new()
+            .with_id(res[1].id.unwrap())
+            .with_result(serde_json::from_value(json!({"roots": [{"uri": "file:///tmp"}]})).unwrap())
+            .build();
+        let mut frame = serde_json::to_vec(&roots).unwrap();
+        frame.push(b'\n');
+        writer.write_all(&frame).await.unwrap();
+
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        assert_eq!(res[0].id, Some(3));
+        assert_eq!(res[0].result.as_ref().unwrap()["content"][0]["text"], "1");
+
+        //
+        // whatever the upstream tool returns, not only text
+        //
+        send(&mut writer, Some(7), "tools/call", json!({"name": "image"})).await;
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        let result = res[0].result.as_ref().unwrap();
+        assert_eq!(result["content"][0]["mimeType"], "image/png");
+        assert_eq!(result["structuredContent"]["width"], 1);
+
+        send(&mut writer, Some(4), "resources/read", json!({"uri": "mem://a"})).await;
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        assert_eq!(res[0].result.as_ref().unwrap()["contents"][0]["text"], "hello");
+
+        send(&mut writer, Some(5), "prompts/list", Value::Null).await;
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        assert_eq!(res[0].error.as_ref().unwrap().code, -32601);
+
+        //
+        // progress comes back with the client's token, cancellation goes up
+        //
+        send(
+            &mut writer,
+            Some(6),
+            "tools/call",
+            json!({"name": "slow", "_meta": {"progressToken": "abc"}}),
+        )
+        .await;
+        let res = read_messages(&mut reader, &mut data, 1).await;
+        assert_eq!(res[0].method.as_deref(), Some("notifications/progress"));
+        assert_eq!(res[0].parameters.as_ref().unwrap()["progressToken"], "abc");
+
+        send(&mut writer, None, "notifications/cancelled", json!({"requestId": 6})).await;
+
+        for _ in 0..100 {
+            if dropped.load(Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    //
+    // upstream requests skip the clients that went away
+    //
+    #[tokio::test]
+    async fn dead_sessions() {
+        let (gateway, mut reader, mut writer) = start(Arc::new(AtomicBool::new(false))).await;
+        let mut data = Vec::new();
+
+        send(
+            &mut writer,
+            Some(1),
+            "initialize",
+            json!({"capabilities": {"roots": {}}}),
+        )
+        .await;
+        read_messages(&mut reader, &mut data, 1).await;
+
+        let (mut gone_reader, mut gone_writer) = client(&gateway);
+        let mut gone_data = Vec::new();
+
+        send(
+            &mut gone_writer,
+            Some(1),
+            "initialize",
+            json!({"capabilities": {"roots": {}}}),
+        )
+        .await;
+        read_messages(&mut gone_reader, &mut gone_data, 1).await;
+
+        drop(gone_reader);
+        drop(gone_writer);
+
+        for _ in 0..100 {
+            if gateway.inner.sessions().len() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(gateway.inner.sessions().len(), 1);
+
+        send(&mut writer, Some(2), "tools/call", json!({"name": "roots"})).await;
+        let res = read_messages(&mut reader, &mut data, 2).await;
+        assert_eq!(res[1].method.as_deref(), Some("roots/list"));
+    }
+}
@@ -8,6 +8,12 @@ pub const JSON_RPC_PROTOCOL_VERSION: &str = "2025-03-26";
 pub const CLIENT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CLIENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+pub const JSON_RPC_PARSE_ERROR: i64 = -32700;
+pub const JSON_RPC_INVALID_REQUEST: i64 = -32600;
+pub const JSON_RPC_METHOD_NOT_FOUND: i64 = -32601;
+pub const JSON_RPC_INVALID_PARAMS: i64 = -32602;
+pub const JSON_RPC_INTERNAL_ERROR: i64 = -32603;
+
 #[derive(Serialize)]
 pub struct JsonRPCRoots {
     #[serde(rename = "listChanged")]
@@ -23,10 +29,10 @@ pub struct JsonRPCCapabilities {
     sampling: JsonRPCSampling,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JsonRPCClientInfo {
-    name: String,
-    version: String,
+    pub name: String,
+    pub version: String,
 }
 
 #[derive(Serialize)]
@@ -53,15 +59,15 @@ pub struct JsonRPCMessageBuilder {
     inner: JsonRPCMessage,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct JsonRPCError {
-    code: u64,
-    message: String,
+    pub code: i64,
+    pub message: String,
 }
 
 pub type JsonRPCParameters = HashMap<String, Value>;
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct JsonRPCMessage {
     pub jsonrpc: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -155,7 +161,7 @@ impl JsonRPCMessageBuilder {
         self
     }
 
-    pub fn with_error<S>(mut self, code: u64, message: S) -> Self
+    pub fn with_error<S>(mut self, code: i64, message: S) -> Self
     where
         S: AsRef<str>,
     {
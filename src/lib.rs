@@ -1,6 +1,12 @@
 pub mod client;
+pub mod codec;
+pub mod config;
 pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gateway;
 pub mod json_rpc;
 pub mod server;
 pub mod test;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transport;
 pub mod types;
@@ -0,0 +1,300 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+#[cfg(not(target_arch = "wasm32"))]
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use log::warn;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::header::WWW_AUTHENTICATE;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName};
+
+use crate::{
+    error::{Error, Result},
+    types::MaybeSendSync,
+};
+
+//
+// who made the request, available to tools through ToolContext::principal()
+//
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Principal {
+    pub subject: String,
+    pub scopes: Vec<String>,
+}
+
+//
+// Validates the headers of every HTTP request, an error turns into a 401 with
+// challenge() as the WWW-Authenticate header
+//
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait Authenticator: MaybeSendSync {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal>;
+
+    fn challenge(&self) -> String {
+        "Bearer".into()
+    }
+}
+
+pub type SharedAuthenticator = Arc<dyn Authenticator>;
+
+//
+// Authorization: Bearer <token>, what OMcpClientBuilder::with_sse_bearer sends
+//
+#[derive(Default)]
+pub struct BearerAuthenticator {
+    realm: Option<String>,
+    resource_metadata: Option<String>,
+    tokens: HashMap<String, Principal>,
+}
+
+//
+// static API keys in a custom header, e.g. X-Api-Key
+//
+pub struct HeaderAuthenticator {
+    header: HeaderName,
+    keys: HashMap<String, Principal>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+
+//
+// doesn't bail out on the first different byte so the time it takes doesn't
+// tell how much of the secret was right
+//
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn find_secret<'a>(secrets: &'a HashMap<String, Principal>, value: &str) -> Option<&'a Principal> {
+    let mut found = None;
+
+    for (secret, principal) in secrets.iter() {
+        if constant_time_eq(secret.as_bytes(), value.as_bytes()) {
+            found = Some(principal);
+        }
+    }
+
+    found
+}
+
+//
+// the principal when the request is allowed, the 401 to send back otherwise
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn authorize(
+    auth: Option<&SharedAuthenticator>,
+    headers: &HeaderMap,
+) -> core::result::Result<Option<Principal>, Response> {
+    let auth = match auth {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    match auth.authenticate(headers).await {
+        Ok(p) => Ok(Some(p)),
+        Err(e) => {
+            let res = (
+                StatusCode::UNAUTHORIZED,
+                [(WWW_AUTHENTICATE, auth.challenge())],
+                e.to_string(),
+            );
+            Err(res.into_response())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Principal {
+    pub fn new<S>(subject: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            subject: subject.as_ref().to_string(),
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn with_scope<S>(mut self, scope: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.scopes.push(scope.as_ref().to_string());
+        self
+    }
+
+    pub fn has_scope<S>(&self, scope: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        self.scopes.iter().any(|s| s == scope.as_ref())
+    }
+}
+
+impl BearerAuthenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_realm<S>(mut self, realm: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.realm = Some(realm.as_ref().to_string());
+        self
+    }
+
+    //
+    // protected resource metadata url advertised to OAuth clients
+    //
+    pub fn with_resource_metadata<S>(mut self, url: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.resource_metadata = Some(url.as_ref().to_string());
+        self
+    }
+
+    pub fn with_token<S>(mut self, token: S, principal: Principal) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.tokens.insert(token.as_ref().to_string(), principal);
+        self
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Authenticator for BearerAuthenticator {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal> {
+        let value = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(Error::Unauthorized)?;
+
+        let token = match value.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+            _ => return Err(Error::Unauthorized),
+        };
+
+        match find_secret(&self.tokens, token) {
+            Some(p) => Ok(p.clone()),
+            None => {
+                warn!("invalid bearer token");
+                Err(Error::Unauthorized)
+            }
+        }
+    }
+
+    fn challenge(&self) -> String {
+        let mut params = Vec::new();
+
+        if let Some(realm) = &self.realm {
+            params.push(format!("realm=\"{realm}\""));
+        }
+
+        if let Some(url) = &self.resource_metadata {
+            params.push(format!("resource_metadata=\"{url}\""));
+        }
+
+        match params.is_empty() {
+            true => "Bearer".into(),
+            false => format!("Bearer {}", params.join(", ")),
+        }
+    }
+}
+
+impl HeaderAuthenticator {
+    pub fn new<S>(header: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let header = HeaderName::from_bytes(header.as_ref().as_bytes())?;
+
+        Ok(Self {
+            header,
+            keys: HashMap::new(),
+        })
+    }
+
+    pub fn with_key<S>(mut self, key: S, principal: Principal) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.keys.insert(key.as_ref().to_string(), principal);
+        self
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Authenticator for HeaderAuthenticator {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal> {
+        let key = headers
+            .get(&self.header)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(Error::Unauthorized)?;
+
+        match find_secret(&self.keys, key.trim()) {
+            Some(p) => Ok(p.clone()),
+            None => {
+                warn!("invalid {} key", self.header);
+                Err(Error::Unauthorized)
+            }
+        }
+    }
+
+    fn challenge(&self) -> String {
+        format!("ApiKey header=\"{}\"", self.header)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use crate::{
+        error::Error,
+        server::auth::{Authenticator, BearerAuthenticator, HeaderAuthenticator, Principal},
+    };
+
+    #[tokio::test]
+    async fn bearer_and_header() {
+        let bearer = BearerAuthenticator::new().with_token("secret", Principal::new("alice").with_scope("tools"));
+
+        let mut headers = HeaderMap::new();
+        assert!(matches!(bearer.authenticate(&headers).await, Err(Error::Unauthorized)));
+
+        headers.insert("Authorization", HeaderValue::from_static("Bearer nope"));
+        assert!(matches!(bearer.authenticate(&headers).await, Err(Error::Unauthorized)));
+
+        headers.insert("Authorization", HeaderValue::from_static("bearer secret"));
+        let principal = bearer.authenticate(&headers).await.unwrap();
+        assert_eq!(principal.subject, "alice");
+        assert!(principal.has_scope("tools"));
+
+        let api_key = HeaderAuthenticator::new("X-Api-Key")
+            .unwrap()
+            .with_key("k1", Principal::new("bob"));
+
+        headers.insert("x-api-key", HeaderValue::from_static("k1"));
+        assert_eq!(api_key.authenticate(&headers).await.unwrap().subject, "bob");
+        assert_eq!(api_key.challenge(), "ApiKey header=\"x-api-key\"");
+    }
+}
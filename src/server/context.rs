@@ -0,0 +1,554 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use log::{Level, LevelFilter, warn};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::{CLIENT_NAME, JsonRPCClientInfo, JsonRPCMessage, JsonRPCMessageBuilder},
+    server::auth::Principal,
+};
+
+//
+// what the client told us in its initialize request
+//
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SessionInfo {
+    #[serde(rename = "protocolVersion", default)]
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: Map<String, Value>,
+    #[serde(rename = "clientInfo", default)]
+    pub client_info: JsonRPCClientInfo,
+}
+
+//
+// the process on the other end of a Unix domain socket, SO_PEERCRED on Linux
+//
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+//
+// server side of the connection shared by every running tool
+//
+pub(crate) struct ServerPeer {
+    outgoing: mpsc::UnboundedSender<JsonRPCMessage>,
+    pending: Mutex<HashMap<u64, oneshot::Sender<Result<JsonRPCMessage>>>>,
+    running: Mutex<HashMap<u64, CancellationToken>>,
+    next_id: AtomicU64,
+    session_id: Mutex<Option<String>>,
+    principal: Mutex<Option<Principal>>,
+    peer_credentials: Mutex<Option<PeerCredentials>>,
+    session: Mutex<Option<SessionInfo>>,
+    log_level: Mutex<LevelFilter>,
+}
+
+pub struct ToolContext {
+    request_id: Option<u64>,
+    meta: Option<Map<String, Value>>,
+    session_id: Option<String>,
+    principal: Option<Principal>,
+    peer_credentials: Option<PeerCredentials>,
+    session: Option<SessionInfo>,
+    cancellation: CancellationToken,
+    peer: Option<Arc<ServerPeer>>,
+}
+
+//
+// a request the other side is working on, it gets notifications/cancelled if
+// we stop waiting for the answer
+//
+struct PendingRequest<'a> {
+    peer: &'a ServerPeer,
+    id: u64,
+    answered: bool,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn mcp_log_level(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warning",
+        Level::Info => "info",
+        Level::Debug | Level::Trace => "debug",
+    }
+}
+
+pub(crate) fn parse_log_level<S>(level: S) -> Option<LevelFilter>
+where
+    S: AsRef<str>,
+{
+    let level = match level.as_ref() {
+        "debug" => LevelFilter::Debug,
+        "info" | "notice" => LevelFilter::Info,
+        "warning" => LevelFilter::Warn,
+        "error" | "critical" | "alert" | "emergency" => LevelFilter::Error,
+        _ => return None,
+    };
+
+    Some(level)
+}
+
+//
+// requestId of notifications/cancelled, a number or a string. Our ids are
+// numbers so a string has to hold one
+//
+pub(crate) fn parse_request_id(params: &Value) -> Option<u64> {
+    match params.get("requestId")? {
+        Value::Number(v) => v.as_u64(),
+        Value::String(v) => v.parse().ok(),
+        _ => None,
+    }
+}
+
+fn build_notification<S>(method: S, params: Value) -> Result<JsonRPCMessage>
+where
+    S: AsRef<str>,
+{
+    let mut builder = JsonRPCMessageBuilder::new().with_method(method);
+
+    if !params.is_null() {
+        let params: HashMap<String, Value> = serde_json::from_value(params)?;
+        builder = builder.with_parameter(params);
+    }
+
+    Ok(builder.build())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        if self.answered {
+            return;
+        }
+
+        lock(&self.peer.pending).remove(&self.id);
+
+        if let Ok(msg) = build_notification("notifications/cancelled", json!({ "requestId": self.id })) {
+            let _ = self.peer.send(msg);
+        }
+    }
+}
+
+impl ServerPeer {
+    pub(crate) fn new(outgoing: mpsc::UnboundedSender<JsonRPCMessage>) -> Self {
+        Self {
+            outgoing,
+            pending: Mutex::new(HashMap::new()),
+            running: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            session_id: Mutex::new(None),
+            principal: Mutex::new(None),
+            peer_credentials: Mutex::new(None),
+            session: Mutex::new(None),
+            log_level: Mutex::new(LevelFilter::Info),
+        }
+    }
+
+    //
+    // the session's server is gone
+    //
+    pub(crate) fn is_closed(&self) -> bool {
+        self.outgoing.is_closed()
+    }
+
+    pub(crate) fn send(&self, msg: JsonRPCMessage) -> Result<()> {
+        self.outgoing.send(msg).map_err(|_| Error::EventSendFailure)
+    }
+
+    pub(crate) fn notify<S>(&self, method: S, params: Value) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.send(build_notification(method, params)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_session_id<S>(&self, id: S)
+    where
+        S: AsRef<str>,
+    {
+        *lock(&self.session_id) = Some(id.as_ref().to_string());
+    }
+
+    pub(crate) fn session_id(&self) -> Option<String> {
+        lock(&self.session_id).clone()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_principal(&self, principal: Option<Principal>) {
+        *lock(&self.principal) = principal;
+    }
+
+    pub(crate) fn principal(&self) -> Option<Principal> {
+        lock(&self.principal).clone()
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn set_peer_credentials(&self, credentials: Option<PeerCredentials>) {
+        *lock(&self.peer_credentials) = credentials;
+    }
+
+    pub(crate) fn peer_credentials(&self) -> Option<PeerCredentials> {
+        *lock(&self.peer_credentials)
+    }
+
+    pub(crate) fn set_session(&self, session: SessionInfo) {
+        *lock(&self.session) = Some(session);
+    }
+
+    pub(crate) fn session(&self) -> Option<SessionInfo> {
+        lock(&self.session).clone()
+    }
+
+    pub(crate) fn set_log_level(&self, level: LevelFilter) {
+        *lock(&self.log_level) = level;
+    }
+
+    pub(crate) fn start_call(&self, request_id: u64) -> CancellationToken {
+        let token = CancellationToken::new();
+        lock(&self.running).insert(request_id, token.clone());
+        token
+    }
+
+    pub(crate) fn end_call(&self, request_id: u64) {
+        lock(&self.running).remove(&request_id);
+    }
+
+    pub(crate) fn cancel_call(&self, request_id: u64) {
+        if let Some(token) = lock(&self.running).remove(&request_id) {
+            token.cancel();
+        }
+    }
+
+    //
+    // response to one of our requests, false if nobody was waiting for it
+    //
+    pub(crate) fn complete(&self, msg: JsonRPCMessage) -> bool {
+        let sender = match msg.id {
+            Some(id) => lock(&self.pending).remove(&id),
+            None => None,
+        };
+
+        match sender {
+            Some(sender) => sender.send(Ok(msg)).is_ok(),
+            None => false,
+        }
+    }
+
+    //
+    // one of our requests never made it to the other side
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn fail(&self, request_id: u64, error: Error) -> bool {
+        match lock(&self.pending).remove(&request_id) {
+            Some(sender) => sender.send(Err(error)).is_ok(),
+            None => false,
+        }
+    }
+
+    //
+    // the other side went away, nobody is going to answer
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn drop_pending(&self) {
+        lock(&self.pending).clear();
+    }
+
+    pub(crate) async fn request<S>(&self, method: S, params: Value) -> Result<Map<String, Value>>
+    where
+        S: AsRef<str>,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let params: HashMap<String, Value> = serde_json::from_value(params)?;
+
+        let msg = JsonRPCMessageBuilder::new()
+            .with_id(id)
+            .with_method(method)
+            .with_parameter(params)
+            .build();
+
+        let (tx, rx) = oneshot::channel();
+
+        lock(&self.pending).insert(id, tx);
+
+        if let Err(e) = self.send(msg) {
+            lock(&self.pending).remove(&id);
+            return Err(e);
+        }
+
+        let mut pending = PendingRequest {
+            peer: self,
+            id,
+            answered: false,
+        };
+
+        let res = rx.await;
+        pending.answered = true;
+
+        let res = res.map_err(|_| Error::NotConnected)??;
+
+        match (res.result, res.error) {
+            (_, Some(e)) => Err(Error::JsonRpcError {
+                code: e.code,
+                message: e.message,
+            }),
+            (Some(result), None) => Ok(result.into_iter().collect()),
+            (None, None) => Err(Error::Empty),
+        }
+    }
+}
+
+impl ToolContext {
+    pub(crate) fn new(request_id: Option<u64>, meta: Option<Map<String, Value>>, peer: Arc<ServerPeer>) -> Self {
+        let cancellation = match request_id {
+            Some(id) => peer.start_call(id),
+            None => CancellationToken::new(),
+        };
+
+        Self {
+            request_id,
+            meta,
+            session_id: peer.session_id(),
+            principal: peer.principal(),
+            peer_credentials: peer.peer_credentials(),
+            session: peer.session(),
+            cancellation,
+            peer: Some(peer),
+        }
+    }
+
+    //
+    // context for tools called without a server, e.g. BakedClient
+    //
+    pub fn detached() -> Self {
+        Self {
+            request_id: None,
+            meta: None,
+            session_id: None,
+            principal: None,
+            peer_credentials: None,
+            session: None,
+            cancellation: CancellationToken::new(),
+            peer: None,
+        }
+    }
+
+    pub fn request_id(&self) -> Option<u64> {
+        self.request_id
+    }
+
+    pub fn meta(&self) -> Option<&Map<String, Value>> {
+        self.meta.as_ref()
+    }
+
+    pub fn progress_token(&self) -> Option<&Value> {
+        self.meta.as_ref()?.get("progressToken")
+    }
+
+    //
+    // transport session, stable for the lifetime of the client connection
+    //
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    //
+    // set when the server has an Authenticator
+    //
+    pub fn principal(&self) -> Option<&Principal> {
+        self.principal.as_ref()
+    }
+
+    //
+    // set when the client came in over a Unix domain socket
+    //
+    pub fn peer_credentials(&self) -> Option<&PeerCredentials> {
+        self.peer_credentials.as_ref()
+    }
+
+    pub fn session(&self) -> Option<&SessionInfo> {
+        self.session.as_ref()
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    //
+    // false once the session ended, a detached context never is
+    //
+    pub fn is_connected(&self) -> bool {
+        self.peer.as_ref().is_some_and(|p| !p.is_closed())
+    }
+
+    fn peer(&self) -> Result<&Arc<ServerPeer>> {
+        self.peer.as_ref().ok_or(Error::NotConnected)
+    }
+
+    fn has_capability<S>(&self, name: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        match &self.session {
+            Some(s) => s.capabilities.contains_key(name.as_ref()),
+            None => false,
+        }
+    }
+
+    fn require_capability<S>(&self, name: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        match self.has_capability(&name) {
+            true => Ok(()),
+            false => Err(Error::CapabilityMissing {
+                name: name.as_ref().to_string(),
+            }),
+        }
+    }
+
+    //
+    // silently dropped when the client didn't ask for progress
+    //
+    pub fn progress<S>(&self, progress: f64, total: Option<f64>, message: Option<S>) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let token = match self.progress_token() {
+            Some(v) => v.clone(),
+            None => return Ok(()),
+        };
+
+        let mut params = HashMap::new();
+        params.insert("progressToken".to_string(), token);
+        params.insert("progress".to_string(), json!(progress));
+
+        if let Some(total) = total {
+            params.insert("total".to_string(), json!(total));
+        }
+
+        if let Some(message) = message {
+            params.insert("message".to_string(), json!(message.as_ref()));
+        }
+
+        let msg = JsonRPCMessageBuilder::new()
+            .with_method("notifications/progress")
+            .with_parameter(params)
+            .build();
+
+        self.peer()?.send(msg)
+    }
+
+    //
+    // any other notification, params is an object or null
+    //
+    pub fn notify<S>(&self, method: S, params: Value) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.peer()?.notify(method, params)
+    }
+
+    pub fn log(&self, level: Level, data: Value) -> Result<()> {
+        let peer = self.peer()?;
+
+        if level > *lock(&peer.log_level) {
+            return Ok(());
+        }
+
+        let mut params = HashMap::new();
+        params.insert("level".to_string(), json!(mcp_log_level(level)));
+        params.insert("logger".to_string(), json!(CLIENT_NAME));
+        params.insert("data".to_string(), data);
+
+        let msg = JsonRPCMessageBuilder::new()
+            .with_method("notifications/message")
+            .with_parameter(params)
+            .build();
+
+        peer.send(msg)
+    }
+
+    //
+    // server to client requests, only answered while the server is running
+    // with serve() / io_loop()
+    //
+    pub async fn request<S>(&self, method: S, params: Value) -> Result<Map<String, Value>>
+    where
+        S: AsRef<str>,
+    {
+        let peer = self.peer()?;
+
+        tokio::select! {
+            res = peer.request(method, params) => res,
+            _ = self.cancellation.cancelled() => Err(Error::Cancelled),
+        }
+    }
+
+    pub async fn create_message(&self, params: Value) -> Result<Map<String, Value>> {
+        self.require_capability("sampling")?;
+        self.request("sampling/createMessage", params).await
+    }
+
+    pub async fn elicit<S>(&self, message: S, requested_schema: Value) -> Result<Map<String, Value>>
+    where
+        S: AsRef<str>,
+    {
+        self.require_capability("elicitation")?;
+
+        let params = json!({
+            "message": message.as_ref(),
+            "requestedSchema": requested_schema,
+        });
+
+        self.request("elicitation/create", params).await
+    }
+
+    pub async fn list_roots(&self) -> Result<Vec<Value>> {
+        self.require_capability("roots")?;
+
+        let mut res = self.request("roots/list", json!({})).await?;
+
+        match res.remove("roots") {
+            Some(Value::Array(roots)) => Ok(roots),
+            _ => {
+                warn!("roots/list without roots");
+                Err(Error::NotFound)
+            }
+        }
+    }
+}
+
+impl Drop for ToolContext {
+    fn drop(&mut self) {
+        if let (Some(peer), Some(id)) = (&self.peer, self.request_id) {
+            peer.end_call(id);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use crate::{error::Result, server::context::ToolContext, types::MaybeSendSync};
+
+//
+// Requests and notifications OmcpServer doesn't handle itself (resources,
+// prompts, completion, ...) end up here instead of a method not found error.
+// That's how the gateway hands them to the upstream server
+//
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait McpForwarder: MaybeSendSync {
+    //
+    // merged into the capabilities of the initialize response
+    //
+    fn capabilities(&self) -> Map<String, Value> {
+        Map::new()
+    }
+
+    //
+    // the client is initialized, ctx can be kept to reach it for as long as
+    // the session lasts
+    //
+    fn session_started(&self, _ctx: ToolContext) {}
+
+    //
+    // runs in the background like tools/call, JsonRpcError is passed to the
+    // client as is
+    //
+    async fn request(&self, ctx: &ToolContext, method: &str, params: Value) -> Result<Map<String, Value>>;
+
+    //
+    // tools/call for these goes to request() too and the result is passed
+    // on unchanged, the tools still have to be on the handle to be listed
+    //
+    fn forwards_tool(&self, _name: &str) -> bool {
+        false
+    }
+
+    fn notify(&self, _method: &str, _params: Value) {}
+}
+
+pub type SharedForwarder = Arc<dyn McpForwarder>;
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::sync::watch;
+
+use crate::types::{BakedMcpToolTrait, McpTool};
+
+pub(crate) type SharedHandler<E> = Arc<dyn BakedMcpToolTrait<Error = E>>;
+
+struct ServerTool<E> {
+    tool: McpTool,
+    enabled: bool,
+    handler: SharedHandler<E>,
+}
+
+//
+// Cloneable view on the server tools, can be used while io_loop() is running
+//
+pub struct OmcpServerHandle<E> {
+    tools: Arc<Mutex<HashMap<String, ServerTool<E>>>>,
+    version: Arc<watch::Sender<u64>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl<E> Clone for OmcpServerHandle<E> {
+    fn clone(&self) -> Self {
+        Self {
+            tools: self.tools.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+impl<E> Default for OmcpServerHandle<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> OmcpServerHandle<E> {
+    pub fn new() -> Self {
+        Self {
+            tools: Arc::new(Mutex::new(HashMap::new())),
+            version: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, ServerTool<E>>> {
+        //
+        // the map is always left in a consistent state, poisoning is harmless
+        //
+        self.tools.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_changed(&self) {
+        self.version.send_modify(|v| *v += 1);
+    }
+
+    pub fn add_tool<S, T>(&self, name: S, client: T)
+    where
+        S: AsRef<str>,
+        T: BakedMcpToolTrait<Error = E> + 'static,
+    {
+        let tool = McpTool {
+            name: name.as_ref().to_string(),
+            description: "".into(),
+            input_schema: None,
+        };
+
+        self.add_mcp_tool(tool, client)
+    }
+
+    //
+    // replaces any tool with the same name
+    //
+    pub fn add_mcp_tool<T>(&self, tool: McpTool, client: T)
+    where
+        T: BakedMcpToolTrait<Error = E> + 'static,
+    {
+        let server_tool = ServerTool {
+            tool,
+            enabled: true,
+            handler: Arc::new(client),
+        };
+
+        self.lock().insert(server_tool.tool.name.clone(), server_tool);
+        self.set_changed();
+    }
+
+    pub fn remove_tool<S>(&self, name: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        let removed = self.lock().remove(name.as_ref()).is_some();
+
+        if removed {
+            self.set_changed();
+        }
+
+        removed
+    }
+
+    pub fn set_enabled<S>(&self, name: S, enabled: bool) -> bool
+    where
+        S: AsRef<str>,
+    {
+        let updated = match self.lock().get_mut(name.as_ref()) {
+            Some(t) if t.enabled != enabled => {
+                t.enabled = enabled;
+                true
+            }
+            _ => false,
+        };
+
+        if updated {
+            self.set_changed();
+        }
+
+        updated
+    }
+
+    pub fn enable_tool<S>(&self, name: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        self.set_enabled(name, true)
+    }
+
+    pub fn disable_tool<S>(&self, name: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        self.set_enabled(name, false)
+    }
+
+    pub fn tool_names(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    pub(crate) fn tools(&self) -> Vec<McpTool> {
+        self.lock().values().filter(|t| t.enabled).map(|t| t.tool.clone()).collect()
+    }
+
+    pub(crate) fn handler<S>(&self, name: S) -> Option<SharedHandler<E>>
+    where
+        S: AsRef<str>,
+    {
+        match self.lock().get(name.as_ref()) {
+            Some(t) if t.enabled => Some(t.handler.clone()),
+            _ => None,
+        }
+    }
+
+    //
+    // every session keeps its own receiver so they all hear about changes
+    //
+    pub(crate) fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+}
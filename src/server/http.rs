@@ -0,0 +1,1118 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    convert::Infallible,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+use axum::{
+    Router,
+    extract::State,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::get,
+};
+use futures_util::{Stream, StreamExt, stream};
+use log::{debug, error, info, warn};
+use serde_json::Value;
+use tokio::{
+    io::{self, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf},
+    net::{TcpListener, ToSocketAddrs},
+    sync::mpsc,
+};
+use uuid::Uuid;
+
+use crate::{
+    codec::LineReader,
+    error::{Error, Result},
+    json_rpc::{JSON_RPC_INTERNAL_ERROR, JsonRPCMessage, JsonRPCMessageBuilder},
+    server::{
+        auth::{Principal, SharedAuthenticator, authorize},
+        matrix::OmcpServer,
+        session::SessionManager,
+    },
+};
+
+const SESSION_BUFFER_SIZE: usize = 64 * 1024;
+const MAX_BACKLOG: usize = 1024;
+const DEFAULT_PATH: &str = "/mcp";
+
+pub const MCP_SESSION_ID: HeaderName = HeaderName::from_static("mcp-session-id");
+const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+
+type SessionFactory<E> = Arc<dyn Fn() -> OmcpServer<E> + Send + Sync>;
+type SessionMap = Arc<SessionManager<Arc<HttpSession>>>;
+type EventSender = mpsc::UnboundedSender<(u64, JsonRPCMessage)>;
+type EventReceiver = mpsc::UnboundedReceiver<(u64, JsonRPCMessage)>;
+
+#[derive(Debug, Clone)]
+pub struct HttpServerOptions {
+    path: String,
+    allowed_origins: Vec<String>,
+    event_history: usize,
+}
+
+//
+// where the messages produced by a session should go
+//
+#[derive(Clone)]
+struct StreamRoute {
+    stream: u64,
+    sender: EventSender,
+    json: bool,
+}
+
+struct HistoryEvent {
+    stream: u64,
+    event_id: u64,
+    msg: JsonRPCMessage,
+}
+
+#[derive(Default)]
+struct Routes {
+    requests: HashMap<u64, StreamRoute>,
+    progress: HashMap<String, u64>,
+    standalone: Option<StreamRoute>,
+    backlog: VecDeque<(u64, JsonRPCMessage)>,
+    history: VecDeque<HistoryEvent>,
+    next_event_id: u64,
+    next_stream: u64,
+}
+
+struct HttpSession {
+    id: String,
+    subject: Option<String>,
+    writer: tokio::sync::Mutex<WriteHalf<DuplexStream>>,
+    routes: Mutex<Routes>,
+    event_history: usize,
+}
+
+struct HttpState<E> {
+    new_session: SessionFactory<E>,
+    sessions: SessionMap,
+    auth: Option<SharedAuthenticator>,
+    options: HttpServerOptions,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn error_response<S>(status: StatusCode, message: S) -> Response
+where
+    S: AsRef<str>,
+{
+    (status, message.as_ref().to_string()).into_response()
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn accepts_sse(headers: &HeaderMap) -> bool {
+    header_str(headers, &header::ACCEPT)
+        .map(|v| v.contains("text/event-stream"))
+        .unwrap_or(false)
+}
+
+fn progress_key(token: &Value) -> String {
+    token.to_string()
+}
+
+fn to_event(event_id: u64, msg: &JsonRPCMessage, resumable: bool) -> Event {
+    let data = serde_json::to_string(msg).unwrap_or_default();
+
+    let event = Event::default().event("message").data(data);
+
+    match resumable {
+        true => event.id(event_id.to_string()),
+        false => event,
+    }
+}
+
+fn with_session_header(id: &str, res: impl IntoResponse) -> Response {
+    let mut res = res.into_response();
+
+    if let Ok(v) = HeaderValue::from_str(id) {
+        res.headers_mut().insert(MCP_SESSION_ID, v);
+    }
+
+    res
+}
+
+//
+// browsers always send an Origin, anything we don't know about is rejected
+// to prevent DNS rebinding attacks
+//
+fn check_origin(options: &HttpServerOptions, headers: &HeaderMap) -> Option<Response> {
+    let origin = header_str(headers, &header::ORIGIN)?;
+
+    match options.allowed_origins.iter().any(|o| o == origin || o == "*") {
+        true => None,
+        false => {
+            warn!("rejecting origin {origin}");
+            Some(error_response(StatusCode::FORBIDDEN, "origin not allowed"))
+        }
+    }
+}
+
+fn parse_body(body: &str) -> Result<(Vec<JsonRPCMessage>, bool)> {
+    let value: Value = serde_json::from_str(body)?;
+
+    match value {
+        Value::Array(v) => {
+            let messages = v
+                .into_iter()
+                .map(serde_json::from_value)
+                .collect::<core::result::Result<Vec<JsonRPCMessage>, _>>()?;
+            Ok((messages, true))
+        }
+        v => Ok((vec![serde_json::from_value(v)?], false)),
+    }
+}
+
+//
+// only holds a weak reference, once the session is dropped its writer goes
+// away and serve() returns
+//
+async fn dispatch(session: Weak<HttpSession>, mut reader: LineReader<ReadHalf<DuplexStream>>) {
+    loop {
+        let line = match reader.read_line().await {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("session done: {e}");
+                break;
+            }
+        };
+
+        let msg = match serde_json::from_str::<JsonRPCMessage>(&line) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("{e}");
+                continue;
+            }
+        };
+
+        let session = match session.upgrade() {
+            Some(v) => v,
+            None => break,
+        };
+
+        if let Some(res) = session.route(msg)
+            && let Err(e) = session.write(&[res]).await
+        {
+            error!("{e}");
+        }
+    }
+}
+
+async fn http_post<E>(State(state): State<HttpState<E>>, headers: HeaderMap, body: String) -> Response
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    let principal = match state.admit(&headers).await {
+        Ok(v) => v,
+        Err(res) => return res,
+    };
+
+    let (messages, batch) = match parse_body(&body) {
+        Ok(v) => v,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let is_init = messages.iter().any(|m| m.method.as_deref() == Some("initialize"));
+
+    let session = match (header_str(&headers, &MCP_SESSION_ID), is_init) {
+        (Some(_), _) => match state.lookup(&headers, principal.as_ref()) {
+            Ok(v) => v,
+            Err((status, msg)) => return error_response(status, msg),
+        },
+        (None, true) => match state.create_session(principal) {
+            Ok(v) => v,
+            Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+        },
+        (None, false) => return error_response(StatusCode::BAD_REQUEST, "missing session"),
+    };
+
+    let requests: Vec<&JsonRPCMessage> = messages.iter().filter(|m| m.method.is_some() && m.id.is_some()).collect();
+
+    //
+    // notifications and responses only, nothing to wait for
+    //
+    if requests.is_empty() {
+        return match session.write(&messages).await {
+            Ok(()) => with_session_header(&session.id, StatusCode::ACCEPTED),
+            Err(e) => error_response(StatusCode::GONE, e.to_string()),
+        };
+    }
+
+    let json = !accepts_sse(&headers);
+    let (route, receiver) = session.register(&requests, json);
+    let pending: HashSet<u64> = requests.iter().filter_map(|m| m.id).collect();
+
+    if let Err(e) = session.write(&messages).await {
+        return error_response(StatusCode::GONE, e.to_string());
+    }
+
+    match json {
+        true => json_response(&session, receiver, pending, batch).await,
+        false => {
+            let resumable = session.event_history > 0;
+            let events = request_stream(receiver, pending, resumable);
+            debug!("stream {} for session {}", route.stream, session.id);
+            with_session_header(&session.id, Sse::new(events).keep_alive(KeepAlive::default()))
+        }
+    }
+}
+
+async fn json_response(
+    session: &HttpSession,
+    mut receiver: EventReceiver,
+    mut pending: HashSet<u64>,
+    batch: bool,
+) -> Response {
+    let mut responses = Vec::new();
+
+    while !pending.is_empty() {
+        let msg = match receiver.recv().await {
+            Some((_, msg)) => msg,
+            None => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "session closed"),
+        };
+
+        if let Some(id) = msg.id {
+            pending.remove(&id);
+        }
+
+        responses.push(msg);
+    }
+
+    let body = match batch {
+        true => serde_json::to_string(&responses),
+        false => serde_json::to_string(&responses[0]),
+    };
+
+    match body {
+        Ok(body) => with_session_header(&session.id, ([(header::CONTENT_TYPE, "application/json")], body)),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+//
+// ends once every request got its response
+//
+fn request_stream(
+    receiver: EventReceiver,
+    pending: HashSet<u64>,
+    resumable: bool,
+) -> impl Stream<Item = core::result::Result<Event, Infallible>> {
+    stream::unfold((receiver, pending), move |(mut receiver, mut pending)| async move {
+        if pending.is_empty() {
+            return None;
+        }
+
+        let (event_id, msg) = receiver.recv().await?;
+
+        if msg.method.is_none()
+            && let Some(id) = msg.id
+        {
+            pending.remove(&id);
+        }
+
+        let event = to_event(event_id, &msg, resumable);
+        Some((Ok(event), (receiver, pending)))
+    })
+}
+
+async fn http_get<E>(State(state): State<HttpState<E>>, headers: HeaderMap) -> Response {
+    let principal = match state.admit(&headers).await {
+        Ok(v) => v,
+        Err(res) => return res,
+    };
+
+    if !accepts_sse(&headers) {
+        return error_response(StatusCode::NOT_ACCEPTABLE, "text/event-stream only");
+    }
+
+    let session = match state.lookup(&headers, principal.as_ref()) {
+        Ok(v) => v,
+        Err((status, msg)) => return error_response(status, msg),
+    };
+
+    let last_event_id = header_str(&headers, &LAST_EVENT_ID).and_then(|v| v.parse::<u64>().ok());
+
+    let (replay, receiver) = session.open_standalone(last_event_id);
+
+    let resumable = session.event_history > 0;
+
+    let replay =
+        stream::iter(replay).map(move |(event_id, msg)| Ok::<_, Infallible>(to_event(event_id, &msg, resumable)));
+
+    //
+    // the session stays alive as long as somebody listens
+    //
+    let guard = state.sessions.open_stream(&session.id);
+
+    let live = stream::unfold((receiver, guard), move |(mut receiver, guard)| async move {
+        let (event_id, msg) = receiver.recv().await?;
+
+        if let Some(guard) = &guard {
+            guard.touch();
+        }
+
+        Some((Ok(to_event(event_id, &msg, resumable)), (receiver, guard)))
+    });
+
+    with_session_header(
+        &session.id,
+        Sse::new(replay.chain(live)).keep_alive(KeepAlive::default()),
+    )
+}
+
+async fn http_delete<E>(State(state): State<HttpState<E>>, headers: HeaderMap) -> Response {
+    let principal = match state.admit(&headers).await {
+        Ok(v) => v,
+        Err(res) => return res,
+    };
+
+    let session = match state.lookup(&headers, principal.as_ref()) {
+        Ok(v) => v,
+        Err((status, msg)) => return error_response(status, msg),
+    };
+
+    //
+    // dropping the writer ends the session's serve() loop
+    //
+    match state.sessions.remove(&session.id) {
+        Some(_) => {
+            info!("deleted session {}", session.id);
+            StatusCode::OK.into_response()
+        }
+        None => error_response(StatusCode::NOT_FOUND, "unknown session"),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Default for HttpServerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpServerOptions {
+    pub fn new() -> Self {
+        Self {
+            path: DEFAULT_PATH.into(),
+            allowed_origins: Vec::new(),
+            event_history: 0,
+        }
+    }
+
+    pub fn with_path<S>(mut self, path: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.path = path.as_ref().into();
+        self
+    }
+
+    //
+    // requests without an Origin header are always accepted, "*" allows any
+    //
+    pub fn with_allowed_origin<S>(mut self, origin: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.allowed_origins.push(origin.as_ref().into());
+        self
+    }
+
+    //
+    // number of events kept per session so clients can resume with
+    // Last-Event-ID, 0 disables resumability
+    //
+    pub fn with_event_history(mut self, events: usize) -> Self {
+        self.event_history = events;
+        self
+    }
+}
+
+impl Routes {
+    fn new_stream(&mut self, json: bool) -> (StreamRoute, EventReceiver) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        self.next_stream += 1;
+
+        let route = StreamRoute {
+            stream: self.next_stream,
+            sender,
+            json,
+        };
+
+        (route, receiver)
+    }
+
+    fn target(&mut self, msg: &JsonRPCMessage) -> Option<StreamRoute> {
+        match (msg.method.as_deref(), msg.id) {
+            (None, Some(id)) => {
+                self.progress.retain(|_, v| *v != id);
+                self.requests.remove(&id)
+            }
+            (Some("notifications/progress"), _) => {
+                let token = msg.parameters.as_ref()?.get("progressToken")?;
+                let id = self.progress.get(&progress_key(token))?;
+                self.requests.get(id).filter(|r| !r.json).cloned()
+            }
+            //
+            // roots, sampling, ... asked while a tool runs go on the latest
+            // request stream, JSON responses can't carry them
+            //
+            (Some(_), Some(_)) => self.requests.values().filter(|r| !r.json).max_by_key(|r| r.stream).cloned(),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, stream: u64, event_id: u64, msg: &JsonRPCMessage, max: usize) {
+        if max == 0 {
+            return;
+        }
+
+        self.history.push_back(HistoryEvent {
+            stream,
+            event_id,
+            msg: msg.clone(),
+        });
+
+        while self.history.len() > max {
+            self.history.pop_front();
+        }
+    }
+}
+
+impl HttpSession {
+    fn new(id: String, subject: Option<String>, writer: WriteHalf<DuplexStream>, event_history: usize) -> Self {
+        Self {
+            id,
+            subject,
+            writer: tokio::sync::Mutex::new(writer),
+            routes: Mutex::new(Routes::default()),
+            event_history,
+        }
+    }
+
+    async fn write(&self, messages: &[JsonRPCMessage]) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+
+        for msg in messages {
+            let mut data = serde_json::to_vec(msg)?;
+            data.push(b'\n');
+            writer.write_all(&data).await?;
+        }
+
+        Ok(())
+    }
+
+    fn register(&self, requests: &[&JsonRPCMessage], json: bool) -> (StreamRoute, EventReceiver) {
+        let mut routes = lock(&self.routes);
+
+        let (route, receiver) = routes.new_stream(json);
+
+        for req in requests {
+            let id = match req.id {
+                Some(v) => v,
+                None => continue,
+            };
+
+            routes.requests.insert(id, route.clone());
+
+            let token = req
+                .parameters
+                .as_ref()
+                .and_then(|p| p.get("_meta"))
+                .and_then(|m| m.get("progressToken"));
+
+            if let Some(token) = token {
+                routes.progress.insert(progress_key(token), id);
+            }
+        }
+
+        (route, receiver)
+    }
+
+    fn open_standalone(&self, last_event_id: Option<u64>) -> (Vec<(u64, JsonRPCMessage)>, EventReceiver) {
+        let mut routes = lock(&self.routes);
+
+        let mut replay = Vec::new();
+
+        //
+        // resume the stream the event was sent on
+        //
+        if let Some(last) = last_event_id {
+            let stream = routes.history.iter().find(|e| e.event_id == last).map(|e| e.stream);
+
+            if let Some(stream) = stream {
+                replay = routes
+                    .history
+                    .iter()
+                    .filter(|e| e.stream == stream && e.event_id > last)
+                    .map(|e| (e.event_id, e.msg.clone()))
+                    .collect();
+            }
+        }
+
+        let (route, receiver) = routes.new_stream(false);
+
+        //
+        // what didn't make it to the old stream is in the history and the
+        // backlog, it's only sent once
+        //
+        for (event_id, msg) in std::mem::take(&mut routes.backlog) {
+            if replay.iter().any(|(id, _)| *id == event_id) {
+                continue;
+            }
+
+            routes.record(route.stream, event_id, &msg, self.event_history);
+            let _ = route.sender.send((event_id, msg));
+        }
+
+        routes.standalone = Some(route);
+
+        (replay, receiver)
+    }
+
+    //
+    // a request from the server that has no stream to go on is answered with
+    // an error right away, nothing would ever answer it otherwise
+    //
+    fn route(&self, msg: JsonRPCMessage) -> Option<JsonRPCMessage> {
+        let mut routes = lock(&self.routes);
+
+        routes.next_event_id += 1;
+        let event_id = routes.next_event_id;
+
+        let mut candidates = Vec::new();
+
+        if let Some(target) = routes.target(&msg) {
+            candidates.push(target);
+        }
+
+        if let Some(standalone) = routes.standalone.clone() {
+            candidates.push(standalone);
+        }
+
+        for route in candidates {
+            //
+            // keep it around even if the client went away, it can resume
+            //
+            routes.record(route.stream, event_id, &msg, self.event_history);
+
+            if route.sender.send((event_id, msg.clone())).is_ok() {
+                return None;
+            }
+
+            if routes.standalone.as_ref().map(|s| s.stream) == Some(route.stream) {
+                routes.standalone = None;
+            }
+        }
+
+        if let (Some(id), Some(method)) = (msg.id, msg.method.as_deref()) {
+            warn!("session {} has no stream for {method}", self.id);
+
+            let message = format!("{method} needs a GET stream or a POST accepting text/event-stream");
+            let res = JsonRPCMessageBuilder::new()
+                .with_id(id)
+                .with_error(JSON_RPC_INTERNAL_ERROR, message)
+                .build();
+
+            return Some(res);
+        }
+
+        if routes.backlog.len() >= MAX_BACKLOG {
+            warn!("session {} backlog full", self.id);
+            routes.backlog.pop_front();
+        }
+
+        routes.backlog.push_back((event_id, msg));
+        None
+    }
+}
+
+impl<E> Clone for HttpState<E> {
+    fn clone(&self) -> Self {
+        Self {
+            new_session: self.new_session.clone(),
+            sessions: self.sessions.clone(),
+            auth: self.auth.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<E> HttpState<E> {
+    async fn admit(&self, headers: &HeaderMap) -> core::result::Result<Option<Principal>, Response> {
+        if let Some(res) = check_origin(&self.options, headers) {
+            return Err(res);
+        }
+
+        authorize(self.auth.as_ref(), headers).await
+    }
+
+    //
+    // a session only answers to whoever created it
+    //
+    fn lookup(
+        &self,
+        headers: &HeaderMap,
+        principal: Option<&Principal>,
+    ) -> core::result::Result<Arc<HttpSession>, (StatusCode, &'static str)> {
+        let id = match header_str(headers, &MCP_SESSION_ID) {
+            Some(v) => v,
+            None => return Err((StatusCode::BAD_REQUEST, "missing session")),
+        };
+
+        let session = match self.sessions.get(id) {
+            Some(v) => v,
+            None => return Err((StatusCode::NOT_FOUND, "unknown session")),
+        };
+
+        match principal.map(|p| p.subject.as_str()) == session.subject.as_deref() {
+            true => Ok(session),
+            false => Err((StatusCode::FORBIDDEN, "session belongs to someone else")),
+        }
+    }
+}
+
+impl<E> HttpState<E>
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    fn create_session(&self, principal: Option<Principal>) -> Result<Arc<HttpSession>> {
+        let id = Uuid::new_v4().simple().to_string();
+
+        let (client_io, server_io) = io::duplex(SESSION_BUFFER_SIZE);
+        let (server_read, server_write) = io::split(server_io);
+        let (client_read, client_write) = io::split(client_io);
+
+        let subject = principal.as_ref().map(|p| p.subject.clone());
+        let session = Arc::new(HttpSession::new(
+            id.clone(),
+            subject,
+            client_write,
+            self.options.event_history,
+        ));
+
+        let mut server = (self.new_session)();
+        server.set_session_id(&id);
+        server.set_principal(principal);
+
+        self.sessions.insert(&id, session.clone(), server.peer())?;
+
+        info!("new http session {id}");
+
+        tokio::spawn(async move {
+            match server.serve(server_read, server_write).await {
+                Ok(()) | Err(Error::Eof) => {}
+                Err(e) => error!("{e}"),
+            }
+        });
+
+        let reader = LineReader::new(client_read).with_max_line_length(usize::MAX);
+
+        tokio::spawn(dispatch(Arc::downgrade(&session), reader));
+
+        Ok(session)
+    }
+}
+
+impl<E> OmcpServer<E>
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    //
+    // Streamable HTTP transport, POST / GET / DELETE on a single endpoint
+    //
+    pub fn http_router(&self, options: HttpServerOptions) -> Router {
+        let path = options.path.clone();
+
+        let state = HttpState {
+            new_session: Arc::new(self.session_factory()),
+            sessions: SessionManager::new(self.session_config()),
+            auth: self.authenticator(),
+            options,
+        };
+
+        Router::new()
+            .route(&path, get(http_get::<E>).post(http_post::<E>).delete(http_delete::<E>))
+            .with_state(state)
+    }
+
+    pub async fn http_loop<A>(&self, addr: A, options: HttpServerOptions) -> Result<()>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr).await?;
+        self.http_serve(listener, options).await
+    }
+
+    pub async fn http_serve(&self, listener: TcpListener, options: HttpServerOptions) -> Result<()> {
+        info!("listening on {}", listener.local_addr()?);
+        axum::serve(listener, self.http_router(options)).await?;
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use reqwest::{Client, Response, StatusCode};
+    use serde_json::{Value, json};
+    use tokio::net::TcpListener;
+
+    use crate::{
+        error::{Error, Result},
+        server::{
+            auth::{BearerAuthenticator, Principal},
+            context::ToolContext,
+            http::{HttpServerOptions, MCP_SESSION_ID},
+            matrix::OmcpServer,
+        },
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct ProgressTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for ProgressTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            ctx.progress(1.0, None, Some("done"))?;
+            Ok("ok".into())
+        }
+    }
+
+    struct RootsTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for RootsTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            let roots = ctx.list_roots().await?;
+            Ok(format!("{}", roots.len()))
+        }
+    }
+
+    struct WhoamiTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for WhoamiTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            ctx.principal().map(|p| p.subject.clone()).ok_or(Error::Unauthorized)
+        }
+    }
+
+    fn test_server() -> OmcpServer<Error> {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("progress", ProgressTool {});
+        server.add_tool("whoami", WhoamiTool {});
+        server
+    }
+
+    async fn start(server: OmcpServer<Error>, options: HttpServerOptions) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { server.http_serve(listener, options).await });
+
+        format!("http://{addr}/mcp")
+    }
+
+    //
+    // initialize and initialized, returns the session id
+    //
+    async fn open_session(client: &Client, url: &str, capabilities: Value) -> String {
+        let init = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": capabilities}});
+
+        let res = client.post(url).body(init.to_string()).send().await.unwrap();
+        let session = res.headers()[MCP_SESSION_ID.as_str()].to_str().unwrap().to_string();
+
+        let initialized = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+
+        let res = client
+            .post(url)
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .body(initialized.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+        session
+    }
+
+    //
+    // id and data of the next event on the stream
+    //
+    async fn next_event(res: &mut Response, pending: &mut String) -> (u64, String) {
+        loop {
+            if let Some(end) = pending.find("\n\n") {
+                let block: String = pending.drain(..end + 2).collect();
+
+                let field = |name: &str| block.lines().find_map(|l| l.strip_prefix(name)).map(|v| v.trim().to_string());
+
+                if let (Some(id), Some(data)) = (field("id:"), field("data:")) {
+                    return (id.parse().unwrap(), data);
+                }
+
+                continue;
+            }
+
+            let chunk = res.chunk().await.unwrap().unwrap();
+            pending.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn streamable_http_session() {
+        let url = start(test_server(), HttpServerOptions::new()).await;
+        let client = Client::new();
+
+        let init = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+
+        let res = client
+            .post(&url)
+            .header("Accept", "application/json")
+            .body(init.to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let session = res.headers()[MCP_SESSION_ID.as_str()].to_str().unwrap().to_string();
+        let body: Value = serde_json::from_str(&res.text().await.unwrap()).unwrap();
+        assert_eq!(body["id"], 1);
+
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {"name": "progress", "_meta": {"progressToken": "p1"}}
+        });
+
+        let res = client
+            .post(&url)
+            .header("Accept", "application/json, text/event-stream")
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .body(call.to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["content-type"], "text/event-stream");
+
+        //
+        // the progress notification comes first on the same stream
+        //
+        let body = res.text().await.unwrap();
+        let progress = body.find("notifications/progress").unwrap();
+        let result = body.find("\"id\":2").unwrap();
+        assert!(progress < result);
+
+        let res = client
+            .delete(&url)
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client
+            .post(&url)
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .body(call.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn streamable_http_origin() {
+        let url = start(
+            test_server(),
+            HttpServerOptions::new().with_allowed_origin("http://localhost"),
+        )
+        .await;
+        let client = Client::new();
+
+        let init = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+
+        let res = client
+            .post(&url)
+            .header("Origin", "http://evil.example")
+            .body(init.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = client
+            .post(&url)
+            .header("Origin", "http://localhost")
+            .body(init.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client
+            .post(&url)
+            .body(json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn streamable_http_resume() {
+        let server = test_server();
+        let handle = server.handle();
+
+        let url = start(server, HttpServerOptions::new().with_event_history(16)).await;
+        let client = Client::new();
+        let session = open_session(&client, &url, json!({})).await;
+
+        let get = || {
+            client
+                .get(&url)
+                .header("Accept", "text/event-stream")
+                .header(MCP_SESSION_ID.as_str(), &session)
+        };
+
+        let mut res = get().send().await.unwrap();
+        let mut pending = String::new();
+
+        handle.add_tool("first", ProgressTool {});
+        let (last, data) = next_event(&mut res, &mut pending).await;
+        assert!(data.contains("notifications/tools/list_changed"));
+
+        //
+        // gone while the next one is sent
+        //
+        drop(res);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        handle.add_tool("second", ProgressTool {});
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut res = get().header("Last-Event-ID", last.to_string()).send().await.unwrap();
+        let mut pending = String::new();
+
+        let (id, data) = next_event(&mut res, &mut pending).await;
+        assert!(id > last);
+        assert!(data.contains("notifications/tools/list_changed"));
+
+        //
+        // replayed once, not again from the backlog
+        //
+        handle.add_tool("third", ProgressTool {});
+        let (next, _) = next_event(&mut res, &mut pending).await;
+        assert!(next > id);
+    }
+
+    //
+    // a JSON response can't carry roots/list, the tool gets an error instead
+    // of the POST hanging
+    //
+    #[tokio::test]
+    async fn streamable_http_json_server_request() {
+        let mut server = test_server();
+        server.add_tool("roots", RootsTool {});
+
+        let url = start(server, HttpServerOptions::new()).await;
+        let client = Client::new();
+        let session = open_session(&client, &url, json!({"roots": {}})).await;
+
+        let call = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "roots"}});
+
+        let res = client
+            .post(&url)
+            .header("Accept", "application/json")
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .body(call.to_string())
+            .send();
+
+        let res = tokio::time::timeout(Duration::from_secs(5), res).await.unwrap().unwrap();
+        let body: Value = serde_json::from_str(&res.text().await.unwrap()).unwrap();
+
+        assert_eq!(body["result"]["isError"], true);
+        assert!(body["result"]["content"][0]["text"].as_str().unwrap().contains("GET stream"));
+    }
+
+    #[tokio::test]
+    async fn streamable_http_bearer() {
+        let auth = BearerAuthenticator::new()
+            .with_realm("omcp")
+            .with_token("t1", Principal::new("alice"))
+            .with_token("t2", Principal::new("bob"));
+
+        let url = start(test_server().with_authenticator(auth), HttpServerOptions::new()).await;
+        let client = Client::new();
+
+        let init = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+
+        let res = client.post(&url).body(init.to_string()).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()["www-authenticate"], "Bearer realm=\"omcp\"");
+
+        let res = client.post(&url).bearer_auth("t1").body(init.to_string()).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let session = res.headers()[MCP_SESSION_ID.as_str()].to_str().unwrap().to_string();
+
+        let call = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "whoami"}});
+
+        //
+        // somebody else's session
+        //
+        let res = client
+            .post(&url)
+            .bearer_auth("t2")
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .body(call.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = client
+            .post(&url)
+            .bearer_auth("t1")
+            .header(MCP_SESSION_ID.as_str(), &session)
+            .body(call.to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let body: Value = serde_json::from_str(&res.text().await.unwrap()).unwrap();
+        assert_eq!(body["result"]["content"][0]["text"], "alice");
+    }
+}
@@ -1,66 +1,381 @@
-use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::VecDeque;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use log::{error, info};
+#[cfg(not(target_arch = "wasm32"))]
+use log::error;
+use log::{info, warn};
+use serde_json::{Map, Value, json};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::{
+    io::{self, AsyncRead, AsyncWrite},
+    task::JoinSet,
+};
 use tokio::{
-    io::{self, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter, Stdin},
     select,
+    sync::{mpsc, watch},
 };
 
+#[cfg(unix)]
+use crate::server::context::PeerCredentials;
 use crate::{
+    codec::DEFAULT_MAX_LINE_LENGTH,
     error::{Error, Result},
-    json_rpc::JsonRPCMessage,
-    types::BakedMcpToolTrait,
+    json_rpc::{
+        CLIENT_NAME, CLIENT_VERSION, JSON_RPC_INTERNAL_ERROR, JSON_RPC_INVALID_PARAMS, JSON_RPC_METHOD_NOT_FOUND,
+        JSON_RPC_PROTOCOL_VERSION, JsonRPCMessage, JsonRPCMessageBuilder,
+    },
+    server::{
+        auth::{Authenticator, SharedAuthenticator},
+        context::{ServerPeer, SessionInfo, ToolContext, parse_log_level, parse_request_id},
+        forward::{McpForwarder, SharedForwarder},
+        handle::{OmcpServerHandle, SharedHandler},
+        session::{SessionConfig, SessionEvent},
+    },
+    types::{BakedMcpToolTrait, McpParams, McpTool},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    server::auth::Principal,
+    transport::{Transport, error_reply, io::IoTransport},
 };
 
-const IO_BUFFER_SIZE: usize = 8 * 1024;
+const DEFAULT_MAX_CONCURRENT_CALLS: usize = 16;
+
+//
+// never handed to the forwarder
+//
+const SERVER_METHODS: [&str; 5] = ["initialize", "ping", "logging/setLevel", "tools/list", "tools/call"];
 
 pub struct OmcpServer<E> {
-    tools: HashMap<String, Box<dyn BakedMcpToolTrait<Error = E>>>,
+    handle: OmcpServerHandle<E>,
+    initialized: bool,
+    max_concurrent_calls: usize,
+    max_message_size: usize,
+    peer: Arc<ServerPeer>,
+    outgoing: mpsc::UnboundedReceiver<JsonRPCMessage>,
+    tools_version: watch::Receiver<u64>,
+    sessions: SessionConfig,
+    authenticator: Option<SharedAuthenticator>,
+    forwarder: Option<SharedForwarder>,
+}
+
+enum PreparedCall<E> {
+    Ready(JsonRPCMessage),
+    Pending(ToolContext, SharedHandler<E>, McpParams),
+    Forward(ToolContext, SharedForwarder, String, Value),
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 // PRIVATE FUNCTIONS
 ////////////////////////////////////////////////////////////////////////////////
-async fn read_all(stream: &mut BufReader<Stdin>) -> Result<String> {
-    let mut vec: Vec<u8> = Vec::new();
+//
+// None when the client cancelled the request, it doesn't expect an answer
+//
+async fn run_call<E>(ctx: ToolContext, handler: SharedHandler<E>, params: McpParams) -> Option<JsonRPCMessage>
+where
+    E: std::fmt::Display,
+{
+    let ret = select! {
+        ret = handler.call(&ctx, &params) => ret,
+        _ = ctx.cancellation().cancelled() => {
+            info!("{} cancelled", params.tool_name);
+            return None
+        }
+    };
 
-    let mut buffer = vec![0u8; IO_BUFFER_SIZE];
+    let (text, is_error) = match ret {
+        Ok(v) => (v, false),
+        Err(e) => (format!("{e}"), true),
+    };
 
-    loop {
-        match stream.read(&mut buffer).await {
-            Ok(len) => {
-                info!("len: {len}");
+    let result = json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    });
 
-                if 0 == len {
-                    return Err(Error::Eof);
-                }
+    let id = ctx.request_id();
 
-                vec.extend(&buffer[0..len]);
+    match build_result(id, result) {
+        Ok(v) => Some(v),
+        Err(e) => Some(build_error(id, JSON_RPC_INTERNAL_ERROR, e.to_string())),
+    }
+}
 
-                if vec.ends_with(b"\r\n\r\n") {
-                    break;
-                }
-            }
-            Err(e) => {
-                error!("{e}");
-                return Err(e.into());
-            }
+async fn run_forward(
+    ctx: ToolContext,
+    forwarder: SharedForwarder,
+    method: String,
+    params: Value,
+) -> Option<JsonRPCMessage> {
+    let ret = select! {
+        ret = forwarder.request(&ctx, &method, params) => ret,
+        _ = ctx.cancellation().cancelled() => {
+            info!("{method} cancelled");
+            return None
         }
+    };
+
+    let id = ctx.request_id();
+
+    let res = match ret {
+        Ok(v) => build_result(id, Value::Object(v)),
+        Err(Error::JsonRpcError { code, message }) => Ok(build_error(id, code, message)),
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok(v) => Some(v),
+        Err(e) => Some(build_error(id, JSON_RPC_INTERNAL_ERROR, e.to_string())),
     }
+}
 
-    Ok(String::from_utf8(vec)?)
+fn request_meta(req: &JsonRPCMessage) -> Option<Map<String, Value>> {
+    req.parameters
+        .as_ref()
+        .and_then(|p| p.get("_meta"))
+        .and_then(|v| v.as_object())
+        .cloned()
 }
 
-async fn process_message(_msg: &JsonRPCMessage) -> Result<JsonRPCMessage> {
-    unimplemented!()
+fn tool_to_wire(tool: &McpTool) -> Value {
+    //
+    // McpTool serializes to the LLM friendly "input_schema"
+    //
+    let schema = match &tool.input_schema {
+        Some(v) => json!(v),
+        None => json!({"type": "object"}),
+    };
+
+    json!({
+        "name": tool.name,
+        "description": tool.description,
+        "inputSchema": schema,
+    })
+}
+
+fn build_result(id: Option<u64>, result: Value) -> Result<JsonRPCMessage> {
+    let result: HashMap<String, Value> = serde_json::from_value(result)?;
+
+    let mut builder = JsonRPCMessageBuilder::new().with_result(result);
+
+    if let Some(id) = id {
+        builder = builder.with_id(id);
+    }
+
+    Ok(builder.build())
+}
+
+fn build_error<S>(id: Option<u64>, code: i64, message: S) -> JsonRPCMessage
+where
+    S: AsRef<str>,
+{
+    let mut builder = JsonRPCMessageBuilder::new().with_error(code, message);
+
+    if let Some(id) = id {
+        builder = builder.with_id(id);
+    }
+
+    builder.build()
+}
+
+fn build_list_changed() -> JsonRPCMessage {
+    JsonRPCMessageBuilder::new()
+        .with_method("notifications/tools/list_changed")
+        .build()
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 // IMPL
 ////////////////////////////////////////////////////////////////////////////////
+impl<E> PreparedCall<E>
+where
+    E: std::fmt::Display,
+{
+    async fn run(self) -> Option<JsonRPCMessage> {
+        match self {
+            Self::Ready(res) => Some(res),
+            Self::Pending(ctx, handler, params) => run_call(ctx, handler, params).await,
+            Self::Forward(ctx, forwarder, method, params) => run_forward(ctx, forwarder, method, params).await,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn is_cancelled(&self) -> bool {
+        match self {
+            Self::Ready(_) => false,
+            Self::Pending(ctx, ..) | Self::Forward(ctx, ..) => ctx.is_cancelled(),
+        }
+    }
+}
+
+impl<E> Default for OmcpServer<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<E> OmcpServer<E> {
     pub fn new() -> Self {
-        Self { tools: HashMap::new() }
+        Self::with_handle(OmcpServerHandle::new())
+    }
+
+    pub fn with_handle(handle: OmcpServerHandle<E>) -> Self {
+        let (tx, outgoing) = mpsc::unbounded_channel();
+        let tools_version = handle.subscribe();
+
+        Self {
+            handle,
+            initialized: false,
+            max_concurrent_calls: DEFAULT_MAX_CONCURRENT_CALLS,
+            max_message_size: DEFAULT_MAX_LINE_LENGTH,
+            peer: Arc::new(ServerPeer::new(tx)),
+            outgoing,
+            tools_version,
+            sessions: SessionConfig::default(),
+            authenticator: None,
+            forwarder: None,
+        }
+    }
+
+    //
+    // fresh connection state sharing the same tools and settings
+    //
+    pub fn new_session(&self) -> Self {
+        let mut session = Self::with_handle(self.handle.clone());
+        session.max_concurrent_calls = self.max_concurrent_calls;
+        session.max_message_size = self.max_message_size;
+        session.sessions = self.sessions.clone();
+        session.authenticator = self.authenticator.clone();
+        session.forwarder = self.forwarder.clone();
+        session
+    }
+
+    //
+    // new_session() for transports that open sessions from their own tasks
+    //
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn session_factory(&self) -> impl Fn() -> Self + Send + Sync + 'static
+    where
+        E: 'static,
+    {
+        let template = self.new_session();
+        move || template.new_session()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn session_config(&self) -> SessionConfig {
+        self.sessions.clone()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn authenticator(&self) -> Option<SharedAuthenticator> {
+        self.authenticator.clone()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn peer(&self) -> Arc<ServerPeer> {
+        self.peer.clone()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_principal(&self, principal: Option<Principal>) {
+        self.peer.set_principal(principal)
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn set_peer_credentials(&self, credentials: Option<PeerCredentials>) {
+        self.peer.set_peer_credentials(credentials)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_session_id<S>(&self, id: S)
+    where
+        S: AsRef<str>,
+    {
+        self.peer.set_session_id(id)
+    }
+
+    //
+    // every HTTP request goes through it, stdio is trusted
+    //
+    pub fn with_authenticator<A>(mut self, authenticator: A) -> Self
+    where
+        A: Authenticator + 'static,
+    {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    //
+    // everything but initialize, ping, logging and tools goes there
+    //
+    pub fn with_forwarder<F>(mut self, forwarder: F) -> Self
+    where
+        F: McpForwarder + 'static,
+    {
+        self.forwarder = Some(Arc::new(forwarder));
+        self
+    }
+
+    //
+    // network transports only, stdio is always a single session
+    //
+    pub fn with_max_sessions(mut self, max: usize) -> Self {
+        self.sessions.max_sessions = Some(max);
+        self
+    }
+
+    //
+    // sessions without any request for that long are dropped
+    //
+    pub fn with_session_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.sessions.idle_timeout = Some(timeout);
+        self
+    }
+
+    //
+    // the event's id matches ToolContext::session_id() so tools can keep per
+    // client resources, along with who opened the session
+    //
+    pub fn with_session_start_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SessionEvent) + Send + Sync + 'static,
+    {
+        self.sessions.on_start = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn with_session_end_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SessionEvent) + Send + Sync + 'static,
+    {
+        self.sessions.on_end = Some(Arc::new(hook));
+        self
+    }
+
+    //
+    // tools/call requests running at the same time, the ones past the limit
+    // wait in line until one of them completes
+    //
+    pub fn with_max_concurrent_calls(mut self, max: usize) -> Self {
+        self.max_concurrent_calls = max.max(1);
+        self
+    }
+
+    //
+    // larger messages are dropped
+    //
+    pub fn with_max_message_size(mut self, max: usize) -> Self {
+        self.max_message_size = max;
+        self
+    }
+
+    //
+    // the handle stays valid while io_loop() is running
+    //
+    pub fn handle(&self) -> OmcpServerHandle<E> {
+        self.handle.clone()
     }
 
     pub fn add_tool<S, T>(&mut self, name: S, client: T)
@@ -68,57 +383,794 @@ impl<E> OmcpServer<E> {
         S: AsRef<str>,
         T: BakedMcpToolTrait<Error = E> + 'static,
     {
-        let boxed_client = Box::new(client);
-        self.tools.insert(name.as_ref().to_string(), boxed_client);
+        self.handle.add_tool(name, client)
+    }
+
+    pub fn add_mcp_tool<T>(&mut self, tool: McpTool, client: T)
+    where
+        T: BakedMcpToolTrait<Error = E> + 'static,
+    {
+        self.handle.add_mcp_tool(tool, client)
+    }
+
+    pub fn remove_tool<S>(&mut self, name: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        self.handle.remove_tool(name)
+    }
+
+    //
+    // messages queued for the client (notifications and requests coming from
+    // tools), io_loop() flushes them as they come
+    //
+    pub fn take_notifications(&mut self) -> Vec<JsonRPCMessage> {
+        let mut messages = Vec::new();
+
+        //
+        // nothing to tell if the client hasn't asked for the list yet
+        //
+        if self.take_tools_changed() && self.initialized {
+            messages.push(build_list_changed());
+        }
+
+        while let Ok(msg) = self.outgoing.try_recv() {
+            messages.push(msg);
+        }
+
+        messages
+    }
+
+    fn take_tools_changed(&mut self) -> bool {
+        let changed = self.tools_version.has_changed().unwrap_or(false);
+        self.tools_version.borrow_and_update();
+        changed
     }
 
     pub fn start(&mut self) -> Result<()> {
         Ok(())
     }
+}
+
+impl<E> OmcpServer<E>
+where
+    E: std::fmt::Display,
+{
+    pub async fn handle_message(&mut self, req: &JsonRPCMessage) -> Result<Option<JsonRPCMessage>> {
+        let method = match (&req.method, req.id) {
+            (Some(v), _) => v.as_str(),
+            (None, Some(_)) => {
+                //
+                // answer to a request a tool sent to the client
+                //
+                if !self.peer.complete(req.clone()) {
+                    warn!("unexpected response {:?}", req.id);
+                }
+                return Ok(None);
+            }
+            (None, None) => {
+                warn!("ignoring message without a method");
+                return Ok(None);
+            }
+        };
+
+        //
+        // notifications don't get a response
+        //
+        if req.id.is_none() {
+            self.handle_notification(method, req);
+            return Ok(None);
+        }
+
+        let res = match method {
+            "initialize" => self.handle_initialize(req),
+            "ping" => build_result(req.id, json!({})),
+            "logging/setLevel" => self.handle_set_level(req),
+            "tools/list" => self.handle_list_tools(req),
+            "tools/call" => self.handle_call(req).await,
+            _ => match self.prepare_forward(method, req) {
+                Some(call) => call.run().await.ok_or(Error::Cancelled),
+                None => Ok(build_error(
+                    req.id,
+                    JSON_RPC_METHOD_NOT_FOUND,
+                    format!("{method} not found"),
+                )),
+            },
+        };
+
+        //
+        // a cancelled request doesn't get an answer
+        //
+        match res {
+            Ok(v) => Ok(Some(v)),
+            Err(Error::Cancelled) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, req: &JsonRPCMessage) {
+        match method {
+            "notifications/initialized" => self.initialized = true,
+            "notifications/cancelled" => {
+                let params = req.parameters.clone().map(|p| json!(p)).unwrap_or(Value::Null);
+
+                if let Some(id) = parse_request_id(&params) {
+                    self.peer.cancel_call(id);
+                }
+            }
+            _ => match &self.forwarder {
+                Some(forwarder) => {
+                    let params = req.parameters.clone().map(|p| json!(p)).unwrap_or(Value::Null);
+                    forwarder.notify(method, params);
+                }
+                None => info!("ignoring {method}"),
+            },
+        }
+    }
+
+    fn handle_set_level(&mut self, req: &JsonRPCMessage) -> Result<JsonRPCMessage> {
+        let level = req.parameters.as_ref().and_then(|p| p.get("level")).and_then(|v| v.as_str());
+
+        match level.and_then(parse_log_level) {
+            Some(level) => {
+                self.peer.set_log_level(level);
+                build_result(req.id, json!({}))
+            }
+            None => Ok(build_error(req.id, JSON_RPC_INVALID_PARAMS, "invalid level")),
+        }
+    }
+
+    fn handle_initialize(&mut self, req: &JsonRPCMessage) -> Result<JsonRPCMessage> {
+        let params = req.parameters.clone().unwrap_or_default();
+
+        let session: SessionInfo = match serde_json::to_value(params).and_then(serde_json::from_value) {
+            Ok(v) => v,
+            Err(e) => return Ok(build_error(req.id, JSON_RPC_INVALID_PARAMS, e.to_string())),
+        };
+
+        self.peer.set_session(session);
+
+        //
+        // the client gets the current list anyway
+        //
+        self.take_tools_changed();
+
+        let mut capabilities = json!({
+            "logging": {},
+            "tools": {
+                "listChanged": true
+            }
+        });
+
+        if let (Some(forwarder), Some(caps)) = (&self.forwarder, capabilities.as_object_mut()) {
+            caps.extend(forwarder.capabilities());
+            forwarder.session_started(ToolContext::new(None, None, self.peer.clone()));
+        }
+
+        let result = json!({
+            "protocolVersion": JSON_RPC_PROTOCOL_VERSION,
+            "capabilities": capabilities,
+            "serverInfo": {
+                "name": CLIENT_NAME,
+                "version": CLIENT_VERSION,
+            }
+        });
+
+        build_result(req.id, result)
+    }
+
+    fn handle_list_tools(&self, req: &JsonRPCMessage) -> Result<JsonRPCMessage> {
+        let tools: Vec<Value> = self.handle.tools().iter().map(tool_to_wire).collect();
+
+        build_result(req.id, json!({ "tools": tools }))
+    }
+
+    fn prepare_call(&self, req: &JsonRPCMessage) -> PreparedCall<E> {
+        let params = req.parameters.clone().unwrap_or_default();
+
+        let params: McpParams = match serde_json::to_value(params).and_then(serde_json::from_value) {
+            Ok(v) => v,
+            Err(e) => return PreparedCall::Ready(build_error(req.id, JSON_RPC_INVALID_PARAMS, e.to_string())),
+        };
+
+        let meta = request_meta(req);
+
+        if let Some(forwarder) = self.forwarder.as_ref().filter(|f| f.forwards_tool(&params.tool_name)) {
+            let ctx = ToolContext::new(req.id, meta, self.peer.clone());
+            let params = req.parameters.clone().map(|p| json!(p)).unwrap_or(Value::Null);
+
+            return PreparedCall::Forward(ctx, forwarder.clone(), "tools/call".into(), params);
+        }
+
+        match self.handle.handler(&params.tool_name) {
+            Some(handler) => {
+                let ctx = ToolContext::new(req.id, meta, self.peer.clone());
+                PreparedCall::Pending(ctx, handler, params)
+            }
+            None => {
+                let msg = format!("unknown tool {}", params.tool_name);
+                PreparedCall::Ready(build_error(req.id, JSON_RPC_INVALID_PARAMS, msg))
+            }
+        }
+    }
 
+    fn prepare_forward(&self, method: &str, req: &JsonRPCMessage) -> Option<PreparedCall<E>> {
+        let forwarder = self.forwarder.as_ref()?;
+
+        if SERVER_METHODS.contains(&method) {
+            return None;
+        }
+
+        let params = req.parameters.clone().map(|p| json!(p)).unwrap_or(Value::Null);
+        let ctx = ToolContext::new(req.id, request_meta(req), self.peer.clone());
+
+        Some(PreparedCall::Forward(
+            ctx,
+            forwarder.clone(),
+            method.to_string(),
+            params,
+        ))
+    }
+
+    async fn handle_call(&mut self, req: &JsonRPCMessage) -> Result<JsonRPCMessage> {
+        self.prepare_call(req).run().await.ok_or(Error::Cancelled)
+    }
+}
+
+//
+// there's neither stdio nor a socket in the browser
+//
+#[cfg(not(target_arch = "wasm32"))]
+impl<E> OmcpServer<E>
+where
+    E: std::fmt::Display + Send + 'static,
+{
     pub async fn io_loop(&mut self) -> Result<()> {
-        let mut stdin_reader = BufReader::new(io::stdin());
-        let mut stdout_writer = BufWriter::new(io::stdout());
+        self.serve(io::stdin(), io::stdout()).await
+    }
+
+    pub async fn serve<R, W>(&mut self, reader: R, writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        let transport = IoTransport::new(reader, writer).with_max_message_size(self.max_message_size);
+        self.serve_transport(transport).await
+    }
+
+    //
+    // the response goes through the outgoing queue so it can't overtake the
+    // tool's own notifications
+    //
+    fn spawn_call(&self, calls: &mut JoinSet<()>, call: PreparedCall<E>) {
+        let peer = self.peer.clone();
+
+        calls.spawn(async move {
+            if let Some(res) = call.run().await
+                && let Err(e) = peer.send(res)
+            {
+                error!("{e}");
+            }
+        });
+    }
+
+    //
+    // serve() for any Transport, returns once the client is gone
+    //
+    pub async fn serve_transport<T>(&mut self, mut transport: T) -> Result<()>
+    where
+        T: Transport,
+    {
+        let mut calls: JoinSet<()> = JoinSet::new();
+        let mut queued = VecDeque::new();
 
         loop {
             select! {
-               local = read_all(&mut stdin_reader) => {
-                   match local{
-                       Ok(msg) => {
-
-                           let req: JsonRPCMessage = match serde_json::from_str(&msg){
-                               Ok(v) => v,
-                               Err(e) => {
-                                   error!("{e}");
-                                   continue
-                               }
-                           };
-
-                           let res = match process_message(&req).await{
-                               Ok(v) => v,
-                               Err(e) => {
-                                   error!("{e}");
-                                   continue
-                               }
-                           };
-
-                           let res = match serde_json::to_string(&res){
-                               Ok(v) => v,
-                               Err(e) => {
-                                   error!("{e}");
-                                   continue
-                               }
-                           };
-
-                           stdout_writer.write_all(res.as_bytes()).await?;
-                       }
-                       Err(e) => {
-                           error!("{e}");
-                           break Err(e.into())
-                       }
-                   }
-               }
+                local = transport.recv() => {
+                    let req = match local {
+                        Ok(v) => v,
+                        Err(e) => match error_reply(&e) {
+                            //
+                            // the connection is still good, the answer goes
+                            // out like any other
+                            //
+                            Some(res) => {
+                                warn!("{e}");
+                                transport.send(res).await?;
+                                continue;
+                            }
+                            None => {
+                                error!("{e}");
+                                break Err(e)
+                            }
+                        },
+                    };
+
+                    //
+                    // tool calls run in the background, everything else is
+                    // answered right away
+                    //
+                    let prepared = match (req.id, req.method.as_deref()) {
+                        (Some(_), Some("tools/call")) => Some(self.prepare_call(&req)),
+                        (Some(_), Some(method)) => self.prepare_forward(method, &req),
+                        _ => None,
+                    };
+
+                    let res = match prepared {
+                        Some(PreparedCall::Ready(res)) => Some(res),
+                        //
+                        // past the limit calls wait for a slot, reading goes
+                        // on so responses to our own requests, pings and
+                        // cancellations still get through
+                        //
+                        Some(call) if calls.len() >= self.max_concurrent_calls => {
+                            queued.push_back(call);
+                            None
+                        }
+                        Some(call) => {
+                            self.spawn_call(&mut calls, call);
+                            None
+                        }
+                        None => match self.handle_message(&req).await {
+                            Ok(v) => v,
+                            Err(e) => {
+                                error!("{e}");
+                                Some(build_error(req.id, JSON_RPC_INTERNAL_ERROR, e.to_string()))
+                            }
+                        },
+                    };
+
+                    if let Some(res) = res {
+                        transport.send(res).await?;
+                    }
+
+                    for notification in self.take_notifications() {
+                        transport.send(notification).await?;
+                    }
+                }
+                Some(done) = calls.join_next() => {
+                    if let Err(e) = done {
+                        error!("{e}");
+                    }
+
+                    //
+                    // the ones cancelled while waiting are dropped
+                    //
+                    while let Some(call) = queued.pop_front() {
+                        if !call.is_cancelled() {
+                            self.spawn_call(&mut calls, call);
+                            break;
+                        }
+                    }
+                }
+                Some(msg) = self.outgoing.recv() => {
+                    transport.send(msg).await?;
+                }
+                Ok(()) = self.tools_version.changed() => {
+                    if self.initialized {
+                        transport.send(build_list_changed()).await?;
+                    }
+                }
             }
         }
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use serde_json::json;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+    use crate::{
+        error::{Error, Result},
+        json_rpc::{JSON_RPC_INVALID_REQUEST, JSON_RPC_PARSE_ERROR, JsonRPCMessage, JsonRPCMessageBuilder},
+        server::{context::ToolContext, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(params.tool_name.clone())
+        }
+    }
+
+    struct SleepTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for SleepTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            let ms = params.get_int("ms")?;
+            tokio::time::sleep(Duration::from_millis(ms as u64)).await;
+            Ok(format!("{ms}"))
+        }
+    }
+
+    //
+    // never returns, the flags are set once the call started and once it got
+    // dropped
+    //
+    struct HangTool {
+        started: Arc<AtomicBool>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for HangTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            let _flag = DropFlag(self.dropped.clone());
+            self.started.store(true, Ordering::SeqCst);
+            std::future::pending().await
+        }
+    }
+
+    struct RootsTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for RootsTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            ctx.progress(0.5, Some(1.0), Some("listing"))?;
+            let roots = ctx.list_roots().await?;
+            Ok(format!("{}", roots.len()))
+        }
+    }
+
+    async fn read_messages<R>(reader: &mut R, data: &mut Vec<u8>, count: usize) -> Vec<JsonRPCMessage>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            let mut stream = serde_json::Deserializer::from_slice(data).into_iter::<JsonRPCMessage>();
+            let messages: Vec<JsonRPCMessage> = stream.by_ref().take(count).filter_map(|m| m.ok()).collect();
+
+            if messages.len() == count {
+                let offset = stream.byte_offset();
+                data.drain(..offset);
+                break messages;
+            }
+
+            let len = reader.read(&mut buffer).await.unwrap();
+            data.extend(&buffer[..len]);
+        }
+    }
+
+    fn frame(msg: &JsonRPCMessage) -> String {
+        format!("{}\n", serde_json::to_string(msg).unwrap())
+    }
+
+    fn sleep_call(id: u64, ms: u64) -> String {
+        let mut params = McpParams::new("sleep");
+        params.add_argument("ms", ms.into());
+
+        let msg = JsonRPCMessageBuilder::new()
+            .with_id(id)
+            .with_method("tools/call")
+            .with_parameter((&params).try_into().unwrap())
+            .build();
+
+        frame(&msg)
+    }
+
+    fn request<S>(id: u64, method: S) -> JsonRPCMessage
+    where
+        S: AsRef<str>,
+    {
+        JsonRPCMessageBuilder::new().with_id(id).with_method(method).build()
+    }
+
+    fn notification<S>(method: S) -> JsonRPCMessage
+    where
+        S: AsRef<str>,
+    {
+        JsonRPCMessageBuilder::new().with_method(method).build()
+    }
+
+    #[tokio::test]
+    async fn list_changed_after_init() {
+        let mut server = OmcpServer::<Error>::new();
+
+        server.add_tool("echo", EchoTool {});
+        assert!(server.take_notifications().is_empty());
+
+        let res = server.handle_message(&request(1, "initialize")).await.unwrap().unwrap();
+        assert_eq!(res.id, Some(1));
+        assert!(server.take_notifications().is_empty());
+
+        //
+        // not before the client said it's ready
+        //
+        server.add_tool("echo1", EchoTool {});
+        assert!(server.take_notifications().is_empty());
+
+        server.handle_message(&notification("notifications/initialized")).await.unwrap();
+
+        server.add_tool("echo2", EchoTool {});
+        assert!(server.remove_tool("echo1"));
+        assert!(server.remove_tool("echo"));
+        assert!(!server.remove_tool("echo"));
+
+        let notifications = server.take_notifications();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(
+            notifications[0].method.as_deref(),
+            Some("notifications/tools/list_changed")
+        );
+
+        let res = server.handle_message(&request(2, "tools/list")).await.unwrap().unwrap();
+        let tools = res.result.unwrap().remove("tools").unwrap();
+        assert_eq!(tools[0]["name"], "echo2");
+    }
+
+    #[tokio::test]
+    async fn handle_disable_tool() {
+        let mut server = OmcpServer::<Error>::new();
+        let handle = server.handle();
+
+        server.handle_message(&request(1, "initialize")).await.unwrap();
+        server.handle_message(&notification("notifications/initialized")).await.unwrap();
+
+        handle.add_tool("echo", EchoTool {});
+        assert!(handle.disable_tool("echo"));
+        assert!(!handle.disable_tool("echo"));
+        assert_eq!(server.take_notifications().len(), 1);
+
+        let res = server.handle_message(&request(2, "tools/list")).await.unwrap().unwrap();
+        let tools = res.result.unwrap().remove("tools").unwrap();
+        assert_eq!(tools.as_array().unwrap().len(), 0);
+
+        let call = JsonRPCMessageBuilder::new()
+            .with_id(3)
+            .with_method("tools/call")
+            .with_parameter([("name".to_string(), "echo".into())].into())
+            .build();
+
+        let res = server.handle_message(&call).await.unwrap().unwrap();
+        assert!(res.error.is_some());
+
+        handle.enable_tool("echo");
+        let res = server.handle_message(&call).await.unwrap().unwrap();
+        assert!(res.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls() {
+        let (client, server_io) = tokio::io::duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("sleep", SleepTool {});
+
+        let task = tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        client_write.write_all(sleep_call(1, 500).as_bytes()).await.unwrap();
+        client_write.write_all(sleep_call(2, 1).as_bytes()).await.unwrap();
+
+        //
+        // the fast call overtakes the slow one
+        //
+        let mut ids = Vec::new();
+        let mut data = Vec::new();
+        let mut buffer = [0u8; 1024];
+
+        while ids.len() < 2 {
+            let len = client_read.read(&mut buffer).await.unwrap();
+            data.extend(&buffer[..len]);
+
+            ids = serde_json::Deserializer::from_slice(&data)
+                .into_iter::<JsonRPCMessage>()
+                .filter_map(|m| m.ok())
+                .filter_map(|m| m.id)
+                .collect();
+        }
+
+        assert_eq!(ids, vec![2, 1]);
+
+        drop(client_read);
+        drop(client_write);
+        assert!(task.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn calls_over_the_limit_wait() {
+        let (client, server_io) = tokio::io::duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+
+        let mut server = OmcpServer::<Error>::new().with_max_concurrent_calls(1);
+        server.add_tool("sleep", SleepTool {});
+
+        tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        client_write.write_all(sleep_call(1, 200).as_bytes()).await.unwrap();
+        client_write.write_all(sleep_call(2, 1).as_bytes()).await.unwrap();
+        client_write.write_all(frame(&request(3, "ping")).as_bytes()).await.unwrap();
+
+        //
+        // the ping still gets through, the second call runs after the first
+        //
+        let mut data = Vec::new();
+        let res = read_messages(&mut client_read, &mut data, 3).await;
+
+        let ids: Vec<_> = res.iter().filter_map(|m| m.id).collect();
+        assert_eq!(ids, vec![3, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn cancel_with_string_id() {
+        let (client, server_io) = tokio::io::duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+
+        let started = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        let tool = HangTool {
+            started: started.clone(),
+            dropped: dropped.clone(),
+        };
+
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("hang", tool);
+
+        tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        let call = JsonRPCMessageBuilder::new()
+            .with_id(1)
+            .with_method("tools/call")
+            .with_parameter((&McpParams::new("hang")).try_into().unwrap())
+            .build();
+
+        let cancel = JsonRPCMessageBuilder::new()
+            .with_method("notifications/cancelled")
+            .with_parameter(serde_json::from_value(json!({"requestId": "1"})).unwrap())
+            .build();
+
+        client_write.write_all(frame(&call).as_bytes()).await.unwrap();
+
+        while !started.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        client_write.write_all(frame(&cancel).as_bytes()).await.unwrap();
+        client_write.write_all(frame(&request(2, "ping")).as_bytes()).await.unwrap();
+
+        //
+        // nothing comes back for the cancelled call
+        //
+        let mut data = Vec::new();
+        let res = read_messages(&mut client_read, &mut data, 1).await;
+        assert_eq!(res[0].id, Some(2));
+
+        for _ in 0..100 {
+            if dropped.load(Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn parse_error() {
+        let (client, server_io) = tokio::io::duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+
+        let mut server = OmcpServer::<Error>::new().with_max_message_size(256);
+        server.add_tool("sleep", SleepTool {});
+
+        tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        let oversized = format!("{{\"pad\":\"{}\"}}\n", "x".repeat(512));
+
+        client_write.write_all(b"{not json\n").await.unwrap();
+        client_write
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":5}\n")
+            .await
+            .unwrap();
+        client_write.write_all(oversized.as_bytes()).await.unwrap();
+        client_write.write_all(sleep_call(1, 1).as_bytes()).await.unwrap();
+
+        //
+        // answered, and the connection keeps working
+        //
+        let mut data = Vec::new();
+        let res = read_messages(&mut client_read, &mut data, 4).await;
+
+        assert_eq!(res[0].id, None);
+        assert_eq!(res[0].error.as_ref().unwrap().code, JSON_RPC_PARSE_ERROR);
+        assert_eq!(res[1].id, Some(7));
+        assert_eq!(res[1].error.as_ref().unwrap().code, JSON_RPC_INVALID_REQUEST);
+        assert_eq!(res[2].id, None);
+        assert_eq!(res[2].error.as_ref().unwrap().code, JSON_RPC_INVALID_REQUEST);
+        assert_eq!(res[3].id, Some(1));
+    }
+
+    #[tokio::test]
+    async fn context_progress_and_roots() {
+        let (client, server_io) = tokio::io::duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("roots", RootsTool {});
+
+        let task = tokio::spawn(async move { server.serve(server_read, server_write).await });
+
+        let init = JsonRPCMessageBuilder::new()
+            .with_id(1)
+            .with_method("initialize")
+            .with_parameter(serde_json::from_value(json!({"capabilities": {"roots": {}}})).unwrap())
+            .build();
+
+        let call = JsonRPCMessageBuilder::new()
+            .with_id(2)
+            .with_method("tools/call")
+            .with_parameter(serde_json::from_value(json!({"name": "roots", "_meta": {"progressToken": 7}})).unwrap())
+            .build();
+
+        client_write.write_all(frame(&init).as_bytes()).await.unwrap();
+        client_write.write_all(frame(&call).as_bytes()).await.unwrap();
+
+        let mut data = Vec::new();
+        let messages = read_messages(&mut client_read, &mut data, 3).await;
+
+        assert_eq!(messages[0].id, Some(1));
+        assert_eq!(messages[1].method.as_deref(), Some("notifications/progress"));
+        assert_eq!(messages[1].parameters.as_ref().unwrap()["progressToken"], 7);
+        assert_eq!(messages[2].method.as_deref(), Some("roots/list"));
+
+        let roots = JsonRPCMessageBuilder::new()
+            .with_id(messages[2].id.unwrap())
+            .with_result(serde_json::from_value(json!({"roots": [{"uri": "file:///tmp"}]})).unwrap())
+            .build();
+
+        client_write.write_all(frame(&roots).as_bytes()).await.unwrap();
+
+        let messages = read_messages(&mut client_read, &mut data, 1).await;
+        assert_eq!(messages[0].id, Some(2));
+        assert_eq!(messages[0].result.as_ref().unwrap()["content"][0]["text"], "1");
+
+        drop(client_read);
+        drop(client_write);
+        assert!(task.await.unwrap().is_err());
+    }
+}
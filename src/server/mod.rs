@@ -1,3 +1,16 @@
+pub mod auth;
+pub mod context;
+pub mod forward;
+pub mod handle;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod http;
 pub mod matrix;
+pub mod session;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sse;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod stdio;
-pub mod types;
+#[cfg(unix)]
+pub mod unix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ws;
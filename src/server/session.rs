@@ -0,0 +1,406 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, Weak},
+    time::Instant,
+};
+use std::{sync::Arc, time::Duration};
+
+#[cfg(not(target_arch = "wasm32"))]
+use log::{info, warn};
+
+use crate::server::{
+    auth::Principal,
+    context::{PeerCredentials, SessionInfo},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    error::{Error, Result},
+    server::context::ServerPeer,
+};
+
+//
+// What the session hooks are told. The client only sends its initialize
+// request after the session started, so client is None in the start hook
+//
+#[derive(Debug, Clone)]
+pub struct SessionEvent {
+    pub id: String,
+    pub principal: Option<Principal>,
+    pub peer_credentials: Option<PeerCredentials>,
+    pub client: Option<SessionInfo>,
+}
+
+pub type SessionHook = Arc<dyn Fn(&SessionEvent) + Send + Sync>;
+
+//
+// limits and hooks shared by every network transport
+//
+#[derive(Clone, Default)]
+pub(crate) struct SessionConfig {
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) max_sessions: Option<usize>,
+    pub(crate) on_start: Option<SessionHook>,
+    pub(crate) on_end: Option<SessionHook>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct SessionEntry<T> {
+    value: T,
+    peer: Arc<ServerPeer>,
+    last_seen: Instant,
+    streams: usize,
+}
+
+//
+// Sessions of one transport by id. Each value owns the input side of its
+// OmcpServer, dropping it ends the session
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct SessionManager<T> {
+    sessions: Mutex<HashMap<String, SessionEntry<T>>>,
+    config: SessionConfig,
+}
+
+//
+// An open SSE stream of a session, the session doesn't go idle while it's
+// around
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct StreamGuard<T>
+where
+    T: Clone + Send + 'static,
+{
+    manager: Weak<SessionManager<T>>,
+    id: String,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(not(target_arch = "wasm32"))]
+async fn reaper<T>(manager: Weak<SessionManager<T>>, period: Duration)
+where
+    T: Clone + Send + 'static,
+{
+    loop {
+        tokio::time::sleep(period).await;
+
+        match manager.upgrade() {
+            Some(m) => m.expire(),
+            None => break,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn session_event(id: &str, peer: &ServerPeer) -> SessionEvent {
+    SessionEvent {
+        id: id.to_string(),
+        principal: peer.principal(),
+        peer_credentials: peer.peer_credentials(),
+        client: peer.session(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> SessionManager<T>
+where
+    T: Clone + Send + 'static,
+{
+    //
+    // idle sessions are also checked whenever a session is looked up, the
+    // background reaper only runs when there's a tokio runtime around
+    //
+    pub(crate) fn new(config: SessionConfig) -> Arc<Self> {
+        let manager = Arc::new(Self {
+            sessions: Mutex::new(HashMap::new()),
+            config,
+        });
+
+        if let (Some(timeout), Ok(rt)) = (manager.config.idle_timeout, tokio::runtime::Handle::try_current()) {
+            let period = (timeout / 2).max(Duration::from_millis(10));
+            rt.spawn(reaper(Arc::downgrade(&manager), period));
+        }
+
+        manager
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, SessionEntry<T>>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    //
+    // the peer of the session's OmcpServer, the hooks get the principal and
+    // the client's details from it
+    //
+    pub(crate) fn insert<S>(&self, id: S, value: T, peer: Arc<ServerPeer>) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.expire();
+
+        {
+            let mut sessions = self.lock();
+
+            if let Some(max) = self.config.max_sessions
+                && sessions.len() >= max
+            {
+                warn!("too many sessions ({max})");
+                return Err(Error::TooManySessions);
+            }
+
+            let entry = SessionEntry {
+                value,
+                peer: peer.clone(),
+                last_seen: Instant::now(),
+                streams: 0,
+            };
+
+            sessions.insert(id.as_ref().to_string(), entry);
+        }
+
+        info!("session {} started", id.as_ref());
+
+        if let Some(hook) = &self.config.on_start {
+            hook(&session_event(id.as_ref(), &peer));
+        }
+
+        Ok(())
+    }
+
+    //
+    // counts as activity
+    //
+    pub(crate) fn get<S>(&self, id: S) -> Option<T>
+    where
+        S: AsRef<str>,
+    {
+        self.expire();
+
+        let mut sessions = self.lock();
+
+        let entry = sessions.get_mut(id.as_ref())?;
+        entry.last_seen = Instant::now();
+
+        Some(entry.value.clone())
+    }
+
+    pub(crate) fn remove<S>(&self, id: S) -> Option<T>
+    where
+        S: AsRef<str>,
+    {
+        let entry = self.lock().remove(id.as_ref())?;
+
+        self.ended(id.as_ref(), &entry.peer);
+
+        Some(entry.value)
+    }
+
+    //
+    // counts as activity, the session can't expire until the guard is gone
+    //
+    pub(crate) fn open_stream<S>(self: &Arc<Self>, id: S) -> Option<StreamGuard<T>>
+    where
+        S: AsRef<str>,
+    {
+        let mut sessions = self.lock();
+
+        let entry = sessions.get_mut(id.as_ref())?;
+        entry.last_seen = Instant::now();
+        entry.streams += 1;
+
+        Some(StreamGuard {
+            manager: Arc::downgrade(self),
+            id: id.as_ref().to_string(),
+        })
+    }
+
+    fn touch(&self, id: &str, closed: bool) {
+        if let Some(entry) = self.lock().get_mut(id) {
+            entry.last_seen = Instant::now();
+
+            if closed {
+                entry.streams -= 1;
+            }
+        }
+    }
+
+    pub(crate) fn expire(&self) {
+        let timeout = match self.config.idle_timeout {
+            Some(v) => v,
+            None => return,
+        };
+
+        let expired: Vec<(String, Arc<ServerPeer>)> = {
+            let mut sessions = self.lock();
+
+            let expired: Vec<String> = sessions
+                .iter()
+                .filter(|(_, e)| e.streams == 0 && e.last_seen.elapsed() > timeout)
+                .map(|(id, _)| id.clone())
+                .collect();
+
+            expired
+                .into_iter()
+                .filter_map(|id| sessions.remove(&id).map(|e| (id, e.peer)))
+                .collect()
+        };
+
+        for (id, peer) in expired {
+            info!("session {id} expired");
+            self.ended(&id, &peer);
+        }
+    }
+
+    fn ended(&self, id: &str, peer: &ServerPeer) {
+        info!("session {id} ended");
+
+        if let Some(hook) = &self.config.on_end {
+            hook(&session_event(id, peer));
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> StreamGuard<T>
+where
+    T: Clone + Send + 'static,
+{
+    //
+    // something went out on the stream
+    //
+    pub(crate) fn touch(&self) {
+        if let Some(manager) = self.manager.upgrade() {
+            manager.touch(&self.id, false);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> Drop for StreamGuard<T>
+where
+    T: Clone + Send + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(manager) = self.manager.upgrade() {
+            manager.touch(&self.id, true);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use tokio::sync::mpsc;
+
+    use crate::{
+        error::Error,
+        server::{
+            auth::Principal,
+            context::ServerPeer,
+            session::{SessionConfig, SessionEvent, SessionManager},
+        },
+    };
+
+    fn peer(subject: &str) -> Arc<ServerPeer> {
+        let peer = ServerPeer::new(mpsc::unbounded_channel().0);
+        peer.set_principal(Some(Principal::new(subject)));
+        Arc::new(peer)
+    }
+
+    #[tokio::test]
+    async fn limits_and_hooks() {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let ended = Arc::new(AtomicU32::new(0));
+
+        let config = SessionConfig {
+            idle_timeout: Some(Duration::from_millis(50)),
+            max_sessions: Some(2),
+            on_start: Some({
+                let started = started.clone();
+                Arc::new(move |event: &SessionEvent| {
+                    let subject = event.principal.as_ref().map(|p| p.subject.clone());
+                    started.lock().unwrap().push((event.id.clone(), subject));
+                })
+            }),
+            on_end: Some({
+                let ended = ended.clone();
+                Arc::new(move |_: &SessionEvent| {
+                    ended.fetch_add(1, Ordering::SeqCst);
+                })
+            }),
+        };
+
+        let manager = SessionManager::new(config);
+
+        manager.insert("a", 1, peer("alice")).unwrap();
+        manager.insert("b", 2, peer("bob")).unwrap();
+        assert!(matches!(
+            manager.insert("c", 3, peer("carol")),
+            Err(Error::TooManySessions)
+        ));
+
+        assert_eq!(manager.remove("a"), Some(1));
+        assert_eq!(manager.get("b"), Some(2));
+        assert_eq!(ended.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            *started.lock().unwrap(),
+            vec![
+                ("a".to_string(), Some("alice".to_string())),
+                ("b".to_string(), Some("bob".to_string()))
+            ]
+        );
+
+        //
+        // the reaper gets rid of it without anybody asking
+        //
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(manager.len(), 0);
+        assert_eq!(ended.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn open_stream_keeps_it() {
+        let config = SessionConfig {
+            idle_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+
+        let manager = SessionManager::new(config);
+        manager.insert("a", 1, peer("alice")).unwrap();
+
+        let stream = manager.open_stream("a").unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(manager.get("a"), Some(1));
+
+        //
+        // idle again from the moment it's closed
+        //
+        drop(stream);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(manager.len(), 0);
+        assert!(manager.open_stream("a").is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use futures_util::{StreamExt, stream};
+use log::{debug, error, info};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    io::{self, AsyncWriteExt, DuplexStream, WriteHalf},
+    net::{TcpListener, ToSocketAddrs},
+};
+use uuid::Uuid;
+
+use crate::{
+    codec::LineReader,
+    error::{Error, Result},
+    server::{
+        auth::{SharedAuthenticator, authorize},
+        matrix::OmcpServer,
+        session::{SessionManager, StreamGuard},
+    },
+};
+
+const SESSION_BUFFER_SIZE: usize = 64 * 1024;
+
+type SessionMap = Arc<SessionManager<Arc<SseSession>>>;
+type SessionFactory<E> = Arc<dyn Fn() -> OmcpServer<E> + Send + Sync>;
+
+//
+// Legacy HTTP+SSE transport. Every GET /sse gets its own OmcpServer session,
+// POST /messages?sessionId= feeds it and the answers go out on the stream
+//
+struct SseState<E> {
+    new_session: SessionFactory<E>,
+    sessions: SessionMap,
+    auth: Option<SharedAuthenticator>,
+}
+
+//
+// the session only takes messages from whoever opened it
+//
+struct SseSession {
+    writer: tokio::sync::Mutex<WriteHalf<DuplexStream>>,
+    subject: Option<String>,
+}
+
+//
+// drops the session once the SSE stream goes away, it doesn't go idle before
+//
+struct SessionGuard {
+    id: String,
+    stream: Option<StreamGuard<Arc<SseSession>>>,
+    sessions: SessionMap,
+}
+
+#[derive(Deserialize)]
+struct SessionQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+async fn sse_get<E>(State(state): State<SseState<E>>, headers: HeaderMap) -> Response
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    let principal = match authorize(state.auth.as_ref(), &headers).await {
+        Ok(v) => v,
+        Err(res) => return res,
+    };
+
+    let id = Uuid::new_v4().simple().to_string();
+
+    let (client_io, server_io) = io::duplex(SESSION_BUFFER_SIZE);
+    let (server_read, server_write) = io::split(server_io);
+    let (client_read, client_write) = io::split(client_io);
+
+    let sse_session = SseSession {
+        writer: tokio::sync::Mutex::new(client_write),
+        subject: principal.as_ref().map(|p| p.subject.clone()),
+    };
+
+    let mut session = (state.new_session)();
+    session.set_session_id(&id);
+    session.set_principal(principal);
+
+    if let Err(e) = state.sessions.insert(&id, Arc::new(sse_session), session.peer()) {
+        return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response();
+    }
+
+    info!("new sse session {id}");
+
+    tokio::spawn(async move {
+        match session.serve(server_read, server_write).await {
+            Ok(()) | Err(Error::Eof) => {}
+            Err(e) => error!("{e}"),
+        }
+    });
+
+    let guard = SessionGuard {
+        id: id.clone(),
+        stream: state.sessions.open_stream(&id),
+        sessions: state.sessions.clone(),
+    };
+
+    //
+    // relative to the SSE url so the router can be nested
+    //
+    let endpoint = Event::default().event("endpoint").data(format!("messages?sessionId={id}"));
+
+    //
+    // the server never writes anything but our own JSON, no need for a limit
+    //
+    let reader = LineReader::new(client_read).with_max_line_length(usize::MAX);
+
+    let messages = stream::unfold((reader, guard), |(mut reader, guard)| async move {
+        match reader.read_line().await {
+            Ok(line) => {
+                if let Some(stream) = &guard.stream {
+                    stream.touch();
+                }
+
+                let event = Event::default().event("message").data(line);
+                Some((Ok::<_, Infallible>(event), (reader, guard)))
+            }
+            Err(e) => {
+                debug!("session {} done: {e}", guard.id);
+                None
+            }
+        }
+    });
+
+    let events = stream::once(async { Ok(endpoint) }).chain(messages);
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn sse_post<E>(
+    State(state): State<SseState<E>>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let principal = match authorize(state.auth.as_ref(), &headers).await {
+        Ok(v) => v,
+        Err(res) => return res,
+    };
+
+    let session = match state.sessions.get(&query.session_id) {
+        Some(v) => v,
+        None => return (StatusCode::NOT_FOUND, "unknown session").into_response(),
+    };
+
+    if principal.map(|p| p.subject) != session.subject {
+        return (StatusCode::FORBIDDEN, "session belongs to someone else").into_response();
+    }
+
+    //
+    // the session reads one message per line
+    //
+    let mut line = match serde_json::from_str::<Value>(&body) {
+        Ok(v) => v.to_string(),
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    line.push('\n');
+
+    match session.writer.lock().await.write_all(line.as_bytes()).await {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) => (StatusCode::GONE, e.to_string()).into_response(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl<E> Clone for SseState<E> {
+    fn clone(&self) -> Self {
+        Self {
+            new_session: self.new_session.clone(),
+            sessions: self.sessions.clone(),
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        info!("closing sse session {}", self.id);
+        self.sessions.remove(&self.id);
+    }
+}
+
+impl<E> OmcpServer<E>
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    //
+    // GET /sse and POST /messages, can be nested in a bigger app
+    //
+    pub fn sse_router(&self) -> Router {
+        let state = SseState {
+            new_session: Arc::new(self.session_factory()),
+            sessions: SessionManager::new(self.session_config()),
+            auth: self.authenticator(),
+        };
+
+        Router::new()
+            .route("/sse", get(sse_get::<E>))
+            .route("/messages", post(sse_post::<E>))
+            .with_state(state)
+    }
+
+    pub async fn sse_loop<A>(&self, addr: A) -> Result<()>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr).await?;
+        self.sse_serve(listener).await
+    }
+
+    pub async fn sse_serve(&self, listener: TcpListener) -> Result<()> {
+        info!("listening on {}", listener.local_addr()?);
+        axum::serve(listener, self.sse_router()).await?;
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::net::TcpListener;
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct HelloTool {}
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    impl BakedMcpToolTrait for HelloTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("hello {}", params.get_string("name")?))
+        }
+    }
+
+    #[tokio::test]
+    async fn sse_client_roundtrip() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("hello", HelloTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let mut client = OMcpClientBuilder::new(OMcpServerType::Sse)
+            .with_sse_url(format!("http://{addr}/sse"))
+            .build()
+            .unwrap();
+
+        client.connect().await.unwrap();
+
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "hello");
+
+        let mut params = McpParams::new("hello");
+        params.add_argument("name", "world".into());
+
+        let res = client.call(&params).await.unwrap();
+        assert!(res.contains("hello world"));
+
+        client.disconnect().await.unwrap();
+    }
+}
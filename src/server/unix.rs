@@ -0,0 +1,363 @@
+use std::{
+    fs::{self, DirBuilder, Permissions},
+    io::ErrorKind,
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+        net,
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use tokio::net::{UnixListener, UnixStream};
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, Result},
+    server::{context::PeerCredentials, matrix::OmcpServer, session::SessionManager},
+};
+
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+//
+// Who may connect. The socket file permissions are checked by the kernel,
+// the uid / gid lists against the peer credentials of every connection. Empty
+// lists let anyone who can open the socket in
+//
+#[derive(Debug, Clone, Default)]
+pub struct UnixServerOptions {
+    pub mode: Option<u32>,
+    pub allowed_uids: Vec<u32>,
+    pub allowed_gids: Vec<u32>,
+}
+
+//
+// drops the session once the connection is gone
+//
+struct SessionGuard {
+    id: String,
+    sessions: Arc<SessionManager<()>>,
+}
+
+//
+// the socket unix_loop() created, removed once it stops listening
+//
+struct SocketFile {
+    path: PathBuf,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn peer_credentials(stream: &UnixStream) -> Result<PeerCredentials> {
+    let cred = stream.peer_cred()?;
+
+    Ok(PeerCredentials {
+        uid: cred.uid(),
+        gid: cred.gid(),
+        pid: cred.pid(),
+    })
+}
+
+//
+// a socket left behind by a previous run is replaced, one somebody still
+// listens on and anything else at that path are left alone and bind() fails
+//
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.file_type().is_socket() => {}
+        Ok(_) => return Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    match net::UnixStream::connect(path) {
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            warn!("removing stale socket {}", path.display());
+            fs::remove_file(path)?;
+        }
+        Ok(_) => warn!("{} is in use", path.display()),
+        Err(e) => warn!("{}: {e}", path.display()),
+    }
+
+    Ok(())
+}
+
+//
+// Bound inside a directory only we can enter and linked in place once the
+// permissions are right, nobody else gets to connect in between. Unlike
+// rename() a link fails when something is already at the path, a live
+// socket or any other file is never replaced
+//
+fn bind_with_mode(path: &Path, mode: u32) -> Result<UnixListener> {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let dir = path.with_file_name(format!(".{name}.{}", std::process::id()));
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let tmp = dir.join(name.as_ref());
+
+    let res = UnixListener::bind(&tmp)
+        .and_then(|listener| {
+            fs::set_permissions(&tmp, Permissions::from_mode(mode))?;
+            fs::hard_link(&tmp, path)?;
+            Ok(listener)
+        })
+        .map_err(Error::from);
+
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        warn!("{}: {e}", dir.display());
+    }
+
+    res
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl UnixServerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //
+    // e.g. 0o660 to only let the owner and its group in, applied by
+    // unix_loop() before the socket shows up at its path
+    //
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn with_allowed_uid(mut self, uid: u32) -> Self {
+        self.allowed_uids.push(uid);
+        self
+    }
+
+    pub fn with_allowed_gid(mut self, gid: u32) -> Self {
+        self.allowed_gids.push(gid);
+        self
+    }
+
+    fn allows(&self, peer: &PeerCredentials) -> bool {
+        if self.allowed_uids.is_empty() && self.allowed_gids.is_empty() {
+            return true;
+        }
+
+        self.allowed_uids.contains(&peer.uid) || self.allowed_gids.contains(&peer.gid)
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        info!("closing unix session {}", self.id);
+        self.sessions.remove(&self.id);
+    }
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("{}: {e}", self.path.display());
+        }
+    }
+}
+
+impl<E> OmcpServer<E>
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    //
+    // the socket file is removed when the loop ends or its future is dropped
+    //
+    pub async fn unix_loop<P>(&self, path: P, options: UnixServerOptions) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        remove_stale_socket(path)?;
+
+        let listener = match options.mode {
+            Some(mode) => bind_with_mode(path, mode)?,
+            None => UnixListener::bind(path)?,
+        };
+
+        let _socket = SocketFile { path: path.into() };
+
+        self.unix_serve(listener, options).await
+    }
+
+    //
+    // newline delimited JSON, every connection is a session of its own.
+    // Idle timeouts don't apply, the session ends with the connection
+    //
+    pub async fn unix_serve(&self, listener: UnixListener, options: UnixServerOptions) -> Result<()> {
+        info!("listening on {:?}", listener.local_addr()?);
+
+        let new_session = self.session_factory();
+
+        let mut config = self.session_config();
+        config.idle_timeout = None;
+
+        let sessions = SessionManager::new(config);
+
+        loop {
+            //
+            // e.g. out of file descriptors, the ones in use are given a
+            // moment to go away
+            //
+            let stream = match listener.accept().await {
+                Ok((v, _)) => v,
+                Err(e) => {
+                    error!("{e}");
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            };
+
+            let peer = match peer_credentials(&stream) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("{e}");
+                    continue;
+                }
+            };
+
+            if !options.allows(&peer) {
+                warn!("rejecting uid {} gid {}", peer.uid, peer.gid);
+                continue;
+            }
+
+            let id = Uuid::new_v4().simple().to_string();
+
+            let mut session = new_session();
+            session.set_session_id(&id);
+            session.set_peer_credentials(Some(peer));
+
+            if let Err(e) = sessions.insert(&id, (), session.peer()) {
+                error!("{e}");
+                continue;
+            }
+
+            let guard = SessionGuard {
+                id,
+                sessions: sessions.clone(),
+            };
+
+            tokio::spawn(async move {
+                info!("new unix session {} (uid {} pid {:?})", guard.id, peer.uid, peer.pid);
+
+                let (reader, writer) = stream.into_split();
+
+                match session.serve(reader, writer).await {
+                    Ok(()) | Err(Error::Eof) => {}
+                    Err(e) => error!("{e}"),
+                }
+
+                drop(guard);
+            });
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::net::{UnixListener, UnixStream},
+        time::Duration,
+    };
+
+    use crate::{
+        error::Error,
+        server::{matrix::OmcpServer, unix::UnixServerOptions},
+    };
+
+    #[tokio::test]
+    async fn socket_file() {
+        let dir = std::env::temp_dir().join(format!("omcp-unix-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("mcp.sock");
+
+        //
+        // somebody is listening, the socket isn't stale
+        //
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = OmcpServer::<Error>::new();
+        assert!(server.unix_loop(&path, UnixServerOptions::new()).await.is_err());
+
+        //
+        // nobody is, it gets replaced
+        //
+        drop(listener);
+
+        let a = path.clone();
+        let task = tokio::spawn(async move { server.unix_loop(a, UnixServerOptions::new().with_mode(0o600)).await });
+
+        for _ in 0..100 {
+            if UnixStream::connect(&path).is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(UnixStream::connect(&path).is_ok());
+
+        task.abort();
+        let _ = task.await;
+
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mode_keeps_existing() {
+        let dir = std::env::temp_dir().join(format!("omcp-unix-mode-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("mcp.sock");
+        let options = || UnixServerOptions::new().with_mode(0o600);
+
+        let a = path.clone();
+        let live = tokio::spawn(async move { OmcpServer::<Error>::new().unix_loop(a, options()).await });
+
+        for _ in 0..100 {
+            if UnixStream::connect(&path).is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        //
+        // the second one gives up, the first is still reachable
+        //
+        let server = OmcpServer::<Error>::new();
+        assert!(server.unix_loop(&path, options()).await.is_err());
+        assert!(UnixStream::connect(&path).is_ok());
+
+        live.abort();
+        let _ = live.await;
+
+        //
+        // same for a plain file
+        //
+        std::fs::write(&path, "data").unwrap();
+        assert!(server.unix_loop(&path, options()).await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
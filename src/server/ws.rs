@@ -0,0 +1,261 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use axum::{
+    Router,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+    server::{
+        auth::{SharedAuthenticator, authorize},
+        matrix::OmcpServer,
+        session::SessionManager,
+    },
+    transport::{
+        Transport,
+        ws::{DEFAULT_PING_INTERVAL, Keepalive, MCP_SUBPROTOCOL},
+    },
+};
+
+const DEFAULT_PATH: &str = "/ws";
+
+type SessionFactory<E> = Arc<dyn Fn() -> OmcpServer<E> + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct WsServerOptions {
+    pub path: String,
+    pub ping_interval: Duration,
+}
+
+//
+// Every upgraded socket is an OmcpServer session of its own, gone with the
+// socket
+//
+struct WsState<E> {
+    new_session: SessionFactory<E>,
+    sessions: Arc<SessionManager<()>>,
+    auth: Option<SharedAuthenticator>,
+    options: WsServerOptions,
+}
+
+//
+// the server end of one socket
+//
+struct WsSocket {
+    socket: WebSocket,
+    keepalive: Keepalive,
+}
+
+//
+// drops the session once the socket is gone, or when the upgrade never
+// happened
+//
+struct SessionGuard {
+    id: String,
+    sessions: Arc<SessionManager<()>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+async fn ws_get<E>(State(state): State<WsState<E>>, headers: HeaderMap, upgrade: WebSocketUpgrade) -> Response
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    let principal = match authorize(state.auth.as_ref(), &headers).await {
+        Ok(v) => v,
+        Err(res) => return res,
+    };
+
+    let upgrade = upgrade.protocols([MCP_SUBPROTOCOL]);
+
+    if upgrade.selected_protocol().is_none() {
+        warn!("rejecting websocket without the {MCP_SUBPROTOCOL} subprotocol");
+        return (StatusCode::BAD_REQUEST, "mcp subprotocol required").into_response();
+    }
+
+    let id = Uuid::new_v4().simple().to_string();
+
+    let mut session = (state.new_session)();
+    session.set_session_id(&id);
+    session.set_principal(principal);
+
+    if let Err(e) = state.sessions.insert(&id, (), session.peer()) {
+        return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response();
+    }
+
+    let guard = SessionGuard {
+        id,
+        sessions: state.sessions.clone(),
+    };
+
+    let ping_interval = state.options.ping_interval;
+
+    upgrade.on_upgrade(move |socket| async move {
+        info!("new websocket session {}", guard.id);
+
+        match session.serve_transport(WsSocket::new(socket, ping_interval)).await {
+            Ok(()) | Err(Error::Eof) => {}
+            Err(e) => error!("{e}"),
+        }
+
+        drop(guard);
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Default for WsServerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WsServerOptions {
+    pub fn new() -> Self {
+        Self {
+            path: DEFAULT_PATH.into(),
+            ping_interval: DEFAULT_PING_INTERVAL,
+        }
+    }
+
+    pub fn with_path<S>(mut self, path: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.path = path.as_ref().into();
+        self
+    }
+
+    //
+    // clients that don't answer a ping before the next one are dropped, zero
+    // disables it
+    //
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+}
+
+impl<E> Clone for WsState<E> {
+    fn clone(&self) -> Self {
+        Self {
+            new_session: self.new_session.clone(),
+            sessions: self.sessions.clone(),
+            auth: self.auth.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        info!("closing websocket session {}", self.id);
+        self.sessions.remove(&self.id);
+    }
+}
+
+impl WsSocket {
+    fn new(socket: WebSocket, ping_interval: Duration) -> Self {
+        Self {
+            socket,
+            keepalive: Keepalive::new(ping_interval),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WsSocket {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        let text = serde_json::to_string(&msg)?;
+        self.socket.send(Message::Text(text.into())).await?;
+        Ok(())
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        loop {
+            tokio::select! {
+                frame = self.socket.next() => {
+                    self.keepalive.seen();
+
+                    match frame {
+                        Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                            Ok(msg) => return Ok(msg),
+                            Err(e) => warn!("{e}"),
+                        },
+                        Some(Ok(Message::Binary(_))) => warn!("ignoring binary frame"),
+                        Some(Ok(Message::Close(_))) | None => return Err(Error::Eof),
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            debug!("{e}");
+                            return Err(Error::Eof);
+                        }
+                    }
+                }
+                res = self.keepalive.tick() => {
+                    res?;
+                    self.socket.send(Message::Ping(Default::default())).await?;
+                }
+            }
+        }
+    }
+    async fn close(&mut self) -> Result<()> {
+        if let Err(e) = self.socket.send(Message::Close(None)).await {
+            debug!("{e}");
+        }
+
+        Ok(())
+    }
+}
+
+impl<E> OmcpServer<E>
+where
+    E: std::fmt::Display + Send + 'static,
+{
+    //
+    // GET on options.path upgraded to a WebSocket, can be nested in a bigger
+    // app. Idle timeouts don't apply, dead peers are caught by the pings
+    //
+    pub fn ws_router(&self, options: WsServerOptions) -> Router {
+        let path = options.path.clone();
+
+        let mut config = self.session_config();
+        config.idle_timeout = None;
+
+        let state = WsState {
+            new_session: Arc::new(self.session_factory()),
+            sessions: SessionManager::new(config),
+            auth: self.authenticator(),
+            options,
+        };
+
+        Router::new().route(&path, get(ws_get::<E>)).with_state(state)
+    }
+
+    pub async fn ws_loop<A>(&self, addr: A, options: WsServerOptions) -> Result<()>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr).await?;
+        self.ws_serve(listener, options).await
+    }
+
+    pub async fn ws_serve(&self, listener: TcpListener, options: WsServerOptions) -> Result<()> {
+        info!("listening on {}", listener.local_addr()?);
+        axum::serve(listener, self.ws_router(options)).await?;
+        Ok(())
+    }
+}
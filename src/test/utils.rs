@@ -1,3 +1,28 @@
 pub struct TestHttpServer {}
 
 impl TestHttpServer {}
+
+//
+// a CA and a 127.0.0.1 / localhost certificate it signed, valid until 2126
+//
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) mod tls {
+    use tokio::net::TcpListener;
+    use tokio_native_tls::{
+        TlsAcceptor,
+        native_tls::{self, Identity},
+    };
+
+    pub(crate) const CA_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/test/certs/ca.pem");
+
+    const SERVER_CERT: &[u8] = include_bytes!("certs/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("certs/server.key");
+
+    pub(crate) async fn tls_listener() -> (TcpListener, TlsAcceptor) {
+        let identity = Identity::from_pkcs8(SERVER_CERT, SERVER_KEY).unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        (listener, acceptor.into())
+    }
+}
@@ -0,0 +1,127 @@
+use std::{process::Stdio, time::Duration};
+
+use async_trait::async_trait;
+use log::{error, warn};
+use tokio::{
+    process::{Child, ChildStdin, ChildStdout, Command},
+    time::timeout,
+};
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+    transport::{Transport, io::IoTransport},
+};
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+//
+// Newline delimited JSON over the stdin / stdout of a child process. Nothing
+// restarts it, StdioServer does that on top of its own pipes
+//
+pub struct ChildTransport {
+    child: Child,
+    io: IoTransport<ChildStdout, ChildStdin>,
+    shutdown_timeout: Duration,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl ChildTransport {
+    //
+    // stdin and stdout are taken over, stderr is left the way the command has
+    // it
+    //
+    pub fn spawn(mut command: Command) -> Result<Self> {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped()).kill_on_drop(true);
+
+        let mut child = command.spawn()?;
+
+        let stdin = child.stdin.take().ok_or(Error::NotConnected)?;
+        let stdout = child.stdout.take().ok_or(Error::NotConnected)?;
+
+        Ok(Self {
+            child,
+            io: IoTransport::new(stdout, stdin),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        })
+    }
+
+    //
+    // how long close() waits for the child once stdin is closed
+    //
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+}
+
+#[async_trait]
+impl Transport for ChildTransport {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        self.io.send(msg).await
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        self.io.recv().await
+    }
+    async fn close(&mut self) -> Result<()> {
+        if let Err(e) = self.io.close().await {
+            warn!("{e}");
+        }
+
+        if timeout(self.shutdown_timeout, self.child.wait()).await.is_ok() {
+            return Ok(());
+        }
+
+        warn!("killing {:?}", self.child.id());
+
+        if let Err(e) = self.child.kill().await {
+            error!("{e}");
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::process::Command;
+
+    use crate::{
+        error::Error,
+        transport::{child::ChildTransport, session::McpSession},
+    };
+
+    //
+    // answers initialize and tools/list, then goes away
+    //
+    const SCRIPT: &str = r#"
+        read l; echo '{"jsonrpc":"2.0","id":1,"result":{"capabilities":{}}}'
+        read l; read l
+        echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"sh"}]}}'
+    "#;
+
+    #[tokio::test]
+    async fn session_over_child() {
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(SCRIPT);
+
+        let transport = ChildTransport::spawn(command).unwrap();
+        assert!(transport.id().is_some());
+
+        let session = McpSession::new(transport);
+        session.initialize().await.unwrap();
+        assert_eq!(session.list_tools().await.unwrap()[0].name, "sh");
+
+        let res = session.list_tools().await;
+        assert!(matches!(res, Err(Error::NotConnected)));
+    }
+}
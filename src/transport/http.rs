@@ -0,0 +1,536 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use log::{debug, error};
+use reqwest::{
+    Client, RequestBuilder, Response, StatusCode,
+    header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, WWW_AUTHENTICATE},
+};
+use serde_json::Value;
+use tokio::{
+    sync::mpsc,
+    task::{AbortHandle, JoinSet},
+};
+
+use crate::{
+    client::{
+        builder::OMcpClientBuilder,
+        credentials::CredentialProvider,
+        sse::SseReader,
+        types::{OMcpServerType, SseEvent},
+    },
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+    server::http::MCP_SESSION_ID,
+    transport::Transport,
+};
+
+const ACCEPT_ANY: &str = "application/json, text/event-stream";
+
+type Incoming = mpsc::UnboundedSender<Result<JsonRPCMessage>>;
+
+//
+// What every new StreamableHttpTransport starts from, HttpClient keeps one
+// around to reconnect
+//
+#[derive(Clone)]
+pub(crate) struct HttpConnector {
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+}
+
+//
+// Client side of Streamable HTTP. Every message is a POST, the answers come
+// back either as a JSON body or as an SSE stream. Requests are posted in the
+// background so a slow answer doesn't hold up the next message, a request
+// whose answer never comes fails on its own. A GET stream picks up what the
+// server sends on its own once there's a session
+//
+pub struct StreamableHttpTransport {
+    shared: Arc<HttpShared>,
+    rx: mpsc::UnboundedReceiver<Result<JsonRPCMessage>>,
+    tasks: JoinSet<()>,
+}
+
+//
+// what the transport and the POSTs it has in flight share
+//
+struct HttpShared {
+    connector: HttpConnector,
+    session_id: Mutex<Option<HeaderValue>>,
+    stream: Mutex<Option<AbortHandle>>,
+    tx: Incoming,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn content_type(response: &Response) -> &str {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+//
+// the id the answer will carry, None for notifications and responses
+//
+fn request_id(msg: &JsonRPCMessage) -> Option<u64> {
+    match (msg.id, msg.method.is_some()) {
+        (Some(id), true) => Some(id),
+        _ => None,
+    }
+}
+
+fn answers(msg: &JsonRPCMessage, request: Option<u64>) -> bool {
+    request.is_some() && msg.id == request && msg.method.is_none()
+}
+
+//
+// a single message or a batch
+//
+fn parse_body(body: &str) -> Result<Vec<JsonRPCMessage>> {
+    let messages = match serde_json::from_str(body)? {
+        Value::Array(v) => v.into_iter().map(serde_json::from_value).collect::<serde_json::Result<_>>()?,
+        v => vec![serde_json::from_value(v)?],
+    };
+
+    Ok(messages)
+}
+
+//
+// Ok once request is answered, or the body is read when there's nothing to
+// wait for
+//
+async fn read_json(response: Response, tx: &Incoming, request: Option<u64>) -> Result<()> {
+    let mut answered = request.is_none();
+
+    for msg in parse_body(&response.text().await?)? {
+        answered |= answers(&msg, request);
+        let _ = tx.send(Ok(msg));
+    }
+
+    match answered {
+        true => Ok(()),
+        false => Err(Error::Eof),
+    }
+}
+
+async fn read_events(mut reader: SseReader, tx: &Incoming, request: Option<u64>) -> Result<()> {
+    let mut answered = request.is_none();
+
+    loop {
+        match reader.next_event().await {
+            Ok(SseEvent::JsonRpcMessage(msg)) => {
+                answered |= answers(&msg, request);
+
+                if tx.send(Ok(*msg)).is_err() {
+                    return Ok(());
+                }
+            }
+            Ok(SseEvent::Endpoint(_)) => {}
+            Err(Error::Eof) if answered => return Ok(()),
+            Err(e) if answered => {
+                debug!("{e}");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl HttpConnector {
+    pub(crate) fn from_builder(mut builder: OMcpClientBuilder) -> Result<Self> {
+        let client = builder.http_client()?;
+        let credentials = builder.credential_provider(&client);
+
+        Ok(Self {
+            client,
+            url: builder.url,
+            headers: builder.headers,
+            credentials,
+        })
+    }
+
+    pub(crate) fn transport(&self) -> StreamableHttpTransport {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let shared = HttpShared {
+            connector: self.clone(),
+            session_id: Mutex::new(None),
+            stream: Mutex::new(None),
+            tx,
+        };
+
+        StreamableHttpTransport {
+            shared: Arc::new(shared),
+            rx,
+            tasks: JoinSet::new(),
+        }
+    }
+}
+
+impl HttpShared {
+    async fn request_headers(&self) -> Result<HeaderMap> {
+        let mut headers = self.connector.headers.clone();
+
+        if let Some(credentials) = &self.connector.credentials {
+            headers.extend(credentials.headers().await?);
+        }
+
+        if let Some(id) = lock(&self.session_id).clone() {
+            headers.insert(MCP_SESSION_ID, id);
+        }
+
+        Ok(headers)
+    }
+
+    //
+    // same deal as SseClient, one retry after refreshing the credentials
+    //
+    async fn send_request<F>(&self, request: F) -> Result<Response>
+    where
+        F: Fn(HeaderMap) -> RequestBuilder,
+    {
+        let res = request(self.request_headers().await?).send().await?;
+
+        let credentials = match (&self.connector.credentials, res.status()) {
+            (Some(c), StatusCode::UNAUTHORIZED) => c,
+            _ => return Ok(res),
+        };
+
+        let challenge = res
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+
+        if !credentials.refresh(challenge.as_deref()).await? {
+            return Ok(res);
+        }
+
+        Ok(request(self.request_headers().await?).send().await?)
+    }
+
+    //
+    // servers without a GET stream answer 405, that's fine
+    //
+    async fn open_stream(&self) -> Result<()> {
+        let connector = &self.connector;
+
+        let response = self
+            .send_request(|headers| {
+                connector
+                    .client
+                    .get(&connector.url)
+                    .headers(headers)
+                    .header(ACCEPT, "text/event-stream")
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            debug!("{} has no stream ({})", connector.url, response.status());
+            return Ok(());
+        }
+
+        let reader = SseReader::new(&connector.url, response);
+        let tx = self.tx.clone();
+
+        let task = tokio::spawn(async move {
+            if let Err(e) = read_events(reader, &tx, None).await {
+                debug!("{e}");
+            }
+        });
+
+        *lock(&self.stream) = Some(task.abort_handle());
+        Ok(())
+    }
+
+    //
+    // the response headers, the body is left to read_response()
+    //
+    async fn post(&self, msg: &JsonRPCMessage) -> Result<Response> {
+        let connector = &self.connector;
+        let body = serde_json::to_string(msg)?;
+
+        debug!("sending: {body}");
+
+        let response = self
+            .send_request(|headers| {
+                connector
+                    .client
+                    .post(&connector.url)
+                    .headers(headers)
+                    .header(ACCEPT, ACCEPT_ANY)
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            error!("{} returned {}", connector.url, response.status());
+            return Err(Error::ConnectionFailure);
+        }
+
+        let new_session = match response.headers().get(MCP_SESSION_ID) {
+            Some(id) => {
+                let mut session_id = lock(&self.session_id);
+                let new_session = session_id.is_none();
+                session_id.get_or_insert_with(|| id.clone());
+                new_session
+            }
+            None => false,
+        };
+
+        if new_session {
+            self.open_stream().await?;
+        }
+
+        Ok(response)
+    }
+
+    //
+    // 202 for notifications and responses, nothing to read. A request is
+    // answered in the body
+    //
+    async fn read_response(&self, response: Response, request: Option<u64>) -> Result<()> {
+        let content_type = content_type(&response).to_string();
+
+        if content_type.starts_with("text/event-stream") {
+            let reader = SseReader::new(&self.connector.url, response);
+            read_events(reader, &self.tx, request).await
+        } else if content_type.starts_with("application/json") {
+            read_json(response, &self.tx, request).await
+        } else {
+            match request {
+                Some(_) => Err(Error::Eof),
+                None => Ok(()),
+            }
+        }
+    }
+
+    //
+    // the request is failed rather than left waiting when anything goes
+    // wrong
+    //
+    async fn request(&self, msg: JsonRPCMessage, id: u64) {
+        let res = match self.post(&msg).await {
+            Ok(response) => self.read_response(response, Some(id)).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = res {
+            let _ = self.tx.send(Err(Error::RequestFailed { id, error: Box::new(e) }));
+        }
+    }
+
+    fn close_stream(&self) {
+        if let Some(stream) = lock(&self.stream).take() {
+            stream.abort();
+        }
+    }
+}
+
+impl StreamableHttpTransport {
+    //
+    // url, headers, credentials, TLS and proxy settings come from the builder,
+    // nothing is sent until the first message
+    //
+    pub fn from_builder(builder: OMcpClientBuilder) -> Result<Self> {
+        Ok(HttpConnector::from_builder(builder)?.transport())
+    }
+
+    pub fn new<S>(url: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        Self::from_builder(OMcpClientBuilder::new(OMcpServerType::Http).with_http_url(url))
+    }
+}
+
+impl Drop for StreamableHttpTransport {
+    fn drop(&mut self) {
+        self.tasks.abort_all();
+        self.shared.close_stream();
+    }
+}
+
+#[async_trait]
+impl Transport for StreamableHttpTransport {
+    //
+    // requests are queued and posted in the background. Notifications and
+    // responses go out right away, the server answers them with a 202, so
+    // e.g. notifications/initialized is there before the next request
+    //
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        while self.tasks.try_join_next().is_some() {}
+
+        let shared = self.shared.clone();
+
+        if let Some(id) = request_id(&msg) {
+            self.tasks.spawn(async move { shared.request(msg, id).await });
+            return Ok(());
+        }
+
+        let response = self.shared.post(&msg).await?;
+
+        self.tasks.spawn(async move {
+            if let Err(e) = shared.read_response(response, None).await {
+                debug!("{e}");
+            }
+        });
+
+        Ok(())
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        self.rx.recv().await.ok_or(Error::Eof)?
+    }
+    async fn close(&mut self) -> Result<()> {
+        self.tasks.abort_all();
+        self.shared.close_stream();
+
+        if lock(&self.shared.session_id).is_none() {
+            return Ok(());
+        }
+
+        let connector = &self.shared.connector;
+
+        let res = self
+            .shared
+            .send_request(|headers| connector.client.delete(&connector.url).headers(headers))
+            .await;
+
+        *lock(&self.shared.session_id) = None;
+
+        if let Err(e) = res {
+            debug!("{e}");
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time::timeout,
+    };
+
+    use crate::{
+        error::{Error, Result},
+        json_rpc::JsonRPCMessageBuilder,
+        server::{context::ToolContext, http::HttpServerOptions, matrix::OmcpServer},
+        transport::{Transport, http::StreamableHttpTransport, session::McpSession},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct ProgressTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for ProgressTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            ctx.notify(
+                "notifications/message",
+                serde_json::json!({"level": "info", "data": "working"}),
+            )?;
+            Ok("done".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn session_over_http() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("progress", ProgressTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.http_serve(listener, HttpServerOptions::new()).await });
+
+        let transport = StreamableHttpTransport::new(format!("http://{addr}/mcp")).unwrap();
+        let (session, mut incoming) = McpSession::with_incoming(transport);
+
+        session.initialize().await.unwrap();
+        assert_eq!(session.list_tools().await.unwrap()[0].name, "progress");
+
+        let res = session.call_tool(&McpParams::new("progress")).await.unwrap();
+        assert!(res.contains("done"));
+
+        //
+        // the notification came in on the request's stream, ahead of the result
+        //
+        let msg = incoming.try_recv().unwrap();
+        assert_eq!(msg.method.as_deref(), Some("notifications/message"));
+
+        session.close();
+    }
+
+    //
+    // "slow" is never answered, anything else gets an event stream that ends
+    // right away
+    //
+    #[tokio::test]
+    async fn unanswered_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+
+                    while !request.ends_with(b"}") {
+                        let len = stream.read(&mut buf).await.unwrap();
+                        request.extend(&buf[..len]);
+                    }
+
+                    if String::from_utf8_lossy(&request).contains("\"slow\"") {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                    }
+
+                    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n";
+                    let _ = stream.write_all(head.as_bytes()).await;
+                });
+            }
+        });
+
+        let mut transport = StreamableHttpTransport::new(format!("http://{addr}/mcp")).unwrap();
+        let request = |id, method| JsonRPCMessageBuilder::new().with_id(id).with_method(method).build();
+
+        timeout(Duration::from_secs(5), transport.send(request(1, "slow")))
+            .await
+            .unwrap()
+            .unwrap();
+
+        transport.send(request(2, "ping")).await.unwrap();
+
+        match timeout(Duration::from_secs(5), transport.recv()).await.unwrap() {
+            Err(Error::RequestFailed { id: 2, .. }) => {}
+            res => panic!("{res:?}"),
+        }
+
+        transport.close().await.unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use tokio::io::{self, AsyncRead, AsyncWrite, AsyncWriteExt, BufWriter, Stdin, Stdout};
+
+use crate::{
+    codec::{LineReader, write_line},
+    error::Result,
+    json_rpc::JsonRPCMessage,
+    transport::{Transport, parse_message},
+};
+
+//
+// Newline delimited JSON over any pair of streams, stdio being the usual one
+//
+pub struct IoTransport<R, W> {
+    reader: LineReader<R>,
+    writer: BufWriter<W>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl IoTransport<Stdin, Stdout> {
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+}
+
+impl<R, W> IoTransport<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader: LineReader::new(reader),
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    //
+    // longer lines are skipped, recv() fails with MessageTooLarge for them
+    //
+    pub fn with_max_message_size(mut self, max: usize) -> Self {
+        self.reader = self.reader.with_max_line_length(max);
+        self
+    }
+}
+
+#[async_trait]
+impl<R, W> Transport for IoTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        write_line(&mut self.writer, &msg).await
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        let line = self.reader.read_line().await?;
+        parse_message(&line)
+    }
+    async fn close(&mut self) -> Result<()> {
+        self.writer.shutdown().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+    transport::Transport,
+};
+
+//
+// One end of an in-process channel pair, messages are handed over as is
+//
+pub struct MemoryTransport {
+    tx: Option<mpsc::UnboundedSender<JsonRPCMessage>>,
+    rx: mpsc::UnboundedReceiver<JsonRPCMessage>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl MemoryTransport {
+    //
+    // what's sent on one end is received on the other
+    //
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, b_rx) = mpsc::unbounded_channel();
+        let (b_tx, a_rx) = mpsc::unbounded_channel();
+
+        let a = Self {
+            tx: Some(a_tx),
+            rx: a_rx,
+        };
+
+        let b = Self {
+            tx: Some(b_tx),
+            rx: b_rx,
+        };
+
+        (a, b)
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        match &self.tx {
+            Some(tx) => tx.send(msg).map_err(|_| Error::EventSendFailure),
+            None => Err(Error::NotConnected),
+        }
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        self.rx.recv().await.ok_or(Error::Eof)
+    }
+    async fn close(&mut self) -> Result<()> {
+        //
+        // the other end gets Eof once it drained what was sent
+        //
+        self.tx.take();
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+pub mod child;
+pub mod http;
+pub mod io;
+pub mod memory;
+pub mod session;
+pub mod sse;
+#[cfg(unix)]
+pub mod unix;
+pub mod ws;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::{JSON_RPC_INVALID_REQUEST, JSON_RPC_PARSE_ERROR, JsonRPCMessage, JsonRPCMessageBuilder},
+    types::MaybeSend,
+};
+
+//
+// Moves JSON-RPC messages in and out, framing only. Requests, responses and
+// notifications all look the same from here, McpSession makes sense of them.
+// recv() is raced against send() with select! so it has to be cancel safe,
+// Error::Eof once the other side is gone. InvalidMessage and MessageTooLarge
+// leave the connection usable, the caller answers them with error_reply()
+//
+#[async_trait]
+pub trait Transport: MaybeSend {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()>;
+    async fn recv(&mut self) -> Result<JsonRPCMessage>;
+    async fn close(&mut self) -> Result<()>;
+}
+
+#[async_trait]
+impl Transport for Box<dyn Transport> {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        self.as_mut().send(msg).await
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        self.as_mut().recv().await
+    }
+    async fn close(&mut self) -> Result<()> {
+        self.as_mut().close().await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PUBLIC FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+//
+// -32700 when it isn't JSON, -32600 with the id when it's JSON but not a
+// JSON-RPC message
+//
+pub fn parse_message(text: &str) -> Result<JsonRPCMessage> {
+    let value: Value = serde_json::from_str(text).map_err(|e| Error::InvalidMessage {
+        code: JSON_RPC_PARSE_ERROR,
+        id: None,
+        message: e.to_string(),
+    })?;
+
+    let id = value.get("id").and_then(|v| v.as_u64());
+
+    serde_json::from_value(value).map_err(|e| Error::InvalidMessage {
+        code: JSON_RPC_INVALID_REQUEST,
+        id,
+        message: e.to_string(),
+    })
+}
+
+//
+// the answer to a message recv() couldn't make sense of, None when the error
+// means the connection is gone
+//
+pub fn error_reply(e: &Error) -> Option<JsonRPCMessage> {
+    let (code, id, message) = match e {
+        Error::InvalidMessage { code, id, message } => (*code, *id, message.clone()),
+        Error::MessageTooLarge => (JSON_RPC_INVALID_REQUEST, None, "message too large".to_string()),
+        _ => return None,
+    };
+
+    let mut builder = JsonRPCMessageBuilder::new();
+
+    if let Some(id) = id {
+        builder = builder.with_id(id);
+    }
+
+    Some(builder.with_error(code, message).build())
+}
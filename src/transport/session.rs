@@ -0,0 +1,375 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use log::{debug, error, warn};
+use serde_json::{Map, Value, json};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::{Error, Result},
+    json_rpc::{JSON_RPC_METHOD_NOT_FOUND, JsonRPCInitParams, JsonRPCMessage, JsonRPCMessageBuilder},
+    server::context::ServerPeer,
+    transport::{Transport, error_reply},
+    types::{McpParams, McpTool},
+};
+
+//
+// The protocol side of a connection, over any Transport. Our requests are
+// matched with their responses, everything the other side sends on its own
+// (requests and notifications) goes to the incoming queue when there's one.
+// Without it requests are answered with method not found
+//
+pub struct McpSession {
+    peer: Arc<ServerPeer>,
+    tools_changed: Arc<AtomicBool>,
+    shutdown: CancellationToken,
+}
+
+struct Pump<T> {
+    transport: T,
+    peer: Arc<ServerPeer>,
+    outgoing: mpsc::UnboundedReceiver<JsonRPCMessage>,
+    incoming: Option<mpsc::UnboundedSender<JsonRPCMessage>>,
+    tools_changed: Arc<AtomicBool>,
+    shutdown: CancellationToken,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl<T> Pump<T>
+where
+    T: Transport,
+{
+    async fn run(mut self) {
+        loop {
+            tokio::select! {
+                msg = self.transport.recv() => {
+                    match msg {
+                        Ok(msg) => self.dispatch(msg),
+                        Err(Error::RequestFailed { id, error }) => {
+                            error!("{error}");
+                            self.peer.fail(id, *error);
+                        }
+                        Err(e) => match error_reply(&e) {
+                            Some(res) => {
+                                warn!("{e}");
+
+                                if self.send(res).await.is_err() {
+                                    break
+                                }
+                            }
+                            None => {
+                                debug!("{e}");
+                                break
+                            }
+                        },
+                    }
+                }
+                Some(msg) = self.outgoing.recv() => {
+                    if self.send(msg).await.is_err() {
+                        break
+                    }
+                }
+                _ = self.shutdown.cancelled() => break,
+            }
+        }
+
+        //
+        // nothing gets queued from now on, then whoever is still waiting for
+        // an answer is woken up
+        //
+        self.outgoing.close();
+        self.shutdown.cancel();
+        self.peer.drop_pending();
+
+        if let Err(e) = self.transport.close().await {
+            debug!("{e}");
+        }
+    }
+
+    //
+    // a request the other side turned down (e.g. a POST answered with 403)
+    // fails on its own, anything else means the connection is gone
+    //
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        let request = match (msg.id, msg.method.is_some()) {
+            (Some(id), true) => Some(id),
+            _ => None,
+        };
+
+        let e = match self.transport.send(msg).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        error!("{e}");
+
+        match (request, e) {
+            (Some(id), e @ (Error::Unauthorized | Error::ConnectionFailure | Error::Reqwest(_))) => {
+                self.peer.fail(id, e);
+                Ok(())
+            }
+            (_, e) => Err(e),
+        }
+    }
+
+    fn dispatch(&mut self, msg: JsonRPCMessage) {
+        match (msg.id, msg.method.as_deref()) {
+            (Some(id), None) => {
+                if !self.peer.complete(msg) {
+                    warn!("unexpected response {id}");
+                }
+                return;
+            }
+            (None, Some("notifications/tools/list_changed")) => {
+                self.tools_changed.store(true, Ordering::Relaxed);
+            }
+            (None, Some(method)) => debug!("notification: {method}"),
+            (Some(_), Some(_)) => {}
+            (None, None) => {
+                warn!("ignoring message without a method");
+                return;
+            }
+        }
+
+        let msg = match &self.incoming {
+            Some(incoming) => match incoming.send(msg) {
+                Ok(()) => return,
+                Err(e) => e.0,
+            },
+            None => msg,
+        };
+
+        if let (Some(id), Some(method)) = (msg.id, msg.method) {
+            let res = JsonRPCMessageBuilder::new()
+                .with_id(id)
+                .with_error(JSON_RPC_METHOD_NOT_FOUND, format!("{method} not supported"))
+                .build();
+
+            if let Err(e) = self.peer.send(res) {
+                error!("{e}");
+            }
+        }
+    }
+}
+
+impl McpSession {
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: Transport + 'static,
+    {
+        Self::start(transport, None)
+    }
+
+    //
+    // requests and notifications from the other side are read from the
+    // receiver, requests are answered with respond()
+    //
+    pub fn with_incoming<T>(transport: T) -> (Self, mpsc::UnboundedReceiver<JsonRPCMessage>)
+    where
+        T: Transport + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self::start(transport, Some(tx)), rx)
+    }
+
+    fn start<T>(transport: T, incoming: Option<mpsc::UnboundedSender<JsonRPCMessage>>) -> Self
+    where
+        T: Transport + 'static,
+    {
+        let (tx, outgoing) = mpsc::unbounded_channel();
+
+        let session = Self {
+            peer: Arc::new(ServerPeer::new(tx)),
+            tools_changed: Arc::new(AtomicBool::new(false)),
+            shutdown: CancellationToken::new(),
+        };
+
+        let pump = Pump {
+            transport,
+            peer: session.peer.clone(),
+            outgoing,
+            incoming,
+            tools_changed: session.tools_changed.clone(),
+            shutdown: session.shutdown.clone(),
+        };
+
+        tokio::spawn(pump.run());
+        session
+    }
+
+    //
+    // JsonRpcError when the other side answered with an error, NotConnected
+    // when the connection went away first. The transport's error when the
+    // request couldn't be sent
+    //
+    pub async fn request<S>(&self, method: S, params: Value) -> Result<Map<String, Value>>
+    where
+        S: AsRef<str>,
+    {
+        if self.is_closed() {
+            return Err(Error::NotConnected);
+        }
+
+        match self.peer.request(method, params).await {
+            Err(Error::EventSendFailure) => Err(Error::NotConnected),
+            res => res,
+        }
+    }
+
+    pub fn notify<S>(&self, method: S, params: Value) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.peer.notify(method, params)
+    }
+
+    //
+    // answer to one of the incoming requests
+    //
+    pub fn respond(&self, msg: JsonRPCMessage) -> Result<()> {
+        self.peer.send(msg)
+    }
+
+    //
+    // incoming requests the other side can cancel with notifications/cancelled
+    //
+    pub(crate) fn start_call(&self, request_id: u64) -> CancellationToken {
+        self.peer.start_call(request_id)
+    }
+
+    pub(crate) fn end_call(&self, request_id: u64) {
+        self.peer.end_call(request_id)
+    }
+
+    pub(crate) fn cancel_call(&self, request_id: u64) {
+        self.peer.cancel_call(request_id)
+    }
+
+    //
+    // client side handshake, the server's initialize result
+    //
+    pub async fn initialize(&self) -> Result<Map<String, Value>> {
+        let res = self.request("initialize", json!(JsonRPCInitParams::new())).await?;
+        self.notify("notifications/initialized", Value::Null)?;
+        Ok(res)
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>> {
+        let mut res = self.request("tools/list", json!({})).await?;
+        let tools = res.remove("tools").ok_or(Error::NotFound)?;
+        Ok(serde_json::from_value(tools)?)
+    }
+
+    //
+    // the result formatted the way SseClient does it
+    //
+    pub async fn call_tool(&self, params: &McpParams) -> Result<String> {
+        let res = self.request("tools/call", json!(params)).await?;
+        Ok(serde_json::to_string_pretty(&res)?)
+    }
+
+    //
+    // true once after notifications/tools/list_changed
+    //
+    pub fn take_tools_changed(&self) -> bool {
+        self.tools_changed.swap(false, Ordering::Relaxed)
+    }
+
+    //
+    // closed by us or by the other side
+    //
+    pub fn is_closed(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    //
+    // pending requests fail with NotConnected, the transport is closed in the
+    // background
+    //
+    pub fn close(&self) {
+        self.shutdown.cancel();
+        self.peer.drop_pending();
+    }
+}
+
+impl Drop for McpSession {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    use crate::{
+        error::{Error, Result},
+        json_rpc::JsonRPCMessageBuilder,
+        server::{context::ToolContext, matrix::OmcpServer},
+        transport::{memory::MemoryTransport, session::McpSession},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct RootsTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for RootsTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            let roots = ctx.list_roots().await?;
+            Ok(format!("{} roots", roots.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn both_directions() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("roots", RootsTool {});
+
+        let (local, remote) = MemoryTransport::pair();
+        tokio::spawn(async move { server.serve_transport(remote).await });
+
+        let (session, mut incoming) = McpSession::with_incoming(local);
+
+        let params = json!({"protocolVersion": "2025-06-18", "capabilities": {"roots": {}}, "clientInfo": {"name": "t", "version": "1"}});
+        let res = session.request("initialize", params).await.unwrap();
+        assert!(res.contains_key("capabilities"));
+        session.notify("notifications/initialized", json!(null)).unwrap();
+
+        //
+        // the tool asks us for the roots while we wait for its result
+        //
+        let answer = async {
+            let req = incoming.recv().await.unwrap();
+            assert_eq!(req.method.as_deref(), Some("roots/list"));
+
+            let mut result = std::collections::HashMap::new();
+            result.insert("roots".to_string(), json!([{"uri": "file:///a"}, {"uri": "file:///b"}]));
+
+            let res = JsonRPCMessageBuilder::new()
+                .with_id(req.id.unwrap())
+                .with_result(result)
+                .build();
+
+            session.respond(res).unwrap();
+        };
+
+        let params = McpParams::new("roots");
+        let (res, _) = tokio::join!(session.call_tool(&params), answer);
+        assert!(res.unwrap().contains("2 roots"));
+
+        session.close();
+        assert!(session.is_closed());
+        assert!(matches!(session.list_tools().await, Err(Error::NotConnected)));
+    }
+}
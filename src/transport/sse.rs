@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::{debug, error};
+use reqwest::{
+    Client, RequestBuilder, Response, StatusCode,
+    header::{HeaderMap, WWW_AUTHENTICATE},
+};
+
+use crate::{
+    client::{builder::OMcpClientBuilder, credentials::CredentialProvider, sse::SseReader, types::SseEvent},
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+    transport::Transport,
+};
+
+//
+// What every new SseTransport starts from, SseClient keeps one around to
+// reconnect
+//
+#[derive(Clone)]
+pub(crate) struct SseConnector {
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+}
+
+//
+// Client side of the HTTP+SSE transport, messages come in on the GET stream
+// and go out as POSTs to the endpoint the server announced
+//
+pub struct SseTransport {
+    connector: SseConnector,
+    endpoint: String,
+    reader: Option<SseReader>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+
+//
+// a 401 here is what's left after the credentials got their retry
+//
+fn check_status(url: &str, response: &Response) -> Result<()> {
+    match response.status() {
+        s if s.is_success() => Ok(()),
+        StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
+        s => {
+            error!("{url} returned {s}");
+            Err(Error::ConnectionFailure)
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl SseConnector {
+    pub(crate) fn from_builder(mut builder: OMcpClientBuilder) -> Result<Self> {
+        let client = builder.http_client()?;
+        let credentials = builder.credential_provider(&client);
+
+        Ok(Self {
+            client,
+            url: builder.url,
+            headers: builder.headers,
+            credentials,
+        })
+    }
+
+    async fn request_headers(&self) -> Result<HeaderMap> {
+        let mut headers = self.headers.clone();
+
+        if let Some(credentials) = &self.credentials {
+            headers.extend(credentials.headers().await?);
+        }
+
+        Ok(headers)
+    }
+
+    //
+    // after a 401 the credentials get refreshed and the request is sent again,
+    // only once
+    //
+    async fn send_request<F>(&self, request: F) -> Result<Response>
+    where
+        F: Fn(HeaderMap) -> RequestBuilder,
+    {
+        let res = request(self.request_headers().await?).send().await?;
+
+        let credentials = match (&self.credentials, res.status()) {
+            (Some(c), StatusCode::UNAUTHORIZED) => c,
+            _ => return Ok(res),
+        };
+
+        let challenge = res
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+
+        if !credentials.refresh(challenge.as_deref()).await? {
+            return Ok(res);
+        }
+
+        Ok(request(self.request_headers().await?).send().await?)
+    }
+
+    //
+    // GET and wait for the endpoint, the initialize handshake is up to the
+    // session
+    //
+    pub(crate) async fn connect(&self) -> Result<SseTransport> {
+        let response = self.send_request(|headers| self.client.get(&self.url).headers(headers)).await?;
+
+        check_status(&self.url, &response)?;
+
+        let mut reader = SseReader::new(&self.url, response);
+
+        let endpoint = loop {
+            match reader.next_event().await? {
+                SseEvent::Endpoint(e) => break e.url,
+                SseEvent::JsonRpcMessage(msg) => debug!("ignoring {msg:?}"),
+            }
+        };
+
+        Ok(SseTransport {
+            connector: self.clone(),
+            endpoint,
+            reader: Some(reader),
+        })
+    }
+}
+
+impl SseTransport {
+    //
+    // headers, credentials, TLS and proxy settings come from the builder
+    //
+    pub async fn connect(builder: OMcpClientBuilder) -> Result<Self> {
+        SseConnector::from_builder(builder)?.connect().await
+    }
+}
+
+#[async_trait]
+impl Transport for SseTransport {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        let body = serde_json::to_string(&msg)?;
+        let connector = &self.connector;
+
+        debug!("sending: {body}");
+
+        //
+        // every message is a POST of its own, the answer comes on the stream
+        //
+        let response = connector
+            .send_request(|headers| {
+                connector
+                    .client
+                    .post(&self.endpoint)
+                    .header("Content-Type", "application/json")
+                    .headers(headers)
+                    .body(body.clone())
+            })
+            .await?;
+
+        check_status(&self.endpoint, &response)
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        let reader = self.reader.as_mut().ok_or(Error::Eof)?;
+
+        loop {
+            match reader.next_event().await? {
+                SseEvent::JsonRpcMessage(msg) => return Ok(*msg),
+                SseEvent::Endpoint(e) => debug!("ignoring endpoint {}", e.url),
+            }
+        }
+    }
+    async fn close(&mut self) -> Result<()> {
+        //
+        // dropping the GET stream is what ends the session server side
+        //
+        self.reader.take();
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, types::OMcpServerType},
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer},
+        transport::{session::McpSession, sse::SseTransport},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct EchoTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for EchoTool {
+        type Error = Error;
+
+        async fn call(&self, _ctx: &ToolContext, params: &McpParams) -> Result<String> {
+            Ok(format!("echo {}", params.tool_name))
+        }
+    }
+
+    #[tokio::test]
+    async fn session_over_sse() {
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("echo", EchoTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.sse_serve(listener).await });
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::Sse).with_sse_url(format!("http://{addr}/sse"));
+        let session = McpSession::new(SseTransport::connect(builder).await.unwrap());
+
+        session.initialize().await.unwrap();
+        assert_eq!(session.list_tools().await.unwrap().len(), 1);
+
+        let res = session.call_tool(&McpParams::new("echo")).await.unwrap();
+        assert!(res.contains("echo echo"));
+    }
+
+    //
+    // the stream is fine, every POST gets turned down with status
+    //
+    async fn rejecting_server(status: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+
+                    while !request.ends_with(b"\r\n\r\n") {
+                        request.push(stream.read_u8().await.unwrap());
+                    }
+
+                    if request.starts_with(b"GET") {
+                        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\r\n";
+                        stream.write_all(head.as_bytes()).await.unwrap();
+                        stream.write_all(b"event: endpoint\ndata: /messages\n\n").await.unwrap();
+
+                        //
+                        // keep the stream open
+                        //
+                        let _ = stream.read_u8().await;
+                    } else {
+                        let head = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+                        stream.write_all(head.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        format!("http://{addr}/sse")
+    }
+
+    #[tokio::test]
+    async fn post_rejected() {
+        let url = rejecting_server("403 Forbidden").await;
+        let builder = OMcpClientBuilder::new(OMcpServerType::Sse).with_sse_url(url);
+        let session = McpSession::new(SseTransport::connect(builder).await.unwrap());
+
+        assert!(matches!(session.initialize().await, Err(Error::ConnectionFailure)));
+
+        let url = rejecting_server("401 Unauthorized").await;
+        let builder = OMcpClientBuilder::new(OMcpServerType::Sse).with_sse_url(url);
+        let session = McpSession::new(SseTransport::connect(builder).await.unwrap());
+
+        assert!(matches!(session.list_tools().await, Err(Error::Unauthorized)));
+    }
+}
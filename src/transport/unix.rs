@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use tokio::net::{
+    UnixStream,
+    unix::{OwnedReadHalf, OwnedWriteHalf},
+};
+
+use crate::{
+    error::Result,
+    json_rpc::JsonRPCMessage,
+    transport::{Transport, io::IoTransport},
+};
+
+//
+// Newline delimited JSON over a Unix domain socket, what
+// OmcpServer::unix_loop() listens on
+//
+pub struct UnixTransport {
+    io: IoTransport<OwnedReadHalf, OwnedWriteHalf>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl UnixTransport {
+    pub async fn connect<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let (reader, writer) = UnixStream::connect(path).await?.into_split();
+
+        Ok(Self {
+            io: IoTransport::new(reader, writer),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for UnixTransport {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        self.io.send(msg).await
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        self.io.recv().await
+    }
+    async fn close(&mut self) -> Result<()> {
+        self.io.close().await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::PermissionsExt, time::Duration};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        error::{Error, Result},
+        server::{context::ToolContext, matrix::OmcpServer, unix::UnixServerOptions},
+        transport::{session::McpSession, unix::UnixTransport},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct UidTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for UidTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            let peer = ctx.peer_credentials().ok_or(Error::Unauthorized)?;
+            Ok(format!("uid {}", peer.uid))
+        }
+    }
+
+    #[tokio::test]
+    async fn session_over_unix() {
+        let dir = std::env::temp_dir().join(format!("omcp-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("mcp.sock");
+        let denied = dir.join("denied.sock");
+
+        //
+        // SAFETY: getuid() has no preconditions and can't fail
+        //
+        let uid = unsafe { libc::getuid() };
+
+        let mut server = OmcpServer::<Error>::new();
+        server.add_tool("uid", UidTool {});
+
+        //
+        // the second socket only lets in a uid that isn't ours
+        //
+        let allowed = UnixServerOptions::new().with_mode(0o600).with_allowed_uid(uid);
+        let others = UnixServerOptions::new().with_allowed_uid(uid.wrapping_add(1));
+
+        let (a, b) = (path.clone(), denied.clone());
+
+        tokio::spawn(async move {
+            let _ = tokio::join!(server.unix_loop(a, allowed), server.unix_loop(b, others));
+        });
+
+        for _ in 0..100 {
+            if path.exists() && denied.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let session = McpSession::new(UnixTransport::connect(&path).await.unwrap());
+        session.initialize().await.unwrap();
+
+        let res = session.call_tool(&McpParams::new("uid")).await.unwrap();
+        assert!(res.contains(&format!("uid {uid}")));
+
+        let session = McpSession::new(UnixTransport::connect(&denied).await.unwrap());
+        assert!(matches!(session.initialize().await, Err(Error::NotConnected)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,476 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, warn};
+use reqwest::{
+    StatusCode, Url,
+    header::{HeaderMap, HeaderValue, SEC_WEBSOCKET_PROTOCOL, USER_AGENT, WWW_AUTHENTICATE},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpStream,
+    time::{Instant, Interval, MissedTickBehavior, interval_at},
+};
+use tokio_tungstenite::{
+    Connector, MaybeTlsStream, WebSocketStream, client_async_tls_with_config, connect_async_tls_with_config,
+    tungstenite::{self, Message, client::IntoClientRequest},
+};
+
+use crate::{
+    client::{builder::OMcpClientBuilder, credentials::CredentialProvider},
+    codec::read_head,
+    error::{Error, Result},
+    json_rpc::JsonRPCMessage,
+    transport::{Transport, parse_message},
+};
+
+pub const MCP_SUBPROTOCOL: &str = "mcp";
+
+pub(crate) const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+const MAX_PROXY_RESPONSE: usize = 16 * 1024;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+//
+// Pings every interval, the connection is dead when nothing at all came in
+// between two pings. A zero interval turns it off
+//
+pub(crate) struct Keepalive {
+    ticks: Option<Interval>,
+    waiting: bool,
+}
+
+//
+// Everything needed to open the socket again, WsClient reconnects with it
+//
+#[derive(Clone)]
+pub(crate) struct WsConnector {
+    url: String,
+    headers: HeaderMap,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+    tls: native_tls::TlsConnector,
+    proxy: Option<Url>,
+    user_agent: HeaderValue,
+    ping_interval: Duration,
+}
+
+//
+// Client side of the WebSocket transport, one JSON-RPC message per text frame
+// on a socket negotiated with the mcp subprotocol
+//
+pub struct WebSocketTransport {
+    socket: Socket,
+    keepalive: Keepalive,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl Keepalive {
+    pub(crate) fn new(period: Duration) -> Self {
+        let ticks = (!period.is_zero()).then(|| {
+            let mut ticks = interval_at(Instant::now() + period, period);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticks
+        });
+
+        Self { ticks, waiting: false }
+    }
+
+    pub(crate) fn seen(&mut self) {
+        self.waiting = false;
+    }
+
+    //
+    // resolves when a ping is due, ConnectionFailure when the last one went
+    // unanswered. Cancel safe
+    //
+    pub(crate) async fn tick(&mut self) -> Result<()> {
+        match self.ticks.as_mut() {
+            Some(ticks) => ticks.tick().await,
+            None => std::future::pending().await,
+        };
+
+        if self.waiting {
+            warn!("no pong, giving up on the connection");
+            return Err(Error::ConnectionFailure);
+        }
+
+        self.waiting = true;
+        Ok(())
+    }
+}
+
+impl WsConnector {
+    //
+    // headers, credentials, TLS, proxy and user agent are the same ones the
+    // SSE client uses, the http client is only used by the OAuth flow. The
+    // handshake goes through an http:// proxy with CONNECT, a proxy set
+    // straight on HttpOptions rather than with with_proxy can't be reached
+    //
+    pub(crate) fn from_builder(mut builder: OMcpClientBuilder) -> Result<Self> {
+        if builder.http.proxy.is_some() && builder.http.proxy_url.is_none() {
+            return Err(Error::UnsupportedTransport {
+                name: "ws through a proxy without a url".into(),
+            });
+        }
+
+        let url = Url::parse(&builder.url).map_err(|_| Error::InvalidEndpoint)?;
+        let proxy = builder.http.ws_proxy(&url, |name| std::env::var(name).ok());
+
+        if let Some(proxy) = proxy.as_ref().filter(|v| v.scheme() != "http") {
+            return Err(Error::UnsupportedTransport {
+                name: format!("ws through a {} proxy", proxy.scheme()),
+            });
+        }
+
+        let client = builder.http_client()?;
+        let credentials = builder.credential_provider(&client);
+
+        Ok(Self {
+            tls: builder.tls.connector()?,
+            user_agent: HeaderValue::from_str(&builder.http.user_agent)?,
+            url: builder.url,
+            headers: builder.headers,
+            credentials,
+            proxy,
+            ping_interval: builder.ws.ping_interval,
+        })
+    }
+
+    //
+    // CONNECT host:port through the proxy, the socket is a plain tunnel to
+    // the server once the proxy answers 200
+    //
+    async fn tunnel(&self, proxy: &Url) -> Result<TcpStream> {
+        let url = Url::parse(&self.url).map_err(|_| Error::InvalidEndpoint)?;
+        let host = url.host_str().ok_or(Error::InvalidEndpoint)?;
+        let port = url.port_or_known_default().ok_or(Error::InvalidEndpoint)?;
+
+        let proxy_host = proxy.host_str().ok_or(Error::InvalidEndpoint)?;
+        let proxy_port = proxy.port_or_known_default().ok_or(Error::InvalidEndpoint)?;
+
+        let mut stream = TcpStream::connect((proxy_host, proxy_port)).await?;
+
+        let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+
+        if let Ok(user_agent) = self.user_agent.to_str() {
+            request.push_str(&format!("User-Agent: {user_agent}\r\n"));
+        }
+
+        if !proxy.username().is_empty() {
+            let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or_default());
+            request.push_str(&format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                STANDARD.encode(credentials)
+            ));
+        }
+
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        let head = read_head(&mut stream, MAX_PROXY_RESPONSE).await?;
+        let status = head.split_whitespace().nth(1);
+
+        match status {
+            Some("200") => Ok(stream),
+            Some("407") => Err(Error::Unauthorized),
+            _ => {
+                warn!("proxy refused the tunnel: {}", head.lines().next().unwrap_or_default());
+                Err(Error::ConnectionFailure)
+            }
+        }
+    }
+
+    async fn handshake(&self) -> Result<core::result::Result<Socket, tungstenite::Error>> {
+        let mut request = self.url.as_str().into_client_request()?;
+        let headers = request.headers_mut();
+
+        headers.insert(USER_AGENT, self.user_agent.clone());
+        headers.extend(self.headers.clone());
+
+        if let Some(credentials) = &self.credentials {
+            headers.extend(credentials.headers().await?);
+        }
+
+        headers.insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(MCP_SUBPROTOCOL));
+
+        let connector = Connector::NativeTls(self.tls.clone());
+        let res = match &self.proxy {
+            Some(proxy) => {
+                let stream = self.tunnel(proxy).await?;
+                client_async_tls_with_config(request, stream, None, Some(connector)).await
+            }
+            None => connect_async_tls_with_config(request, None, false, Some(connector)).await,
+        };
+
+        Ok(res.map(|(socket, _)| socket))
+    }
+
+    //
+    // one retry after refreshing the credentials, same as SseClient
+    //
+    pub(crate) async fn connect(&self) -> Result<WebSocketTransport> {
+        let res = match self.handshake().await? {
+            Err(tungstenite::Error::Http(res)) if res.status() == StatusCode::UNAUTHORIZED => res,
+            res => return Ok(WebSocketTransport::new(res?, self.ping_interval)),
+        };
+
+        let credentials = match &self.credentials {
+            Some(v) => v,
+            None => return Err(Error::Unauthorized),
+        };
+
+        let challenge = res.headers().get(WWW_AUTHENTICATE).and_then(|v| v.to_str().ok());
+
+        if !credentials.refresh(challenge).await? {
+            return Err(Error::Unauthorized);
+        }
+
+        let socket = self.handshake().await??;
+        Ok(WebSocketTransport::new(socket, self.ping_interval))
+    }
+}
+
+impl WebSocketTransport {
+    //
+    // url, headers, credentials and ping interval come from the builder
+    //
+    pub async fn connect(builder: OMcpClientBuilder) -> Result<Self> {
+        WsConnector::from_builder(builder)?.connect().await
+    }
+
+    fn new(socket: Socket, ping_interval: Duration) -> Self {
+        Self {
+            socket,
+            keepalive: Keepalive::new(ping_interval),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn send(&mut self, msg: JsonRPCMessage) -> Result<()> {
+        let text = serde_json::to_string(&msg)?;
+        self.socket.send(Message::text(text)).await?;
+        Ok(())
+    }
+    async fn recv(&mut self) -> Result<JsonRPCMessage> {
+        loop {
+            tokio::select! {
+                frame = self.socket.next() => {
+                    self.keepalive.seen();
+
+                    match frame {
+                        Some(Ok(Message::Text(text))) => return parse_message(&text),
+                        Some(Ok(Message::Binary(_))) => warn!("ignoring binary frame"),
+                        Some(Ok(Message::Close(_))) | None => return Err(Error::Eof),
+                        Some(Ok(_)) => {}
+                        Some(Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed)) => {
+                            return Err(Error::Eof);
+                        }
+                        Some(Err(e)) => return Err(e.into()),
+                    }
+                }
+                res = self.keepalive.tick() => {
+                    res?;
+                    self.socket.send(Message::Ping(Default::default())).await?;
+                }
+            }
+        }
+    }
+    async fn close(&mut self) -> Result<()> {
+        if let Err(e) = self.socket.close(None).await {
+            debug!("{e}");
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use reqwest::header::{HeaderValue, SEC_WEBSOCKET_PROTOCOL, USER_AGENT};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt, copy_bidirectional},
+        net::{TcpListener, TcpStream},
+        sync::oneshot,
+    };
+    use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+
+    use crate::{
+        client::{builder::OMcpClientBuilder, types::OMcpServerType},
+        codec::read_head,
+        error::{Error, Result},
+        server::{
+            auth::{BearerAuthenticator, Principal},
+            context::ToolContext,
+            matrix::OmcpServer,
+            ws::WsServerOptions,
+        },
+        test::utils::tls::{CA_PATH, tls_listener},
+        transport::{session::McpSession, ws::WebSocketTransport},
+        types::{BakedMcpToolTrait, McpParams},
+    };
+
+    struct WhoamiTool {}
+
+    #[async_trait]
+    impl BakedMcpToolTrait for WhoamiTool {
+        type Error = Error;
+
+        async fn call(&self, ctx: &ToolContext, _params: &McpParams) -> Result<String> {
+            ctx.principal().map(|p| p.subject.clone()).ok_or(Error::Unauthorized)
+        }
+    }
+
+    #[tokio::test]
+    async fn session_over_ws() {
+        let auth = BearerAuthenticator::new().with_token("secret", Principal::new("alice"));
+
+        let mut server = OmcpServer::<Error>::new().with_authenticator(auth);
+        server.add_tool("whoami", WhoamiTool {});
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.ws_serve(listener, WsServerOptions::new()).await });
+
+        let url = format!("ws://{addr}/ws");
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket).with_ws_url(&url);
+        let res = WebSocketTransport::connect(builder).await;
+        assert!(matches!(res, Err(Error::Unauthorized)));
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket)
+            .with_ws_url(&url)
+            .with_sse_bearer("secret")
+            .unwrap();
+
+        let session = McpSession::new(WebSocketTransport::connect(builder).await.unwrap());
+
+        session.initialize().await.unwrap();
+        assert_eq!(session.list_tools().await.unwrap()[0].name, "whoami");
+
+        let res = session.call_tool(&McpParams::new("whoami")).await.unwrap();
+        assert!(res.contains("alice"));
+
+        session.close();
+    }
+
+    //
+    // the error type is tungstenite's
+    //
+    #[allow(clippy::result_large_err)]
+    fn subprotocol(_: &Request, mut res: Response) -> core::result::Result<Response, ErrorResponse> {
+        res.headers_mut()
+            .insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("mcp"));
+        Ok(res)
+    }
+
+    #[tokio::test]
+    async fn wss_custom_root() {
+        let (listener, acceptor) = tls_listener().await;
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+
+                tokio::spawn(async move {
+                    //
+                    // the client without the CA gives up during the TLS
+                    // handshake
+                    //
+                    let stream = match acceptor.accept(stream).await {
+                        Ok(v) => v,
+                        Err(_) => return,
+                    };
+
+                    let mut socket = tokio_tungstenite::accept_hdr_async(stream, subprotocol).await.unwrap();
+                    let _ = socket.get_mut().read_u8().await;
+                });
+            }
+        });
+
+        let url = format!("wss://localhost:{port}/ws");
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket).with_ws_url(&url);
+        assert!(WebSocketTransport::connect(builder).await.is_err());
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket)
+            .with_ws_url(&url)
+            .with_root_certificate(CA_PATH)
+            .unwrap();
+        assert!(WebSocketTransport::connect(builder).await.is_ok());
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket)
+            .with_ws_url(&url)
+            .with_proxy("socks5://127.0.0.1:1080")
+            .unwrap();
+        let res = WebSocketTransport::connect(builder).await;
+        assert!(matches!(res, Err(Error::UnsupportedTransport { .. })));
+    }
+
+    #[tokio::test]
+    async fn ws_through_proxy() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let (agent_tx, agent_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = server.accept().await.unwrap();
+
+            #[allow(clippy::result_large_err)]
+            let callback = |req: &Request, res: Response| {
+                let agent = req.headers().get(USER_AGENT).map(|v| v.to_str().unwrap().to_string());
+                let _ = agent_tx.send(agent);
+                subprotocol(req, res)
+            };
+
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, callback).await.unwrap();
+            let _ = socket.get_mut().read_u8().await;
+        });
+
+        //
+        // answers CONNECT and then just copies bytes both ways
+        //
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let (connect_tx, connect_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = proxy.accept().await.unwrap();
+            let head = read_head(&mut stream, 4096).await.unwrap();
+
+            let target = head.split_whitespace().nth(1).unwrap().to_string();
+            let _ = connect_tx.send(head);
+
+            let mut upstream = TcpStream::connect(target).await.unwrap();
+            stream.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await.unwrap();
+
+            let _ = copy_bidirectional(&mut stream, &mut upstream).await;
+        });
+
+        let builder = OMcpClientBuilder::new(OMcpServerType::WebSocket)
+            .with_ws_url(format!("ws://{server_addr}/ws"))
+            .with_proxy(format!("http://user:pass@{proxy_addr}"))
+            .unwrap()
+            .with_user_agent("omcp-test");
+
+        WebSocketTransport::connect(builder).await.unwrap();
+
+        let head = connect_rx.await.unwrap();
+        assert!(head.starts_with(&format!("CONNECT {server_addr} HTTP/1.1\r\n")));
+        assert!(head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+
+        assert_eq!(agent_rx.await.unwrap().as_deref(), Some("omcp-test"));
+    }
+}
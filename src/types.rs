@@ -7,13 +7,42 @@ use serde_json::{Map, Value};
 use crate::{
     error::{Error, Result},
     json_rpc::JsonRPCParameters,
+    server::context::ToolContext,
 };
 
-#[async_trait(?Send)]
-pub trait BakedMcpToolTrait {
+//
+// clients and tools are Send + Sync so they can be shared across tasks, wasm32
+// futures aren't Send so the bound goes away there
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync> MaybeSendSync for T {}
+
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSendSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSendSync for T {}
+
+//
+// for what only ever gets moved into a task, never shared
+//
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait BakedMcpToolTrait: MaybeSendSync {
     type Error;
 
-    async fn call(&mut self, params: &McpParams) -> core::result::Result<String, Self::Error>;
+    async fn call(&self, ctx: &ToolContext, params: &McpParams) -> core::result::Result<String, Self::Error>;
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -40,7 +69,7 @@ pub type McpArguments = HashMap<String, Value>;
 pub struct McpParams {
     #[serde(rename = "name")]
     pub tool_name: String,
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub arguments: McpArguments,
 }
 
@@ -175,6 +204,7 @@ pub struct McpToolSchema {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct McpTool {
     pub name: String,
+    #[serde(default)]
     pub description: String,
     #[serde(rename(serialize = "input_schema", deserialize = "inputSchema"))]
     pub input_schema: Option<McpToolSchema>,
@@ -0,0 +1,189 @@
+//
+// BrowserClient against a fake fetch(). Runs in node with
+// `wasm-pack test --node --features browser -- --test browser`
+//
+#![cfg(all(target_arch = "wasm32", feature = "browser"))]
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use js_sys::{Promise, Reflect};
+use omcp::{
+    client::{builder::OMcpClientBuilder, types::OMcpServerType},
+    error::Error,
+    types::McpParams,
+};
+use serde_json::{Value, json};
+use wasm_bindgen::{JsValue, closure::Closure};
+use wasm_bindgen_futures::{JsFuture, future_to_promise};
+use wasm_bindgen_test::wasm_bindgen_test;
+use web_sys::{Headers, Request, Response, ResponseInit};
+
+const URL: &str = "http://localhost/mcp";
+const SESSION_ID: &str = "s1";
+
+//
+// Takes the place of the global fetch() until it's dropped. Answers like a
+// Streamable HTTP server and keeps the method of every request it got
+//
+struct MockFetch {
+    original: JsValue,
+    seen: Rc<RefCell<Vec<String>>>,
+    _handler: Closure<dyn FnMut(Request) -> Promise>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PRIVATE FUNCTIONS
+////////////////////////////////////////////////////////////////////////////////
+fn respond(status: u16, content_type: Option<&str>, body: Option<&str>) -> Result<JsValue, JsValue> {
+    let headers = Headers::new()?;
+    headers.set("mcp-session-id", SESSION_ID)?;
+
+    if let Some(v) = content_type {
+        headers.set("content-type", v)?;
+    }
+
+    let init = ResponseInit::new();
+    init.set_status(status);
+    init.set_headers(&headers);
+
+    Ok(Response::new_with_opt_str_and_init(body, &init)?.into())
+}
+
+async fn answer(request: Request, seen: Rc<RefCell<Vec<String>>>) -> Result<JsValue, JsValue> {
+    if request.method() == "DELETE" {
+        seen.borrow_mut().push("DELETE".into());
+        return respond(200, None, None);
+    }
+
+    let body = JsFuture::from(request.text()?).await?.as_string().unwrap_or_default();
+    let msg: Value = serde_json::from_str(&body).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let method = msg["method"].as_str().unwrap_or_default().to_string();
+    seen.borrow_mut().push(method.clone());
+
+    //
+    // everything after initialize has to carry the session
+    //
+    let session = request.headers().get("mcp-session-id")?;
+
+    if method != "initialize" && session.as_deref() != Some(SESSION_ID) {
+        return respond(400, None, None);
+    }
+
+    let id = msg["id"].clone();
+
+    match method.as_str() {
+        "initialize" => {
+            let result = json!({
+                "protocolVersion": "2025-03-26",
+                "capabilities": { "tools": { "listChanged": true } },
+                "serverInfo": { "name": "mock", "version": "0.1" },
+            });
+            let body = json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string();
+            respond(200, Some("application/json"), Some(&body))
+        }
+        "notifications/initialized" => respond(202, None, None),
+        "tools/list" => {
+            //
+            // a notification ahead of the answer on the same stream
+            //
+            let changed = json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" });
+            let tools = json!([{
+                "name": "echo",
+                "inputSchema": { "type": "object", "properties": { "text": { "type": "string" } } },
+            }]);
+            let result = json!({ "jsonrpc": "2.0", "id": id, "result": { "tools": tools } });
+
+            let body =
+                format!("event: message\r\ndata: {changed}\r\n\r\n: keepalive\n\nevent: message\ndata: {result}");
+            respond(200, Some("text/event-stream"), Some(&body))
+        }
+        "tools/call" => {
+            let text = msg["params"]["arguments"]["text"].clone();
+            let result = json!({ "content": [{ "type": "text", "text": text }], "isError": false });
+            let body = json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string();
+            respond(200, Some("application/json"), Some(&body))
+        }
+        _ => respond(400, None, None),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// IMPL
+////////////////////////////////////////////////////////////////////////////////
+impl MockFetch {
+    fn install() -> Self {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let handler = {
+            let seen = seen.clone();
+            Closure::<dyn FnMut(Request) -> Promise>::new(move |request| {
+                future_to_promise(answer(request, seen.clone()))
+            })
+        };
+
+        let global = js_sys::global();
+        let original = Reflect::get(&global, &"fetch".into()).unwrap();
+        Reflect::set(&global, &"fetch".into(), handler.as_ref()).unwrap();
+
+        Self {
+            original,
+            seen,
+            _handler: handler,
+        }
+    }
+
+    fn seen(&self) -> Vec<String> {
+        self.seen.borrow().clone()
+    }
+}
+
+impl Drop for MockFetch {
+    fn drop(&mut self) {
+        let _ = Reflect::set(&js_sys::global(), &"fetch".into(), &self.original);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TEST
+////////////////////////////////////////////////////////////////////////////////
+#[wasm_bindgen_test]
+async fn initialize_list_call() {
+    let fetch = MockFetch::install();
+
+    let mut client = OMcpClientBuilder::new(OMcpServerType::Http).with_http_url(URL).build().unwrap();
+
+    client.connect().await.unwrap();
+
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0].name, "echo");
+    assert!(client.take_tools_changed());
+
+    let params = McpParams {
+        tool_name: "echo".into(),
+        arguments: HashMap::from([("text".to_string(), json!("hello"))]),
+    };
+
+    let res: Value = serde_json::from_str(&client.call(&params).await.unwrap()).unwrap();
+    assert_eq!(res["content"][0]["text"], "hello");
+
+    client.disconnect().await.unwrap();
+
+    assert_eq!(
+        fetch.seen(),
+        ["initialize", "notifications/initialized", "tools/list", "tools/call", "DELETE"]
+    );
+}
+
+#[wasm_bindgen_test]
+async fn unreachable_server() {
+    let mut client = OMcpClientBuilder::new(OMcpServerType::Http)
+        .with_http_url("http://127.0.0.1:9/mcp")
+        .build()
+        .unwrap();
+
+    assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+    assert!(matches!(client.connect().await, Err(Error::Js { .. })));
+    assert!(matches!(client.list_tools().await, Err(Error::NotConnected)));
+}